@@ -3,7 +3,7 @@ use crate::{
     hittable_list::HittableList,
     ray::Ray
 };
-use utils::{interval::Interval, rtweekend::{degree_to_radian, random}};
+use utils::{interval::Interval, rng::Rng, rtweekend::degree_to_radian};
 use vector3::{extension::random_unit_disk, Point3, Vec3};
 
 #[derive(Default)]
@@ -54,6 +54,7 @@ pub struct Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
             aspect_ratio: f64,
             image_width: i32,
@@ -121,12 +122,13 @@ impl Camera {
     pub fn render(&self, world: &HittableList) {
         // Render
         println!("P3\n{} {}\n255", self.image_width, self.image_height);
+        let mut rng = Rng::new(0);
         for j in 0..self.image_height {
             eprint!("\rScanlines remaining: {:<10}\r", self.image_height - j);
             for i in 0..self.image_width {
                 let mut pixel_color = Color::zero();
                 for _ in 0..self.samples_per_pixel {
-                    pixel_color += Camera::ray_color(self.ray(i, j), self.max_depth, world);
+                    pixel_color += Camera::ray_color(self.ray(i, j, &mut rng), self.max_depth, world, &mut rng);
                 }
 
                 write_color(self.pixel_samples_scale * pixel_color);
@@ -135,42 +137,42 @@ impl Camera {
         eprintln!("\r{:<30}", "Down.");
     }
 
-    fn ray(&self, i: i32, j: i32) ->Ray {
+    fn ray(&self, i: i32, j: i32, rng: &mut Rng) ->Ray {
         // Construct a camera ray originating from the defocus disk and directed at a randomly
         // sampled point around the pixel location i, j.
-        let offset = self.sample_square();
+        let offset = self.sample_square(rng);
         let pixel_sample = self.pixel00_loc
             + (i as f64 + offset.x) * self.pixel_delta_u
             + (j as f64 + offset.y) * self.pixel_delta_v;
         let ray_origin = if self.defocus_angle <= 0. {
             self.center
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(rng)
         };
         let ray_direction = pixel_sample - ray_origin;
 
         Ray::new(ray_origin, ray_direction)
     }
 
-    fn sample_square(&self) -> Vec3 {
+    fn sample_square(&self, rng: &mut Rng) -> Vec3 {
         // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
-        Vec3::new(random() - 0.5, random() - 0.5, 0.)
+        Vec3::new(rng.random() - 0.5, rng.random() - 0.5, 0.)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
+    fn defocus_disk_sample(&self, rng: &mut Rng) -> Point3 {
         // Returns a random point in the camera defocus disk.
-        let p = random_unit_disk();
+        let p = random_unit_disk(rng);
         self.center + p.x * self.defocus_disk_u + p.y * self.defocus_disk_v
     }
 
-    fn ray_color(ray: Ray, depth: i32, world: &HittableList) -> Color {
+    fn ray_color(ray: Ray, depth: i32, world: &HittableList, rng: &mut Rng) -> Color {
         // If we've exceeded the ray bounce limit, no more light is gathered.
         if depth <= 0 {
             return Color::zero();
         }
         if let Some(ref hit_record) = world.hit(&ray, Interval::new(0.001, f64::INFINITY)) {
-            if let Some((scattered, attenuation)) = hit_record.material.scatter(&ray, hit_record) {
-                return attenuation * Camera::ray_color(scattered, depth-1, world);
+            if let Some((scattered, attenuation)) = hit_record.material.scatter(&ray, hit_record, rng) {
+                return attenuation * Camera::ray_color(scattered, depth-1, world, rng);
             }
             return Color::zero();
         }
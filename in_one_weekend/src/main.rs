@@ -10,7 +10,7 @@ use in_one_weekend::{
     hittable_list::HittableList,
     material::{Lambertian, Metal, Dielectric}
 };
-use utils::rtweekend::{random, random_range};
+use utils::rng::Rng;
 use vector3::Point3;
 
 fn main() {
@@ -20,7 +20,7 @@ fn main() {
     let ground_material = Rc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
     world.add(Rc::new(Sphere::new(Point3::from_y(-1000.), 1000., ground_material)));
 
-    generate_sphere_random(&mut world);
+    generate_sphere_random(&mut world, &mut Rng::new(0));
 
     let material_1 = Rc::new(Dielectric::new(1.5));
     let material_2 = Rc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
@@ -45,26 +45,26 @@ fn main() {
     ).render(&world);
 }
 
-fn generate_sphere_random(world: &mut HittableList) {
+fn generate_sphere_random(world: &mut HittableList, rng: &mut Rng) {
     for j in -10..11 {
         for i in -10..11 {
-            let which_material = random();
+            let which_material = rng.random();
             let center = Point3::new(
-                j as f64 + 0.9 * random(),
+                j as f64 + 0.9 * rng.random(),
                 0.2,
-                i as f64 + 0.9 * random()
+                i as f64 + 0.9 * rng.random()
             );
 
             if (center - Point3::new(4., 0.2, 0.)).norm() > 0.9 {
                 if which_material < 0.7 {
                     // diffuse
-                    let albedo = Color::random() * Color::random();
+                    let albedo = Color::random(rng) * Color::random(rng);
                     let material = Rc::new(Lambertian::new(albedo));
                     world.add(Rc::new(Sphere::new(center, 0.2, material)));
                 } else if which_material < 0.9 {
                     // metal
-                    let albedo = Color::random_range(0.5, 1.);
-                    let fuzz = random_range(0., 0.5);
+                    let albedo = Color::random_range(0.5, 1., rng);
+                    let fuzz = rng.random_range(0., 0.5);
                     let material = Rc::new(Metal::new(albedo, fuzz));
                     world.add(Rc::new(Sphere::new(center, 0.2, material)));
                 } else {
@@ -1,16 +1,16 @@
 use crate::{color::Color, hittable::HitRecord, ray::Ray};
-use utils::rtweekend::random;
+use utils::rng::Rng;
 use vector3::extension::{cos_theta, random_unit_vector, reflect, refract};
 
 pub trait Material {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)>;
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)>;
 }
 
 #[derive(Default)]
 pub struct DefaultMaterial;
 
 impl Material for DefaultMaterial {
-    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord, _rng: &mut Rng) -> Option<(Ray, Color)> {
         Some((Ray::default(), Color::zero()))
     }
 }
@@ -26,8 +26,8 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = hit_record.normal + random_unit_vector();
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
+        let mut scatter_direction = hit_record.normal + random_unit_vector(rng);
 
         // Catch degenerate scatter direction
         if scatter_direction.near_zero() {
@@ -50,9 +50,9 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
         let reflected = reflect(ray_in.direction(), &hit_record.normal)
-            .normalize() + self.fuzz * random_unit_vector();
+            .normalize() + self.fuzz * random_unit_vector(rng);
         let scattered = Ray::new(hit_record.point, reflected);
         if scattered.direction().dot(&hit_record.normal) > 0. {
             return Some((scattered, self.albedo));
@@ -81,7 +81,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, rng: &mut Rng) -> Option<(Ray, Color)> {
         let ri = if hit_record.front_face {
             self.refraction_index.recip()
         } else {
@@ -93,7 +93,7 @@ impl Material for Dielectric {
         let sin_theta = (1. - cos_theta.powi(2)).sqrt();
 
         let direction = if ri * sin_theta > 1. ||
-            Dielectric::reflectance(cos_theta, ri) > random() {
+            Dielectric::reflectance(cos_theta, ri) > rng.random() {
             reflect(&unit_direction, &hit_record.normal) // 反射
         } else {
             refract(&unit_direction, &hit_record.normal, ri) // 折射
@@ -7,7 +7,7 @@ pub const UNIVERSE: AABB = AABB { x: interval::UNIVERSE, y: interval::UNIVERSE,
 
 /// Axis-Aligned Bounding Boxes (AABBs)
 /// 轴对齐边界框
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 pub struct AABB {
     pub x: Interval,
     pub y: Interval,
@@ -16,7 +16,7 @@ pub struct AABB {
 
 impl AABB {
     pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
-        Self { x, y, z }
+        Self { x, y, z }.pad_to_minimums()
     }
 
     pub fn from_points(a: Point3, b: Point3) -> Self {
@@ -26,7 +26,7 @@ impl AABB {
             x: if a.x <= b.x { Interval::new(a.x, b.x) } else { Interval::new(b.x, a.x) },
             y: if a.y <= b.y { Interval::new(a.y, b.y) } else { Interval::new(b.y, a.y) },
             z: if a.z <= b.z { Interval::new(a.z, b.z) } else { Interval::new(b.z, a.z) }
-        }
+        }.pad_to_minimums()
     }
 
     pub fn combine(box0: &AABB, box1: &AABB) -> Self {
@@ -72,6 +72,32 @@ impl AABB {
         true
     }
 
+    /// 包围盒的中心
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            (self.x.min + self.x.max) * 0.5,
+            (self.y.min + self.y.max) * 0.5,
+            (self.z.min + self.z.max) * 0.5
+        )
+    }
+
+    /// 包围盒的表面积
+    pub fn surface_area(&self) -> f64 {
+        let (dx, dy, dz) = (self.x.size(), self.y.size(), self.z.size());
+        2. * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// 扩展过薄的轴，避免平面物体产生厚度为零的包围盒
+    fn pad_to_minimums(mut self) -> Self {
+        // Adjust the AABB so that no side is narrower than some delta, padding if necessary.
+        const DELTA: f64 = 0.0001;
+        if self.x.size() < DELTA { self.x = self.x.expand(DELTA); }
+        if self.y.size() < DELTA { self.y = self.y.expand(DELTA); }
+        if self.z.size() < DELTA { self.z = self.z.expand(DELTA); }
+
+        self
+    }
+
     /// 返回包围盒最长轴的索引
     /// x: 0
     /// y: 1
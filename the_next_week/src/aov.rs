@@ -0,0 +1,314 @@
+//! 辅助输出（AOV，arbitrary output variables）
+//!
+//! 与最终颜色一起渲染的逐像素辅助图像，取自每条相机光线的第一个交点，供合成软件和降噪器使用。
+//! 法线、深度、位置、反照率、纹理坐标和时间是像素内所有样本的平均，没有击中物体的样本记为零；物体和材质编号
+//! 取像素内第一个击中物体的样本，在整幅图像中按光栅顺序从 1 开始编号，0 表示背景，因此与线程数和运行次数无关。
+//! 方差由像素内各样本颜色的亮度估计，用于降噪；样本数在自适应采样时逐像素不同，可显示为热力图。
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc
+};
+
+use crate::{
+    color::Color,
+    frame_buffer::{self, FrameBuffer, OutputFormat},
+    hittable::HitRecord,
+    ray::Ray
+};
+use vector3::{Point3, Vec3};
+
+/// 辅助输出的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aov {
+    /// 世界空间中的着色法线，朝向相机一侧
+    Normal,
+    /// 沿光线到交点的距离
+    Depth,
+    /// 世界空间中的交点位置
+    Position,
+    /// 表面的颜色，见 [`Material::albedo`](crate::material::Material::albedo)
+    Albedo,
+    /// 纹理坐标，保存在红、绿两个通道
+    Uv,
+    /// 物体编号，网格和变换后的实例各算一个物体
+    ObjectId,
+    /// 材质编号，共享同一个材质的物体编号相同
+    MaterialId,
+    /// 光线在快门内的时间
+    Time,
+    /// 像素颜色（各样本的平均）的亮度的方差，由样本之间的差异估计
+    Variance,
+    /// 像素的样本数，见 [`AdaptiveSampling`](crate::sampler::AdaptiveSampling)
+    SampleCount
+}
+
+impl Aov {
+    pub const ALL: [Aov; 10] = [
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Position,
+        Aov::Albedo,
+        Aov::Uv,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Time,
+        Aov::Variance,
+        Aov::SampleCount
+    ];
+
+    /// 文件名和 EXR 层使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Position => "position",
+            Self::Albedo => "albedo",
+            Self::Uv => "uv",
+            Self::ObjectId => "object_id",
+            Self::MaterialId => "material_id",
+            Self::Time => "time",
+            Self::Variance => "variance",
+            Self::SampleCount => "sample_count"
+        }
+    }
+
+    /// 把原始值转换为便于在 8 位图片中查看的颜色
+    ///
+    /// 法线从 [-1, 1] 映射到 [0, 1]，深度、位置和方差按图像中的范围归一化，编号显示为各不相同的颜色，
+    /// 样本数显示为从蓝（最少）到红（最多）的热力图。
+    pub fn visualize(self, buffer: &FrameBuffer) -> FrameBuffer {
+        let pixels = buffer.pixels();
+        let visualized = match self {
+            Self::Normal => pixels.iter().map(|&normal| 0.5 * (normal + Vec3::one())).collect(),
+            Self::Depth | Self::Position | Self::Variance => {
+                let (min, max) = pixels.iter().fold(
+                    ([f64::INFINITY; 3], [f64::NEG_INFINITY; 3]),
+                    |(mut min, mut max), value| {
+                        for axis in 0..3 {
+                            min[axis] = min[axis].min(value[axis]);
+                            max[axis] = max[axis].max(value[axis]);
+                        }
+                        (min, max)
+                    }
+                );
+                // Depth and variance start from zero, positions fill the bounds of what is seen.
+                let min = if self == Self::Position { min } else { [0.; 3] };
+                let normalize = |value: f64, axis: usize| {
+                    let range = max[axis] - min[axis];
+                    if range > 0. { (value - min[axis]) / range } else { 0. }
+                };
+                pixels.iter().map(|value| {
+                    Color::new(normalize(value.x, 0), normalize(value.y, 1), normalize(value.z, 2))
+                }).collect()
+            },
+            Self::ObjectId | Self::MaterialId => pixels.iter().map(|id| id_color(id.x as u64)).collect(),
+            Self::SampleCount => {
+                let (min, max) = pixels.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), count| {
+                    (min.min(count.x), max.max(count.x))
+                });
+                pixels.iter().map(|count| {
+                    heat_color(if max > min { (count.x - min) / (max - min) } else { 0. })
+                }).collect()
+            },
+            Self::Albedo | Self::Uv | Self::Time => pixels.to_vec()
+        };
+
+        FrameBuffer::from_pixels(buffer.width(), buffer.height(), visualized)
+    }
+}
+
+/// 编号 `id` 的显示颜色，0 为黑色
+fn id_color(id: u64) -> Color {
+    if id == 0 {
+        return Color::zero();
+    }
+
+    // Scatter consecutive ids over the color cube, avoiding the darkest colors.
+    let hash = id.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let channel = |shift: u32| 0.2 + 0.8 * ((hash >> shift) & 0xff) as f64 / 255.;
+    Color::new(channel(40), channel(48), channel(56))
+}
+
+/// 热力图中 `t` ∈ [0, 1] 的颜色：蓝、青、绿、黄、红
+fn heat_color(t: f64) -> Color {
+    let ramp = [
+        Color::new(0., 0., 1.),
+        Color::new(0., 1., 1.),
+        Color::new(0., 1., 0.),
+        Color::new(1., 1., 0.),
+        Color::new(1., 0., 0.)
+    ];
+
+    let t = t.clamp(0., 1.) * (ramp.len() - 1) as f64;
+    let i = (t as usize).min(ramp.len() - 2);
+    let f = t - i as f64;
+
+    (1. - f) * ramp[i] + f * ramp[i + 1]
+}
+
+/// 一次渲染得到的辅助输出
+#[derive(Clone, Default)]
+pub struct AovBuffers {
+    buffers: Vec<(Aov, FrameBuffer)>
+}
+
+impl AovBuffers {
+    pub fn get(&self, aov: Aov) -> Option<&FrameBuffer> {
+        self.buffers.iter().find(|(kind, _)| *kind == aov).map(|(_, buffer)| buffer)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Aov, &FrameBuffer)> {
+        self.buffers.iter().map(|(aov, buffer)| (*aov, buffer))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    /// 加入或替换一个辅助输出，如由其他渲染器得到的引导图像
+    pub fn insert(&mut self, aov: Aov, buffer: FrameBuffer) {
+        match self.buffers.iter_mut().find(|(kind, _)| *kind == aov) {
+            Some((_, existing)) => *existing = buffer,
+            None => self.buffers.push((aov, buffer))
+        }
+    }
+
+    /// 只保留 `keep` 为真的辅助输出
+    pub fn retain(&mut self, keep: impl Fn(Aov) -> bool) {
+        self.buffers.retain(|(aov, _)| keep(*aov));
+    }
+
+    /// 与最终颜色 `beauty` 一起保存
+    ///
+    /// EXR 把每个辅助输出写成同一个文件中的一层；其他格式把每个辅助输出写成单独的图片，见
+    /// [`AovBuffers::aov_path`]，其中 8 位格式保存 [`Aov::visualize`] 的结果。
+    pub fn save(&self, beauty: &FrameBuffer, path: impl AsRef<Path>, format: OutputFormat) -> io::Result<()> {
+        let path = path.as_ref();
+        if let OutputFormat::Exr(precision) = format {
+            let layers: Vec<(&str, &FrameBuffer)> = std::iter::once(("", beauty))
+                .chain(self.iter().map(|(aov, buffer)| (aov.name(), buffer)))
+                .collect();
+            return frame_buffer::write_exr(path, &layers, precision);
+        }
+
+        beauty.save_with_format(path, format)?;
+        for (aov, buffer) in self.iter() {
+            let aov_path = Self::aov_path(path, aov);
+            if format.is_floating_point() {
+                buffer.save_with_format(aov_path, format)?;
+            } else {
+                aov.visualize(buffer).save_with_format(aov_path, format)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 单独保存辅助输出时的文件名，如 `out/image.png` 的法线保存为 `out/image.normal.png`
+    pub fn aov_path(path: impl AsRef<Path>, aov: Aov) -> PathBuf {
+        let path = path.as_ref();
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, aov.name(), extension.to_string_lossy()),
+            None => format!("{}.{}", stem, aov.name())
+        };
+
+        path.with_file_name(name)
+    }
+}
+
+/// 一个像素或一个样本的辅助输出
+#[derive(Clone, Copy, Default)]
+pub(crate) struct AovSample {
+    normal: Vec3,
+    depth: f64,
+    position: Point3,
+    albedo: Color,
+    uv: (f64, f64),
+    time: f64,
+    /// 像素颜色的亮度的方差和样本数，由 [`AovSample::finish`] 记录
+    variance: f64,
+    samples: u32,
+    /// 物体和材质的标识，0 表示没有击中
+    object: usize,
+    material: usize
+}
+
+impl AovSample {
+    /// 光线 `ray` 的第一个交点
+    pub(crate) fn new(ray: &Ray, hit_record: &HitRecord) -> Self {
+        Self {
+            normal: hit_record.normal,
+            depth: hit_record.t * ray.direction().norm(),
+            position: hit_record.point,
+            albedo: hit_record.material.albedo(hit_record),
+            uv: (hit_record.u, hit_record.v),
+            time: ray.time(),
+            object: hit_record.object,
+            material: Arc::as_ptr(&hit_record.material) as *const () as usize,
+            ..Default::default()
+        }
+    }
+
+    /// 累加一个样本，编号保留第一个击中物体的样本
+    pub(crate) fn add(&mut self, sample: &AovSample) {
+        self.normal += sample.normal;
+        self.depth += sample.depth;
+        self.position += sample.position;
+        self.albedo += sample.albedo;
+        self.uv = (self.uv.0 + sample.uv.0, self.uv.1 + sample.uv.1);
+        self.time += sample.time;
+        if self.material == 0 {
+            (self.object, self.material) = (sample.object, sample.material);
+        }
+    }
+
+    /// 由累加的 `samples` 个样本求出平均值，并记录像素颜色的方差 `variance`
+    pub(crate) fn finish(&mut self, samples: u32, variance: f64) {
+        let scale = (samples.max(1) as f64).recip();
+        self.normal = scale * self.normal;
+        self.depth *= scale;
+        self.position = scale * self.position;
+        self.albedo = scale * self.albedo;
+        self.uv = (scale * self.uv.0, scale * self.uv.1);
+        self.time *= scale;
+        self.variance = variance;
+        self.samples = samples;
+    }
+}
+
+/// 由按行存储的像素生成 `aovs` 中的各个辅助输出
+pub(crate) fn resolve(aovs: &[Aov], width: u32, height: u32, pixels: &[AovSample]) -> AovBuffers {
+    // Number the objects and materials in the order they first appear.
+    let number = |key: fn(&AovSample) -> usize| {
+        let mut ids = HashMap::new();
+        pixels.iter().map(|pixel| match key(pixel) {
+            0 => 0.,
+            key => {
+                let next = ids.len() + 1;
+                *ids.entry(key).or_insert(next) as f64
+            }
+        }).collect::<Vec<f64>>()
+    };
+
+    let buffers = aovs.iter().map(|&aov| {
+        let values: Vec<Color> = match aov {
+            Aov::Normal => pixels.iter().map(|pixel| pixel.normal).collect(),
+            Aov::Depth => pixels.iter().map(|pixel| Color::isotropic(pixel.depth)).collect(),
+            Aov::Position => pixels.iter().map(|pixel| pixel.position).collect(),
+            Aov::Albedo => pixels.iter().map(|pixel| pixel.albedo).collect(),
+            Aov::Uv => pixels.iter().map(|pixel| Color::new(pixel.uv.0, pixel.uv.1, 0.)).collect(),
+            Aov::ObjectId => number(|pixel| pixel.object).into_iter().map(Color::isotropic).collect(),
+            Aov::MaterialId => number(|pixel| pixel.material).into_iter().map(Color::isotropic).collect(),
+            Aov::Time => pixels.iter().map(|pixel| Color::isotropic(pixel.time)).collect(),
+            Aov::Variance => pixels.iter().map(|pixel| Color::isotropic(pixel.variance)).collect(),
+            Aov::SampleCount => pixels.iter().map(|pixel| Color::isotropic(pixel.samples as f64)).collect()
+        };
+        (aov, FrameBuffer::from_pixels(width, height, values))
+    }).collect();
+
+    AovBuffers { buffers }
+}
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use crate::{color::Color, environment::EnvironmentMap, pdf::PDF, ray::Ray};
+
+/// 射线没有击中任何物体时返回的背景颜色
+#[derive(Clone)]
+pub enum Background {
+    /// 固定颜色，黑色表示场景只由其中的光源照亮
+    Solid(Color),
+    /// 沿射线方向的 y 分量从 `bottom` 渐变到 `top`
+    Gradient { bottom: Color, top: Color },
+    /// HDR 环境贴图，同时作为光源被重要性采样
+    Environment(Arc<EnvironmentMap>)
+}
+
+impl Background {
+    /// 白色到浅蓝色的天空
+    pub fn sky() -> Self {
+        Self::Gradient { bottom: Color::one(), top: Color::new(0.5, 0.7, 1.) }
+    }
+
+    pub fn color(&self, ray: &Ray) -> Color {
+        match *self {
+            Self::Solid(color) => color,
+            Self::Gradient { bottom, top } => {
+                let t = 0.5 * (ray.direction().normalize().y + 1.);
+                (1. - t)*bottom + t*top
+            }
+            Self::Environment(ref map) => map.color(ray.direction())
+        }
+    }
+
+    /// 可以朝其采样的背景的方向分布
+    pub fn pdf(&self) -> Option<&dyn PDF> {
+        match self {
+            Self::Environment(map) => Some(map.as_ref()),
+            _ => None
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
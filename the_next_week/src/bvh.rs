@@ -1,78 +1,296 @@
-use std::rc::Rc;
+use std::{fmt::{self, Display}, sync::Arc};
 
 use crate::{
     aabb::{self, AABB},
-    hittable::{HitRecord, Hittable, InvalidHittable},
+    hittable::{HitRecord, Hittable},
     hittable_list::HittableList,
     ray::Ray
 };
-use utils::interval::Interval;
+use utils::{interval::Interval, rng::Rng};
 
+/// SAH 中遍历一个内部节点的相对开销
+const TRAVERSAL_COST: f64 = 0.125;
+/// SAH 中与一个物体求交的相对开销
+const INTERSECTION_COST: f64 = 1.;
+
+/// BVH 内部节点的划分策略
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMethod {
+    /// 按包围盒最长轴排序，从物体数量的中位数处划分
+    #[default]
+    Median,
+    /// 分桶的表面积启发式（Surface Area Heuristic），选择期望求交开销最小的划分
+    Sah
+}
+
+/// BVH 在遍历时的存储方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BVHLayout {
+    /// 由 [`BVHNode`] 组成的树，每个节点单独分配
+    Tree,
+    /// 展开为连续节点数组的 [`LinearBVH`]，迭代遍历
+    #[default]
+    Linear
+}
+
+/// BVH 构建参数
+#[derive(Debug, Clone, Copy)]
+pub struct BVHOptions {
+    pub split: SplitMethod,
+    pub layout: BVHLayout,
+    /// 叶子节点最多包含的物体数
+    pub max_leaf_size: usize,
+    /// SAH 划分时沿划分轴使用的桶数
+    pub bins: usize
+}
+
+impl Default for BVHOptions {
+    fn default() -> Self {
+        Self { split: SplitMethod::Median, layout: BVHLayout::Linear, max_leaf_size: 1, bins: 12 }
+    }
+}
+
+/// BVH 的构建统计
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BVHStats {
+    /// 节点总数，包括叶子节点
+    pub node_count: usize,
+    pub leaf_count: usize,
+    /// 树的深度，只有根节点时为 1
+    pub depth: usize,
+    /// 按表面积启发式估计的每条射线的求交开销
+    pub sah_cost: f64
+}
+
+impl Display for BVHStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} leaves, depth {}, SAH cost {:.3}",
+            self.node_count, self.leaf_count, self.depth, self.sah_cost
+        )
+    }
+}
+
+/// 按 `options` 为 `objects` 构建 BVH，返回可直接求交的加速结构及其构建统计
+pub fn build(objects: &mut [Arc<dyn Hittable>], options: &BVHOptions) -> (Arc<dyn Hittable>, BVHStats) {
+    let tree = BVHNode::with_options(objects, options);
+    let stats = tree.stats();
+    let bvh: Arc<dyn Hittable> = match options.layout {
+        BVHLayout::Tree => Arc::new(tree),
+        BVHLayout::Linear => Arc::new(LinearBVH::from_bvh(&tree))
+    };
+
+    (bvh, stats)
+}
+
+enum Node {
+    Leaf(Vec<Arc<dyn Hittable>>),
+    Interior(Box<BVHNode>, Box<BVHNode>)
+}
+
+/// 层次包围盒（Bounding Volume Hierarchy）
 pub struct BVHNode {
-    left: Rc<dyn Hittable>,
-    right: Rc<dyn Hittable>,
+    node: Node,
     bbox: AABB
 }
 
 impl BVHNode {
     pub fn from_hittable_list(list: &mut HittableList) -> Self {
-        let len = list.objects.len();
-        BVHNode::new(&mut list.objects, 0, len)
+        Self::with_options(&mut list.objects, &BVHOptions::default())
     }
 
-    pub fn new(objects: &mut Vec<Rc<dyn Hittable>>, begin: usize, end: usize) -> Self {
-        let mut bvh_node = BVHNode::default();
+    /// 用默认参数（中位数划分）为 `objects[begin..end]` 构建 BVH
+    pub fn new(objects: &mut [Arc<dyn Hittable>], begin: usize, end: usize) -> Self {
+        Self::with_options(&mut objects[begin..end], &BVHOptions::default())
+    }
+
+    /// 按给定参数构建 BVH，`objects` 会被重新排序
+    ///
+    /// 树的深度不超过 64 层：SAH 在物体分布极不均匀时（如按指数间隔排列）每层只分出一个物体，
+    /// 接近上限的子树改用中位数划分。
+    pub fn with_options(objects: &mut [Arc<dyn Hittable>], options: &BVHOptions) -> Self {
+        Self::build(objects, options, 1)
+    }
 
+    /// 构建深度为 `depth` 的子树
+    fn build(objects: &mut [Arc<dyn Hittable>], options: &BVHOptions, depth: usize) -> Self {
         // Build the bounding box of the span of source objects.
-        bvh_node.bbox = aabb::EMPTY;
-        for object_index in begin..end {
-            bvh_node.bbox = AABB::combine(&bvh_node.bbox, objects[object_index].bounding_box());
-        }
+        let bbox = objects.iter()
+            .fold(aabb::EMPTY, |bbox, object| AABB::combine(&bbox, object.bounding_box()));
+
+        // A median split below this node adds at most log2(n) levels, which still fits.
+        let median_depth = depth + objects.len().next_power_of_two().trailing_zeros() as usize;
+        let split = if median_depth >= MAX_DEPTH { SplitMethod::Median } else { options.split };
+        let max_leaf_size = options.max_leaf_size.max(1);
+        let mid = match split {
+            SplitMethod::Median => (objects.len() > max_leaf_size).then(|| Self::split_median(objects, &bbox)),
+            SplitMethod::Sah => Self::split_sah(objects, &bbox, max_leaf_size, options.bins.max(2))
+        };
 
-        let object_span = end - begin;
-        match object_span {
-            1 => {
-                bvh_node.left = objects[begin].clone();
-                bvh_node.right = objects[begin].clone();
+        let node = match mid {
+            Some(mid) => {
+                let (left, right) = objects.split_at_mut(mid);
+                Node::Interior(
+                    Box::new(Self::build(left, options, depth + 1)),
+                    Box::new(Self::build(right, options, depth + 1))
+                )
             },
-            2 => {
-                bvh_node.left = objects[begin].clone();
-                bvh_node.right = objects[begin + 1].clone();
+            None => Node::Leaf(objects.to_vec())
+        };
+
+        Self { node, bbox }
+    }
+
+    /// 统计树的节点数、深度和 SAH 开销
+    pub fn stats(&self) -> BVHStats {
+        let mut stats = BVHStats::default();
+        self.collect_stats(1, self.bbox.surface_area(), &mut stats);
+
+        stats
+    }
+
+    fn collect_stats(&self, depth: usize, root_area: f64, stats: &mut BVHStats) {
+        // The probability of a ray hitting a node is proportional to its surface area.
+        let probability = if root_area > 0. { self.bbox.surface_area() / root_area } else { 1. };
+
+        stats.node_count += 1;
+        stats.depth = stats.depth.max(depth);
+        match &self.node {
+            Node::Leaf(objects) => {
+                stats.leaf_count += 1;
+                stats.sah_cost += probability * objects.len() as f64 * INTERSECTION_COST;
             },
-            _ => {
-                let axis = bvh_node.bbox.longest_axis();
-                objects[begin..end].sort_by(|a, b| {
-                    let a_axis_interval = a.bounding_box().axis_interval(axis);
-                    let b_axis_interval = b.bounding_box().axis_interval(axis);
+            Node::Interior(left, right) => {
+                stats.sah_cost += probability * TRAVERSAL_COST;
+                left.collect_stats(depth + 1, root_area, stats);
+                right.collect_stats(depth + 1, root_area, stats);
+            }
+        }
+    }
 
-                    a_axis_interval.min.partial_cmp(&b_axis_interval.min).unwrap()
-                });
-                let mid = begin + object_span / 2;
-                bvh_node.left = Rc::new(BVHNode::new(objects, begin, mid));
-                bvh_node.right = Rc::new(BVHNode::new(objects, mid, end));
+    /// 沿最长轴排序，返回中位数下标
+    fn split_median(objects: &mut [Arc<dyn Hittable>], bbox: &AABB) -> usize {
+        let axis = bbox.longest_axis();
+        objects.sort_by(|a, b| {
+            let a_axis_interval = a.bounding_box().axis_interval(axis);
+            let b_axis_interval = b.bounding_box().axis_interval(axis);
+
+            a_axis_interval.min.partial_cmp(&b_axis_interval.min).unwrap()
+        });
+
+        objects.len() / 2
+    }
+
+    /// 用分桶的 SAH 选择划分位置并据此划分物体，返回划分下标；作为叶子节点更划算时返回 `None`
+    fn split_sah(objects: &mut [Arc<dyn Hittable>], bbox: &AABB, max_leaf_size: usize, bins: usize) -> Option<usize> {
+        let count = objects.len();
+        if count <= 1 {
+            return None;
+        }
+
+        // Bin the objects by their centroids along the axis where the centroids spread the most.
+        let centroid_bounds = objects.iter().fold(aabb::EMPTY, |bounds, object| {
+            let c = object.bounding_box().centroid();
+            AABB {
+                x: Interval::from_intervals(bounds.x, Interval::new(c.x, c.x)),
+                y: Interval::from_intervals(bounds.y, Interval::new(c.y, c.y)),
+                z: Interval::from_intervals(bounds.z, Interval::new(c.z, c.z))
+            }
+        });
+        let axis = centroid_bounds.longest_axis();
+        let extent = centroid_bounds.axis_interval(axis);
+        if extent.size() <= 0. {
+            // All centroids coincide, so no split separates them.
+            return (count > max_leaf_size).then(|| Self::split_median(objects, bbox));
+        }
+
+        let bin_of = |object: &Arc<dyn Hittable>| {
+            let c = object.bounding_box().centroid()[axis];
+            (((c - extent.min) / extent.size() * bins as f64) as usize).min(bins - 1)
+        };
+        let mut bin_bounds = vec![aabb::EMPTY; bins];
+        let mut bin_counts = vec![0; bins];
+        for object in objects.iter() {
+            let bin = bin_of(object);
+            bin_bounds[bin] = AABB::combine(&bin_bounds[bin], object.bounding_box());
+            bin_counts[bin] += 1;
+        }
+
+        // Sweep from the right to accumulate the area and count of everything after each split.
+        let mut right_area = vec![0.; bins];
+        let mut right_count = vec![0; bins];
+        let (mut bounds, mut accumulated) = (aabb::EMPTY, 0);
+        for bin in (1..bins).rev() {
+            bounds = AABB::combine(&bounds, &bin_bounds[bin]);
+            accumulated += bin_counts[bin];
+            right_area[bin] = if accumulated > 0 { bounds.surface_area() } else { 0. };
+            right_count[bin] = accumulated;
+        }
+
+        // Sweep from the left and evaluate the cost of splitting after each bin.
+        let mut best: Option<(usize, f64)> = None;
+        let (mut bounds, mut accumulated) = (aabb::EMPTY, 0);
+        for bin in 0..bins - 1 {
+            bounds = AABB::combine(&bounds, &bin_bounds[bin]);
+            accumulated += bin_counts[bin];
+            if accumulated == 0 || right_count[bin + 1] == 0 {
+                continue;
+            }
+
+            let cost = accumulated as f64 * bounds.surface_area()
+                + right_count[bin + 1] as f64 * right_area[bin + 1];
+            if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+                best = Some((bin, cost));
             }
         }
+        let (split_bin, cost) = best?;
 
-        bvh_node
+        // Relative to the node's own area, the expected cost of testing both children.
+        let area = bbox.surface_area();
+        let split_cost = TRAVERSAL_COST + INTERSECTION_COST * cost / area;
+        let leaf_cost = count as f64 * INTERSECTION_COST;
+        if count <= max_leaf_size && leaf_cost <= split_cost {
+            return None;
+        }
+
+        objects.sort_by_key(|object| bin_of(object) > split_bin);
+        Some(objects.iter().take_while(|object| bin_of(object) <= split_bin).count())
     }
 }
 
 impl Hittable for BVHNode {
-    fn hit(&self, ray: &Ray, mut ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, mut ray_t: Interval, rng: &mut Rng) -> Option<HitRecord> {
         if !self.bbox.hit(ray, &mut ray_t) {
             return None;
         }
 
-        let left_record = self.left.hit(ray, ray_t);
-        let mut interval = ray_t;
-        if let Some(ref record) = left_record {
-            interval.max = record.t;
-        }
-        let right_record = self.right.hit(ray, interval);
+        match &self.node {
+            Node::Leaf(objects) => {
+                // Keep the closest hit among the objects of the leaf.
+                let mut closest = None;
+                for object in objects {
+                    if let Some(record) = object.hit(ray, ray_t, rng) {
+                        ray_t.max = record.t;
+                        closest = Some(record);
+                    }
+                }
 
-        // 如果 right_record 不是 None，则返回 right_record，否则返回 left_record
-        // 都为 None 时，返回 None
-        right_record.or(left_record)
+                closest
+            },
+            Node::Interior(left, right) => {
+                let left_record = left.hit(ray, ray_t, rng);
+                let mut interval = ray_t;
+                if let Some(ref record) = left_record {
+                    interval.max = record.t;
+                }
+                let right_record = right.hit(ray, interval, rng);
+
+                // 如果 right_record 不是 None，则返回 right_record，否则返回 left_record
+                // 都为 None 时，返回 None
+                right_record.or(left_record)
+            }
+        }
     }
 
     fn bounding_box(&self) -> &AABB {
@@ -82,10 +300,174 @@ impl Hittable for BVHNode {
 
 impl Default for BVHNode {
     fn default() -> Self {
-        Self {
-            left: Rc::new(InvalidHittable),
-            right: Rc::new(InvalidHittable),
-            bbox: AABB::default()
+        Self { node: Node::Leaf(Vec::new()), bbox: aabb::EMPTY }
+    }
+}
+
+/// BVH 的最大深度，也是线性 BVH 遍历栈的容量
+const MAX_DEPTH: usize = 64;
+
+/// 线性 BVH 中的节点
+struct LinearNode {
+    bbox: AABB,
+    /// 叶子节点：第一个物体在 `primitives` 中的下标；内部节点：第二个子节点的下标，第一个子节点紧随其后
+    offset: u32,
+    /// 叶子节点的物体数，内部节点为 0
+    count: u32,
+    /// 内部节点的划分轴，第一个子节点位于该轴的较小一侧
+    axis: u8
+}
+
+/// 展开为连续数组的 BVH
+///
+/// 节点按深度优先的顺序存放，物体按叶子顺序连续存放，遍历时用栈代替递归，并优先访问离射线起点较近的子节点。
+pub struct LinearBVH {
+    nodes: Vec<LinearNode>,
+    primitives: Vec<Arc<dyn Hittable>>
+}
+
+impl LinearBVH {
+    /// 按给定参数直接从物体列表构建
+    pub fn from_hittable_list(list: &mut HittableList, options: &BVHOptions) -> Self {
+        Self::new(&mut list.objects, options)
+    }
+
+    /// 按给定参数构建，`objects` 会被重新排序
+    pub fn new(objects: &mut [Arc<dyn Hittable>], options: &BVHOptions) -> Self {
+        Self::from_bvh(&BVHNode::with_options(objects, options))
+    }
+
+    /// 展开已构建的 BVH 树
+    pub fn from_bvh(tree: &BVHNode) -> Self {
+        let mut bvh = Self { nodes: Vec::new(), primitives: Vec::new() };
+        bvh.flatten(tree);
+
+        bvh
+    }
+
+    /// 节点总数
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn flatten(&mut self, tree: &BVHNode) -> usize {
+        let index = self.nodes.len();
+        match &tree.node {
+            Node::Leaf(objects) => {
+                self.nodes.push(LinearNode {
+                    bbox: tree.bbox,
+                    offset: self.primitives.len() as u32,
+                    count: objects.len() as u32,
+                    axis: 0
+                });
+                self.primitives.extend(objects.iter().cloned());
+            },
+            Node::Interior(left, right) => {
+                // Order the children along the axis that separates their centers the most.
+                let (a, b) = (left.bbox.centroid(), right.bbox.centroid());
+                let axis = (0..3)
+                    .max_by(|&i, &j| (b[i] - a[i]).abs().partial_cmp(&(b[j] - a[j]).abs()).unwrap())
+                    .unwrap();
+                let (first, second) = if a[axis] <= b[axis] { (left, right) } else { (right, left) };
+
+                self.nodes.push(LinearNode { bbox: tree.bbox, offset: 0, count: 0, axis: axis as u8 });
+                self.flatten(first);
+                self.nodes[index].offset = self.flatten(second) as u32;
+            }
+        }
+
+        index
+    }
+}
+
+impl Hittable for LinearBVH {
+    fn hit(&self, ray: &Ray, mut ray_t: Interval, rng: &mut Rng) -> Option<HitRecord> {
+        if self.primitives.is_empty() {
+            return None;
+        }
+
+        let direction = ray.direction();
+        let dir_is_neg = [direction.x < 0., direction.y < 0., direction.z < 0.];
+
+        let mut closest = None;
+        // Trees are at most MAX_DEPTH deep, and each level pushes at most one node.
+        let mut stack = [0_u32; MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            let mut node_t = ray_t;
+            if node.bbox.hit(ray, &mut node_t) {
+                if node.count > 0 {
+                    let begin = node.offset as usize;
+                    for object in &self.primitives[begin..begin + node.count as usize] {
+                        if let Some(record) = object.hit(ray, node_t, rng) {
+                            ray_t.max = record.t;
+                            node_t.max = record.t;
+                            closest = Some(record);
+                        }
+                    }
+                } else {
+                    // Visit the child nearer to the ray origin first and defer the other one.
+                    let (near, far) = if dir_is_neg[node.axis as usize] {
+                        (node.offset as usize, current + 1)
+                    } else {
+                        (current + 1, node.offset as usize)
+                    };
+                    stack[stack_len] = far as u32;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len] as usize;
+        }
+
+        closest
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.nodes[0].bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Lambertian, sphere::Sphere};
+    use vector3::{Point3, Vec3};
+
+    #[test]
+    fn test_deep_linear_bvh() {
+        // Spheres at x = 4^i make the SAH peel off one sphere per level, until the depth limit switches
+        // to median splits.
+        let material = Arc::new(Lambertian::from_color(Vec3::isotropic(0.5)));
+        let mut objects: Vec<Arc<dyn Hittable>> = (0..300).map(|i| {
+            let center = Point3::from_x(4f64.powi(i));
+            Arc::new(Sphere::new_stationary_sphere(center, 1., material.clone())) as Arc<dyn Hittable>
+        }).collect();
+
+        let options = BVHOptions { split: SplitMethod::Sah, ..Default::default() };
+        let (bvh, stats) = build(&mut objects, &options);
+        assert!(stats.depth > 32 && stats.depth <= MAX_DEPTH, "{}", stats);
+
+        // A ray along the row enters every box and descends to the deepest leaf, the first sphere. The
+        // rays pass off center so that they don't just touch the faces of the boxes.
+        let mut rng = Rng::new(0);
+        let offset = 0.75f64.sqrt();
+        let ray = Ray::new(Point3::new(-10., 0.5, 0.), Vec3::from_x(1.), 0.);
+        let record = bvh.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rng).unwrap();
+        assert!((record.t - (11. - offset)).abs() < 1e-12);
+        for i in 0..20 {
+            let x = 4f64.powi(i);
+            let ray = Ray::new(Point3::new(x, 10., 0.5), Vec3::from_y(-1.), 0.);
+            let record = bvh.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut rng).unwrap();
+            assert!((record.point.x - x).abs() < 1e-12 && (record.t - (10. - offset)).abs() < 1e-9);
         }
     }
 }
@@ -0,0 +1,779 @@
+//! 局部坐标系下的 BSDF
+//!
+//! 着色坐标系以表面法线为 z 轴、切线为 x 轴，`wo` 指向观察者，`wi` 指向光源，都是单位向量。微表面
+//! 模型使用 Trowbridge-Reitz（GGX）法线分布，按可见法线分布（VNDF）采样，并使用完整的 Fresnel
+//! 公式而不是 Schlick 近似。
+
+use crate::{color::{luminance, Color}, pdf::PDF, sampler::Sampler};
+use utils::rtweekend::PI;
+use vector3::{extension::sample_cosine_direction, onb::ONB, Vec3};
+
+/// 局部坐标系下的散射分布函数
+pub trait BxDF: Send + Sync {
+    /// BSDF 的值 f(wo, wi)，不含余弦项
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Color;
+
+    /// [`BxDF::sample`] 生成 `wi` 的立体角概率密度
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64;
+
+    /// 按近似 f 的分布生成 `wi`，生成的方向不属于所选的波瓣时失败
+    fn sample(&self, wo: &Vec3, sampler: &mut dyn Sampler) -> Option<Vec3>;
+}
+
+/// Trowbridge-Reitz（GGX）微表面法线分布，`alpha_x`、`alpha_y` 分别为沿切线和副切线的粗糙度
+#[derive(Debug, Clone, Copy)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        Self { alpha_x, alpha_y }
+    }
+
+    /// 由感知粗糙度 `roughness` ∈ [0, 1] 和各向异性 `anisotropy` ∈ [0, 1) 创建，
+    /// alpha 为粗糙度的平方，各向异性时沿切线方向更粗糙
+    pub fn from_roughness(roughness: f64, anisotropy: f64) -> Self {
+        let alpha = roughness.clamp(0., 1.).powi(2);
+        let aspect = (1. - 0.9 * anisotropy.clamp(0., 1.)).sqrt();
+
+        Self::new(alpha / aspect, alpha * aspect)
+    }
+
+    /// 粗糙度很小，应当按理想镜面处理
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) < 1e-3
+    }
+
+    /// 微表面法线 `wm` 的分布 D(wm)
+    pub fn d(&self, wm: &Vec3) -> f64 {
+        let tan2_theta = tan2_theta(wm);
+        let cos4_theta = wm.z.powi(4);
+        if !tan2_theta.is_finite() || cos4_theta < 1e-16 {
+            return 0.;
+        }
+        let (cos_phi, sin_phi) = cos_sin_phi(wm);
+        let e = tan2_theta * ((cos_phi / self.alpha_x).powi(2) + (sin_phi / self.alpha_y).powi(2));
+
+        1. / (PI * self.alpha_x * self.alpha_y * cos4_theta * (1. + e).powi(2))
+    }
+
+    /// Smith 遮蔽函数的辅助函数 Λ(w)
+    fn lambda(&self, w: &Vec3) -> f64 {
+        let tan2_theta = tan2_theta(w);
+        if !tan2_theta.is_finite() {
+            return 0.;
+        }
+        let (cos_phi, sin_phi) = cos_sin_phi(w);
+        let alpha2 = (cos_phi * self.alpha_x).powi(2) + (sin_phi * self.alpha_y).powi(2);
+
+        ((1. + alpha2 * tan2_theta).sqrt() - 1.) / 2.
+    }
+
+    /// 单方向的遮蔽函数 G1(w)
+    pub fn g1(&self, w: &Vec3) -> f64 {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// 遮蔽-阴影函数 G(wo, wi)（高度相关形式）
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// 从方向 `w` 可见的微表面法线分布 D_w(wm)，也就是 [`TrowbridgeReitz::sample_wm`] 的概率密度，
+    /// `w` 在下半球时按其反方向计算
+    pub fn visible_d(&self, w: &Vec3, wm: &Vec3) -> f64 {
+        if w.z == 0. {
+            return 0.;
+        }
+        let w = if w.z < 0. { -*w } else { *w };
+        self.g1(&w) / w.z * self.d(wm) * w.dot(wm).max(0.)
+    }
+
+    /// 按从 `w` 可见的法线分布采样微表面法线（Heitz 2018）
+    pub fn sample_wm(&self, w: &Vec3, (u1, u2): (f64, f64)) -> Vec3 {
+        // Transform w to the hemispherical configuration.
+        let mut wh = Vec3::new(self.alpha_x * w.x, self.alpha_y * w.y, w.z).normalize();
+        if wh.z < 0. {
+            wh = -wh;
+        }
+
+        // Find an orthonormal basis for the visible normal sampling.
+        let t1 = if wh.z < 0.99999 { Vec3::from_z(1.).cross(&wh).normalize() } else { Vec3::from_x(1.) };
+        let t2 = wh.cross(&t1);
+
+        // Generate a uniformly distributed point on the unit disk, warped to the visible
+        // projected hemisphere.
+        let (r, phi) = (u1.sqrt(), 2. * PI * u2);
+        let (px, py) = (r * phi.cos(), r * phi.sin());
+        let h = (1. - px * px).sqrt();
+        let t = (1. + wh.z) / 2.;
+        let py = (1. - t) * h + t * py;
+        let pz = (1. - px * px - py * py).max(0.).sqrt();
+
+        // Reproject to the hemisphere and transform the normal back to the ellipsoid configuration.
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(1e-6)).normalize()
+    }
+}
+
+/// 介质分界面上非偏振光的 Fresnel 反射率，`eta` 为透射侧与入射侧折射率之比，
+/// `cos_theta_i` 为负时表示从另一侧入射
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let (cos_theta_i, eta) = if cos_theta_i < 0. {
+        (-cos_theta_i.max(-1.), eta.recip())
+    } else {
+        (cos_theta_i.min(1.), eta)
+    };
+
+    // Compute cos(theta_t) using Snell's law, and handle total internal reflection.
+    let sin2_theta_t = (1. - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1. {
+        return 1.;
+    }
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.
+}
+
+/// 导体表面每个通道的 Fresnel 反射率，复折射率为 `eta` + i`k`
+pub fn fresnel_conductor(cos_theta_i: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| fresnel_complex(cos_theta_i, Complex::new(eta, k));
+    Color::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+fn fresnel_complex(cos_theta_i: f64, eta: Complex) -> f64 {
+    let cos_theta_i = Complex::new(cos_theta_i.clamp(0., 1.), 0.);
+    let sin2_theta_i = Complex::new(1., 0.) - cos_theta_i * cos_theta_i;
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    let cos_theta_t = (Complex::new(1., 0.) - sin2_theta_t).sqrt();
+
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parallel.norm() + r_perpendicular.norm()) / 2.
+}
+
+/// 粗糙导体的微表面反射
+pub struct ConductorBxDF {
+    pub distribution: TrowbridgeReitz,
+    pub eta: Color,
+    pub k: Color
+}
+
+impl BxDF for ConductorBxDF {
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        let Some((value, wm)) = microfacet_reflection(&self.distribution, wo, wi) else {
+            return Color::zero();
+        };
+        value * fresnel_conductor(wo.dot(&wm).abs(), self.eta, self.k)
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        microfacet_reflection_pdf(&self.distribution, wo, wi)
+    }
+
+    fn sample(&self, wo: &Vec3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        sample_microfacet_reflection(&self.distribution, wo, sampler)
+    }
+}
+
+/// GGX 反射中与 Fresnel 项无关的部分 D G / (4 cos_o cos_i)，以及半向量
+fn microfacet_reflection(distribution: &TrowbridgeReitz, wo: &Vec3, wi: &Vec3) -> Option<(f64, Vec3)> {
+    if wo.z * wi.z <= 0. {
+        return None;
+    }
+    let wm = half_vector(wo, wi)?;
+
+    let d = distribution.d(&wm);
+    let g = distribution.g(wo, wi);
+    Some((d * g / (4. * wo.z.abs() * wi.z.abs()), wm))
+}
+
+fn microfacet_reflection_pdf(distribution: &TrowbridgeReitz, wo: &Vec3, wi: &Vec3) -> f64 {
+    if wo.z * wi.z <= 0. {
+        return 0.;
+    }
+    let Some(wm) = half_vector(wo, wi) else {
+        return 0.;
+    };
+
+    // Account for the Jacobian of the reflection about the microfacet normal.
+    distribution.visible_d(wo, &wm) / (4. * wo.dot(&wm).abs())
+}
+
+fn sample_microfacet_reflection(distribution: &TrowbridgeReitz, wo: &Vec3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+    let wm = distribution.sample_wm(wo, sampler.get_2d());
+    let wi = reflect(wo, &wm);
+    (wo.z * wi.z > 0.).then_some(wi)
+}
+
+/// 粗糙电介质的微表面反射和透射，`eta` 为 -z 一侧与 +z 一侧的折射率之比
+pub struct DielectricBxDF {
+    pub distribution: TrowbridgeReitz,
+    pub eta: f64
+}
+
+impl DielectricBxDF {
+    /// `wo`、`wi` 对应的广义半向量，朝向 +z；该微表面背对任一方向时为 `None`
+    fn half_vector(&self, wo: &Vec3, wi: &Vec3) -> Option<(Vec3, f64)> {
+        let (cos_theta_o, cos_theta_i) = (wo.z, wi.z);
+        if cos_theta_o == 0. || cos_theta_i == 0. {
+            return None;
+        }
+
+        // The relative index of refraction along the path, 1 for reflection.
+        let reflect = cos_theta_i * cos_theta_o > 0.;
+        let etap = match (reflect, cos_theta_o > 0.) {
+            (true, _) => 1.,
+            (false, true) => self.eta,
+            (false, false) => self.eta.recip()
+        };
+        let wm = *wi * etap + *wo;
+        if wm.squared() == 0. {
+            return None;
+        }
+        let wm = wm.normalize();
+        let wm = if wm.z < 0. { -wm } else { wm };
+
+        // Discard backfacing microfacets.
+        if wm.dot(wi) * cos_theta_i < 0. || wm.dot(wo) * cos_theta_o < 0. {
+            return None;
+        }
+        Some((wm, etap))
+    }
+}
+
+impl BxDF for DielectricBxDF {
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        let Some((wm, etap)) = self.half_vector(wo, wi) else {
+            return Color::zero();
+        };
+
+        let fresnel = fresnel_dielectric(wo.dot(&wm), self.eta);
+        let d = self.distribution.d(&wm);
+        let g = self.distribution.g(wo, wi);
+        let value = if etap == 1. {
+            d * g * fresnel / (4. * wo.z * wi.z).abs()
+        } else {
+            // Radiance is compressed into the smaller solid angle when entering the denser
+            // medium, hence the division by eta squared.
+            let denom = (wi.dot(&wm) + wo.dot(&wm) / etap).powi(2) * wi.z * wo.z;
+            d * (1. - fresnel) * g * (wi.dot(&wm) * wo.dot(&wm) / denom).abs() / (etap * etap)
+        };
+
+        Color::isotropic(value)
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        let Some((wm, etap)) = self.half_vector(wo, wi) else {
+            return 0.;
+        };
+
+        let reflectance = fresnel_dielectric(wo.dot(&wm), self.eta);
+        let visible_d = self.distribution.visible_d(wo, &wm);
+        if etap == 1. {
+            visible_d / (4. * wo.dot(&wm).abs()) * reflectance
+        } else {
+            let dwm_dwi = wi.dot(&wm).abs() / (wi.dot(&wm) + wo.dot(&wm) / etap).powi(2);
+            visible_d * dwm_dwi * (1. - reflectance)
+        }
+    }
+
+    fn sample(&self, wo: &Vec3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let choice = sampler.get_1d();
+        let wm = self.distribution.sample_wm(wo, sampler.get_2d());
+
+        // Choose between reflection and transmission by the Fresnel reflectance. A reflection
+        // through the macrosurface or a transmission back out of it belongs to the other lobe.
+        let reflectance = fresnel_dielectric(wo.dot(&wm), self.eta);
+        if choice < reflectance {
+            let wi = reflect(wo, &wm);
+            (wo.z * wi.z > 0.).then_some(wi)
+        } else {
+            let wi = refract(wo, &wm, self.eta)?;
+            (wo.z * wi.z < 0.).then_some(wi)
+        }
+    }
+}
+
+/// Disney 风格的原理化 BSDF，由漫反射（含 sheen）、电介质高光、金属、透射和清漆五个波瓣组成，
+/// 各参数的含义见 [`crate::material::Principled`]
+///
+/// 上层波瓣按其在 `wo` 方向的反射率削弱下层：清漆之下的部分乘以 1 - 清漆反射率，漫反射乘以
+/// 1 - 高光反射率，金属度和透射度在三种基底之间线性混合，因此白色材质的反照率不超过一。
+/// 采样时按各波瓣在 `wo` 方向的估计权重选择波瓣，概率密度为所有波瓣密度的加权和。
+pub struct PrincipledBxDF {
+    pub base_color: Color,
+    pub metallic: f64,
+    pub specular: f64,
+    pub specular_tint: f64,
+    pub sheen: f64,
+    pub sheen_tint: f64,
+    pub clearcoat: f64,
+    pub transmission: f64,
+    pub distribution: TrowbridgeReitz,
+    pub clearcoat_distribution: TrowbridgeReitz,
+    /// 透射波瓣 -z 一侧与 +z 一侧的折射率之比
+    pub eta: f64
+}
+
+/// 清漆层的折射率
+const CLEARCOAT_ETA: f64 = 1.5;
+
+/// [`PrincipledBxDF`] 的波瓣，顺序与 [`PrincipledBxDF::lobe_weights`] 一致
+#[derive(Debug, Clone, Copy)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Metal,
+    Transmission,
+    Clearcoat
+}
+
+const LOBES: [Lobe; 5] = [Lobe::Diffuse, Lobe::Specular, Lobe::Metal, Lobe::Transmission, Lobe::Clearcoat];
+
+impl PrincipledBxDF {
+    /// 电介质高光的相对折射率，`specular` 为 0.5 时正入射反射率为 4%，即折射率 1.5
+    fn specular_eta(&self) -> f64 {
+        let r0 = (0.08 * self.specular.clamp(0., 1.)).sqrt();
+        (1. + r0) / (1. - r0)
+    }
+
+    /// 最大通道归一化为一的基础色色调
+    fn tint(&self) -> Color {
+        let max = self.base_color.x.max(self.base_color.y).max(self.base_color.z);
+        if max > 0. { self.base_color / max } else { Color::one() }
+    }
+
+    /// 各波瓣在 `wo` 方向上的权重，也就是清漆、金属度和透射度混合后各自所占的比例
+    fn layer_weights(&self, wo: &Vec3) -> [f64; 5] {
+        let clearcoat = self.clearcoat * fresnel_dielectric(wo.z.abs(), CLEARCOAT_ETA);
+        let base = 1. - clearcoat;
+        let dielectric = base * (1. - self.metallic) * (1. - self.transmission);
+
+        [
+            dielectric * (1. - fresnel_dielectric(wo.z.abs(), self.specular_eta())),
+            dielectric,
+            base * self.metallic,
+            base * (1. - self.metallic) * self.transmission,
+            self.clearcoat
+        ]
+    }
+
+    /// 选择各波瓣的概率，正比于波瓣在 `wo` 方向的估计反照率；所有波瓣都为黑色时全为零
+    fn lobe_weights(&self, wo: &Vec3) -> [f64; 5] {
+        let cos_theta_o = wo.z.abs();
+        let [diffuse, specular, metal, transmission, clearcoat] = self.layer_weights(wo);
+        let specular_color = lerp(Color::one(), self.tint(), self.specular_tint);
+
+        let mut weights = [
+            diffuse * luminance(self.base_color).max(self.sheen),
+            specular * fresnel_dielectric(cos_theta_o, self.specular_eta()) * luminance(specular_color),
+            metal * luminance(fresnel_schlick(cos_theta_o, self.base_color)),
+            transmission,
+            clearcoat * fresnel_dielectric(cos_theta_o, CLEARCOAT_ETA)
+        ];
+        let total: f64 = weights.iter().sum();
+        if total > 0. {
+            weights.iter_mut().for_each(|weight| *weight /= total);
+        }
+        weights
+    }
+
+    fn transmission_bxdf(&self) -> DielectricBxDF {
+        DielectricBxDF { distribution: self.distribution, eta: self.eta }
+    }
+
+    fn lobe_f(&self, lobe: Lobe, wo: &Vec3, wi: &Vec3) -> Color {
+        match lobe {
+            Lobe::Diffuse => {
+                if wo.z * wi.z <= 0. {
+                    return Color::zero();
+                }
+                // Sheen shifts the color towards the sheen color at grazing angles, like
+                // retro-reflecting fibers, without adding energy.
+                let sheen = match half_vector(wo, wi) {
+                    Some(wm) => self.sheen * (1. - wi.dot(&wm).abs()).powi(5),
+                    None => 0.
+                };
+                let sheen_color = lerp(Color::one(), self.tint(), self.sheen_tint);
+                lerp(self.base_color, sheen_color, sheen) / PI
+            },
+            Lobe::Specular => {
+                let Some((value, wm)) = microfacet_reflection(&self.distribution, wo, wi) else {
+                    return Color::zero();
+                };
+                let specular_color = lerp(Color::one(), self.tint(), self.specular_tint);
+                value * fresnel_dielectric(wo.dot(&wm).abs(), self.specular_eta()) * specular_color
+            },
+            Lobe::Metal => {
+                let Some((value, wm)) = microfacet_reflection(&self.distribution, wo, wi) else {
+                    return Color::zero();
+                };
+                value * fresnel_schlick(wo.dot(&wm).abs(), self.base_color)
+            },
+            Lobe::Transmission => {
+                // Only the refracted light is tinted by the base color.
+                let f = self.transmission_bxdf().f(wo, wi);
+                if wo.z * wi.z < 0. { f * self.base_color } else { f }
+            },
+            Lobe::Clearcoat => {
+                let Some((value, wm)) = microfacet_reflection(&self.clearcoat_distribution, wo, wi) else {
+                    return Color::zero();
+                };
+                Color::isotropic(value * fresnel_dielectric(wo.dot(&wm).abs(), CLEARCOAT_ETA))
+            }
+        }
+    }
+
+    fn lobe_pdf(&self, lobe: Lobe, wo: &Vec3, wi: &Vec3) -> f64 {
+        match lobe {
+            Lobe::Diffuse => if wo.z * wi.z > 0. { wi.z.abs() / PI } else { 0. },
+            Lobe::Specular | Lobe::Metal => microfacet_reflection_pdf(&self.distribution, wo, wi),
+            Lobe::Transmission => self.transmission_bxdf().pdf(wo, wi),
+            Lobe::Clearcoat => microfacet_reflection_pdf(&self.clearcoat_distribution, wo, wi)
+        }
+    }
+}
+
+impl BxDF for PrincipledBxDF {
+    fn f(&self, wo: &Vec3, wi: &Vec3) -> Color {
+        LOBES.iter().zip(self.layer_weights(wo))
+            .filter(|&(_, weight)| weight > 0.)
+            .fold(Color::zero(), |f, (&lobe, weight)| f + weight * self.lobe_f(lobe, wo, wi))
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3) -> f64 {
+        LOBES.iter().zip(self.lobe_weights(wo))
+            .filter(|&(_, weight)| weight > 0.)
+            .map(|(&lobe, weight)| weight * self.lobe_pdf(lobe, wo, wi))
+            .sum()
+    }
+
+    fn sample(&self, wo: &Vec3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        // Pick a lobe in proportion to its weight.
+        let mut choice = sampler.get_1d();
+        let mut lobe = None;
+        for (&candidate, weight) in LOBES.iter().zip(self.lobe_weights(wo)) {
+            if weight > 0. {
+                lobe = Some(candidate);
+                if choice < weight {
+                    break;
+                }
+                choice -= weight;
+            }
+        }
+
+        match lobe? {
+            Lobe::Diffuse => {
+                let wi = sample_cosine_direction(sampler.get_2d());
+                Some(if wo.z < 0. { -wi } else { wi })
+            },
+            Lobe::Specular | Lobe::Metal => sample_microfacet_reflection(&self.distribution, wo, sampler),
+            Lobe::Transmission => self.transmission_bxdf().sample(wo, sampler),
+            Lobe::Clearcoat => sample_microfacet_reflection(&self.clearcoat_distribution, wo, sampler)
+        }
+    }
+}
+
+/// 把 [`BxDF`] 放到着色坐标系 `frame` 中，作为从 `wo` 出发的方向分布
+pub struct BxDFPDF<B: BxDF> {
+    bxdf: B,
+    frame: ONB,
+    wo: Vec3
+}
+
+impl<B: BxDF> BxDFPDF<B> {
+    /// `wo` 为世界坐标下指向观察者的单位向量
+    pub fn new(bxdf: B, frame: ONB, wo: Vec3) -> Self {
+        Self { bxdf, frame, wo: frame.local(wo) }
+    }
+}
+
+impl<B: BxDF> PDF for BxDFPDF<B> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.bxdf.pdf(&self.wo, &self.frame.local(direction.normalize()))
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        // A failed sample becomes a grazing direction, on which every BxDF vanishes.
+        match self.bxdf.sample(&self.wo, sampler) {
+            Some(wi) => self.frame.transform(wi),
+            None => self.frame.u()
+        }
+    }
+}
+
+/// Fresnel 反射率的 Schlick 近似，`f0` 为正入射时的反射率
+fn fresnel_schlick(cos_theta_i: f64, f0: Color) -> Color {
+    f0 + (Color::one() - f0) * (1. - cos_theta_i.clamp(0., 1.)).powi(5)
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1. - t) * a + t * b
+}
+
+/// `w` 关于法线 `n` 的镜面反射
+fn reflect(w: &Vec3, n: &Vec3) -> Vec3 {
+    2. * w.dot(n) * *n - *w
+}
+
+/// `w` 穿过法线为 `n` 的界面的折射方向，`eta` 为 `n` 背面与正面的折射率之比，全反射时为 `None`
+fn refract(w: &Vec3, n: &Vec3, eta: f64) -> Option<Vec3> {
+    // Flip the interface when w arrives from the back side.
+    let (cos_theta_i, n, eta) = match w.dot(n) {
+        cos_theta_i if cos_theta_i < 0. => (-cos_theta_i, -*n, eta.recip()),
+        cos_theta_i => (cos_theta_i, *n, eta)
+    };
+    let sin2_theta_t = (1. - cos_theta_i * cos_theta_i).max(0.) / (eta * eta);
+    if sin2_theta_t >= 1. {
+        return None;
+    }
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+
+    Some(-*w / eta + (cos_theta_i / eta - cos_theta_t) * n)
+}
+
+/// 同侧的 `wo`、`wi` 的半向量，朝向 +z
+fn half_vector(wo: &Vec3, wi: &Vec3) -> Option<Vec3> {
+    let wm = *wo + *wi;
+    if wm.squared() == 0. {
+        return None;
+    }
+    let wm = wm.normalize();
+    Some(if wm.z < 0. { -wm } else { wm })
+}
+
+fn tan2_theta(w: &Vec3) -> f64 {
+    let cos2_theta = w.z * w.z;
+    (1. - cos2_theta).max(0.) / cos2_theta
+}
+
+fn cos_sin_phi(w: &Vec3) -> (f64, f64) {
+    let sin_theta = (1. - w.z * w.z).max(0.).sqrt();
+    if sin_theta == 0. {
+        return (1., 0.);
+    }
+    ((w.x / sin_theta).clamp(-1., 1.), (w.y / sin_theta).clamp(-1., 1.))
+}
+
+/// 计算复数 Fresnel 项所需的最小复数运算
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// 模的平方
+    fn norm(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// 实部非负的平方根
+    fn sqrt(self) -> Self {
+        let n = self.norm().sqrt();
+        if n == 0. {
+            return Self::new(0., 0.);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+
+        if self.re >= 0. {
+            Self::new(t1, t2)
+        } else {
+            Self::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+}
+
+impl std::ops::Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(self.re * rhs.re - self.im * rhs.im, self.re * rhs.im + self.im * rhs.re)
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let scale = rhs.norm().recip();
+        Self::new(
+            scale * (self.re * rhs.re + self.im * rhs.im),
+            scale * (self.im * rhs.re - self.re * rhs.im)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{pdf::SpherePDF, sampler::SamplerKind};
+    use super::*;
+
+    /// 用均匀分布的方向估计 `f` 在整个球面上的积分
+    fn integrate(f: impl Fn(&Vec3) -> f64) -> f64 {
+        let mut sampler = SamplerKind::Sobol.create(3, 1 << 18);
+        sampler.start_pixel(0, 0);
+        let count = 1 << 18;
+        (0..count).map(|index| {
+            sampler.start_sample(index);
+            f(&SpherePDF.generate(sampler.as_mut())) * 4. * PI
+        }).sum::<f64>() / count as f64
+    }
+
+    /// 按 `bxdf` 自身的分布采样，估计反照率 ∫ f cos dwi
+    fn albedo(bxdf: &dyn BxDF, wo: &Vec3) -> f64 {
+        let mut sampler = SamplerKind::Sobol.create(5, 1 << 16);
+        sampler.start_pixel(0, 0);
+        let count = 1 << 16;
+        (0..count).map(|index| {
+            sampler.start_sample(index);
+            match bxdf.sample(wo, sampler.as_mut()) {
+                Some(wi) if bxdf.pdf(wo, &wi) > 0. => bxdf.f(wo, &wi).y * wi.z.abs() / bxdf.pdf(wo, &wi),
+                _ => 0.
+            }
+        }).sum::<f64>() / count as f64
+    }
+
+    fn distribution() -> TrowbridgeReitz {
+        TrowbridgeReitz::from_roughness(0.6, 0.5)
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = distribution();
+        let wo = Vec3::new(0.5, -0.3, 0.6).normalize();
+
+        // The projected microfacet area and the visible normals are both normalized.
+        let projected = integrate(|wm| if wm.z > 0. { distribution.d(wm) * wm.z } else { 0. });
+        assert!((projected - 1.).abs() < 0.01, "{}", projected);
+        let visible = integrate(|wm| if wm.z > 0. { distribution.visible_d(&wo, wm) } else { 0. });
+        assert!((visible - 1.).abs() < 0.01, "{}", visible);
+
+        let wm = distribution.sample_wm(&wo, (0.3, 0.7));
+        assert!(wm.z > 0. && (wm.norm() - 1.).abs() < 1e-12);
+        assert!(!distribution.is_smooth() && TrowbridgeReitz::from_roughness(0.01, 0.).is_smooth());
+    }
+
+    #[test]
+    fn test_fresnel() {
+        // Normal incidence on glass reflects 4%, and grazing incidence reflects everything.
+        assert!((fresnel_dielectric(1., 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(1e-9, 1.5) - 1.).abs() < 1e-6);
+        // Total internal reflection from inside the glass.
+        assert_eq!(fresnel_dielectric(-0.5, 1.5), 1.);
+        assert!((fresnel_dielectric(-1., 1.5) - 0.04).abs() < 1e-12);
+
+        // A conductor without absorption behaves like a dielectric.
+        for cos_theta in [0.1, 0.5, 0.9] {
+            let conductor = fresnel_conductor(cos_theta, Color::isotropic(1.5), Color::zero());
+            assert!((conductor.x - fresnel_dielectric(cos_theta, 1.5)).abs() < 1e-12);
+        }
+        // Gold reflects red more than blue.
+        let gold = fresnel_conductor(1., Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603));
+        assert!(gold.x > 0.9 && gold.x > gold.y && gold.y > gold.z);
+    }
+
+    #[test]
+    fn test_conductor() {
+        let bxdf = ConductorBxDF { distribution: distribution(), eta: Color::isotropic(0.2), k: Color::isotropic(3.) };
+        let (wo, wi) = (Vec3::new(0.5, -0.3, 0.6).normalize(), Vec3::new(-0.2, 0.4, 0.7).normalize());
+        assert!((bxdf.f(&wo, &wi).x - bxdf.f(&wi, &wo).x).abs() < 1e-12);
+        assert_eq!(bxdf.f(&wo, &-wi), Color::zero());
+
+        // Samples that leave through the surface are lost, so the density integrates to at most one.
+        let total = integrate(|wi| bxdf.pdf(&wo, wi));
+        assert!(total < 1. && total > 0.8, "{}", total);
+        let uniform = integrate(|wi| bxdf.f(&wo, wi).y * wi.z.abs());
+        let sampled = albedo(&bxdf, &wo);
+        assert!((sampled / uniform - 1.).abs() < 0.01, "{} != {}", sampled, uniform);
+        assert!(sampled < 1.);
+    }
+
+    #[test]
+    fn test_dielectric() {
+        for (eta, wo) in [(1.5, Vec3::new(0.5, -0.3, 0.6)), (1.5, Vec3::new(0.2, 0.1, -0.9)), (1. / 1.33, Vec3::new(0., 0.3, 0.8))] {
+            let bxdf = DielectricBxDF { distribution: distribution(), eta };
+            let wo = wo.normalize();
+
+            let total = integrate(|wi| bxdf.pdf(&wo, wi));
+            assert!(total <= 1.01 && total > 0.85, "{}", total);
+            let uniform = integrate(|wi| bxdf.f(&wo, wi).y * wi.z.abs());
+            let sampled = albedo(&bxdf, &wo);
+            assert!((sampled / uniform - 1.).abs() < 0.02, "{} != {}", sampled, uniform);
+        }
+
+        // Reflection is reciprocal.
+        let bxdf = DielectricBxDF { distribution: distribution(), eta: 1.5 };
+        let (wo, wi) = (Vec3::new(0.5, -0.3, 0.6).normalize(), Vec3::new(-0.2, 0.4, 0.7).normalize());
+        assert!((bxdf.f(&wo, &wi).x - bxdf.f(&wi, &wo).x).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_principled() {
+        let principled = |metallic: f64, sheen: f64, clearcoat: f64, transmission: f64| PrincipledBxDF {
+            base_color: Color::one(),
+            metallic,
+            specular: 0.5,
+            specular_tint: 0.,
+            sheen,
+            sheen_tint: 0.,
+            clearcoat,
+            transmission,
+            distribution: distribution(),
+            clearcoat_distribution: TrowbridgeReitz::from_roughness(0.2, 0.),
+            eta: 1.5
+        };
+        let materials = [
+            principled(0., 0., 0., 0.),
+            principled(1., 0., 0., 0.),
+            principled(0., 1., 1., 0.),
+            principled(0.3, 0.5, 0.5, 0.5),
+            principled(0., 0., 0., 1.)
+        ];
+
+        for bxdf in &materials {
+            for wo in [Vec3::new(0.5, -0.3, 0.6), Vec3::new(0.9, 0., 0.1)] {
+                let wo = wo.normalize();
+                let total = integrate(|wi| bxdf.pdf(&wo, wi));
+                assert!(total <= 1.01 && total > 0.8, "{}", total);
+
+                // A white material never reflects more than it receives.
+                let uniform = integrate(|wi| bxdf.f(&wo, wi).y * wi.z.abs());
+                let sampled = albedo(bxdf, &wo);
+                assert!((sampled / uniform - 1.).abs() < 0.02, "{} != {}", sampled, uniform);
+                assert!(sampled < 1.01, "{}", sampled);
+            }
+        }
+
+        // A black dielectric without specular reflection is black.
+        let black = PrincipledBxDF { base_color: Color::zero(), specular: 0., ..principled(0., 0., 0., 0.) };
+        let (wo, wi) = (Vec3::new(0.5, -0.3, 0.6).normalize(), Vec3::new(-0.2, 0.4, 0.7).normalize());
+        assert_eq!(black.f(&wo, &wi), Color::zero());
+    }
+}
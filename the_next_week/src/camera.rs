@@ -1,10 +1,22 @@
+use std::{
+    sync::{atomic::{AtomicUsize, Ordering}, mpsc},
+    thread
+};
+
 use crate::{
-    color::{write_color, Color},
+    aov::{self, Aov, AovBuffers, AovSample},
+    background::Background,
+    color::{luminance, Color},
+    frame_buffer::FrameBuffer,
     hittable_list::HittableList,
-    ray::Ray
+    material::Scattered,
+    pdf::{HittablePDF, MixturePDF, PDF},
+    ray::Ray,
+    sampler::{AdaptiveSampling, Sampler, SamplerKind},
+    tone_map::ToneMapping
 };
-use utils::{interval::Interval, rtweekend::{degree_to_radian, random}};
-use vector3::{extension::random_unit_disk, Point3, Vec3};
+use utils::{interval::Interval, rtweekend::degree_to_radian};
+use vector3::{extension::sample_unit_disk, Point3, Vec3};
 
 #[derive(Default)]
 pub struct Camera {
@@ -16,6 +28,8 @@ pub struct Camera {
     pub samples_per_pixel : i32,
     /// Maximum number of ray bounces into scene
     pub max_depth: i32,
+    /// Scene background color
+    pub background: Background,
 
     /// Vertical view angle (field of view)
     pub vertical_fov: f64,
@@ -31,10 +45,21 @@ pub struct Camera {
     /// Distance from camera lookfrom point to plane of perfect focus
     pub focus_dist: f64,
 
+    /// Number of render threads, 0 means one per available core
+    pub threads: usize,
+    /// Side length of the square tiles the image is split into
+    pub tile_size: i32,
+    /// Seed from which every pixel derives its own random stream
+    pub seed: u64,
+    /// Strategy used to place the samples of each pixel
+    pub sampler: SamplerKind,
+    /// Move samples from converged pixels to noisy ones, `samples_per_pixel` becomes the average
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    /// Exposure and tone curve applied when the image is written to an 8-bit format
+    pub tone_mapping: ToneMapping,
+
     /// Rendered image height
     image_height: i32,
-    /// Color scale factor for a sum of pixel samples
-    pixel_samples_scale: f64,
     /// Camera center
     center: Point3,
     /// Location of pixel 0, 0
@@ -50,10 +75,61 @@ pub struct Camera {
     /// Defocus disk horizontal radius
     defocus_disk_u: Vec3,
     /// Defocus disk vertical radius
-    defocus_disk_v: Vec3
+    defocus_disk_v: Vec3,
+    /// Angle subtended by a pixel
+    pixel_spread: f64
+}
+
+/// 像素各样本亮度的累计
+#[derive(Clone, Copy, Default)]
+struct LuminanceStats {
+    count: u32,
+    sum: f64,
+    sum_squared: f64
+}
+
+impl LuminanceStats {
+    fn add(&mut self, color: Color) {
+        let luminance = luminance(color);
+        self.count += 1;
+        self.sum += luminance;
+        self.sum_squared += luminance * luminance;
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.count.max(1) as f64
+    }
+
+    /// 像素颜色（各样本的平均）亮度的方差，即无偏的样本方差除以样本数
+    fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.;
+        }
+
+        let mean = self.mean();
+        (self.sum_squared / self.count as f64 - mean * mean).max(0.) / (self.count - 1) as f64
+    }
+}
+
+/// 像素已取的样本的累计，自适应采样时会分几轮继续累积
+#[derive(Clone, Copy, Default)]
+struct PixelState {
+    color: Color,
+    stats: LuminanceStats,
+    aov: AovSample
+}
+
+/// 图像中的一个矩形渲染块，范围为 [x0, x1) x [y0, y1)
+#[derive(Clone, Copy)]
+struct Tile {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
             aspect_ratio: f64,
             image_width: i32,
@@ -66,116 +142,415 @@ impl Camera {
             defocus_angle: f64,
             focus_dist: f64
         ) -> Self {
-        let mut camera = Self::default();
+        let mut camera = Self {
+            aspect_ratio,
+            image_width,
+            samples_per_pixel,
+            max_depth,
+            vertical_fov,
+            look_from,
+            look_at,
+            vup,
+            defocus_angle,
+            focus_dist,
+            tile_size: 16,
+            ..Default::default()
+        };
+
+        camera.initialize();
+
+        camera
+    }
+
+    /// 渲染图像，返回保存线性颜色的帧缓冲
+    ///
+    /// 图像被划分为若干渲染块，由 `threads` 个线程并行处理。每个像素的样本只由
+    /// `seed`、`sampler` 和像素坐标决定，因此结果与线程数无关。设置了 `adaptive_sampling` 时，
+    /// 每个像素的样本数由各像素的误差决定，同样与线程数无关，总样本数不超过 `samples_per_pixel` 乘以像素数。
+    ///
+    /// `lights` 中的物体会被直接采样（通常是场景中的光源），为空时只按材质的分布散射。
+    pub fn render(&mut self, world: &HittableList, lights: &HittableList) -> FrameBuffer {
+        self.render_with_aovs(world, lights, &[]).0
+    }
+
+    /// 渲染图像，同时渲染 `aovs` 中的辅助输出
+    pub fn render_with_aovs(
+            &mut self,
+            world: &HittableList,
+            lights: &HittableList,
+            aovs: &[Aov]
+        ) -> (FrameBuffer, AovBuffers) {
+        // Public parameters may have changed since construction.
+        self.initialize();
+        let (image, aov_pixels) = self.render_tiles(world, lights, !aovs.is_empty());
+        let aov_buffers = aov::resolve(aovs, image.width(), image.height(), &aov_pixels);
+
+        (image, aov_buffers)
+    }
+
+    /// 渲染所有像素，`record_aovs` 为真时同时返回每个像素的辅助输出
+    fn render_tiles(
+            &self,
+            world: &HittableList,
+            lights: &HittableList,
+            record_aovs: bool
+        ) -> (FrameBuffer, Vec<AovSample>) {
+        let pixel_count = (self.image_width * self.image_height) as usize;
+        let mut pixels = vec![PixelState::default(); pixel_count];
+        self.sample_pixels(&mut pixels, &vec![self.first_pass_samples(); pixel_count], world, lights, record_aovs);
+        if let Some(adaptive) = self.adaptive_sampling {
+            self.spend_saved_samples(adaptive, &mut pixels, world, lights, record_aovs);
+        }
+        eprintln!("\r{:<30}", "Down.");
+
+        let mut image = FrameBuffer::new(self.image_width as u32, self.image_height as u32)
+            .with_tone_mapping(self.tone_mapping);
+        let mut aov_pixels = Vec::with_capacity(if record_aovs { pixel_count } else { 0 });
+        for (color, pixel) in image.pixels_mut().iter_mut().zip(&mut pixels) {
+            *color = (pixel.stats.count.max(1) as f64).recip() * pixel.color;
+            if record_aovs {
+                pixel.aov.finish(pixel.stats.count, pixel.stats.variance());
+                aov_pixels.push(pixel.aov);
+            }
+        }
 
-        camera.aspect_ratio = aspect_ratio;
-        camera.image_width = image_width;
-        camera.samples_per_pixel = samples_per_pixel;
-        camera.max_depth = max_depth;
-        camera.vertical_fov = vertical_fov;
-        camera.look_from = look_from;
-        camera.look_at = look_at;
-        camera.vup = vup;
-        camera.defocus_angle = defocus_angle;
-        camera.focus_dist = focus_dist;
+        (image, aov_pixels)
+    }
 
-        camera.image_height = (image_width as f64 / aspect_ratio) as i32;
-        camera.image_height = camera.image_height.max(1);
+    /// 让每个像素继续取样本，直到共有 `targets` 中的样本数或已经收敛
+    ///
+    /// 有像素要取样本的渲染块由 `threads` 个线程并行处理。
+    fn sample_pixels(
+            &self,
+            pixels: &mut [PixelState],
+            targets: &[u32],
+            world: &HittableList,
+            lights: &HittableList,
+            record_aovs: bool
+        ) {
+        let index = |i: i32, j: i32| (j * self.image_width + i) as usize;
+        let tiles: Vec<Tile> = self.tiles().into_iter().filter(|tile| {
+            (tile.y0..tile.y1).any(|j| (tile.x0..tile.x1).any(|i| targets[index(i, j)] > pixels[index(i, j)].stats.count))
+        }).collect();
+        let threads = self.thread_count().min(tiles.len()).max(1);
+        let next_tile = AtomicUsize::new(0);
 
-        camera.pixel_samples_scale = (samples_per_pixel as f64).recip();
+        let results = thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..threads {
+                let sender = sender.clone();
+                let (tiles, next_tile, pixels) = (&tiles, &next_tile, &*pixels);
+                scope.spawn(move || {
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        let result = self.render_tile(tile, pixels, targets, world, lights, record_aovs);
+                        if sender.send((*tile, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
 
-        camera.center = look_from;
+            receiver.iter().enumerate().map(|(done, result)| {
+                eprint!("\rTiles remaining: {:<10}\r", tiles.len() - done - 1);
+                result
+            }).collect::<Vec<_>>()
+        });
+
+        for (tile, states) in results {
+            let tile_width = (tile.x1 - tile.x0) as usize;
+            for (row, j) in (tile.y0..tile.y1).enumerate() {
+                let begin = index(tile.x0, j);
+                pixels[begin..begin + tile_width].copy_from_slice(&states[row * tile_width..(row + 1) * tile_width]);
+            }
+        }
+    }
+
+    /// 把提前收敛的像素省下的样本分给没有收敛的像素
+    ///
+    /// 每轮给误差最大的若干像素各 `min_samples` 个样本，直到用完 `samples_per_pixel` 乘以像素数的预算，
+    /// 或者所有像素都已收敛或取满 `max_samples` 个样本。
+    fn spend_saved_samples(
+            &self,
+            adaptive: AdaptiveSampling,
+            pixels: &mut [PixelState],
+            world: &HittableList,
+            lights: &HittableList,
+            record_aovs: bool
+        ) {
+        let budget = pixels.len() as u64 * self.samples_per_pixel.max(0) as u64;
+        let (batch, max_samples) = (adaptive.min_samples.max(1), self.max_samples());
+        loop {
+            let spent: u64 = pixels.iter().map(|pixel| pixel.stats.count as u64).sum();
+            let affordable = (budget.saturating_sub(spent) / batch as u64) as usize;
+            let mut noisy: Vec<(f64, usize)> = pixels.iter().enumerate().filter_map(|(index, pixel)| {
+                let error = adaptive.relative_error(pixel.stats.mean(), pixel.stats.variance());
+                (pixel.stats.count < max_samples && error > adaptive.threshold).then_some((error, index))
+            }).collect();
+            if affordable == 0 || noisy.is_empty() {
+                break;
+            }
+
+            // Ties go in image order, so that the choice doesn't depend on the threads.
+            noisy.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+            let mut targets = vec![0; pixels.len()];
+            for &(_, index) in noisy.iter().take(affordable) {
+                targets[index] = (pixels[index].stats.count + batch).min(max_samples);
+            }
+            self.sample_pixels(pixels, &targets, world, lights, record_aovs);
+        }
+    }
+
+    /// 根据公开参数计算相机内部状态
+    fn initialize(&mut self) {
+        self.image_height = (self.image_width as f64 / self.aspect_ratio) as i32;
+        self.image_height = self.image_height.max(1);
+
+        self.center = self.look_from;
 
         // Determine viewport dimensions.
-        let theta = degree_to_radian(camera.vertical_fov);
-        let viewport_height: f64 = (theta / 2.).tan() * 2. * focus_dist;
-        let viewport_width = viewport_height * aspect_ratio;
+        let theta = degree_to_radian(self.vertical_fov);
+        let viewport_height: f64 = (theta / 2.).tan() * 2. * self.focus_dist;
+        let viewport_width = viewport_height * self.aspect_ratio;
 
         // Calculate the u,v,w unit basis vectors for the camera coordinate frame.
-        camera.w = (look_from - look_at).normalize();
-        camera.u = vup.cross(&camera.w).normalize();
-        camera.v = camera.w.cross(&camera.u);
+        self.w = (self.look_from - self.look_at).normalize();
+        self.u = self.vup.cross(&self.w).normalize();
+        self.v = self.w.cross(&self.u);
 
         // Calculate the vectors across the horizontal and down the vertical viewport edges.
         // Vector across viewport horizontal edge
-        let viewport_u = viewport_width * camera.u;
+        let viewport_u = viewport_width * self.u;
         // Vector down viewport vertical edge
-        let viewport_v = viewport_height * (-camera.v);
+        let viewport_v = viewport_height * (-self.v);
 
         // Calculate the horizontal and vertical delta vectors from pixel to pixel.
-        camera.pixel_delta_u = viewport_u / camera.image_width as f64;
-        camera.pixel_delta_v = viewport_v / camera.image_height as f64;
+        self.pixel_delta_u = viewport_u / self.image_width as f64;
+        self.pixel_delta_v = viewport_v / self.image_height as f64;
 
         // Calculate the location of the upper left pixel.
-        let viewport_upper_left = camera.center - focus_dist * camera.w - (viewport_u + viewport_v) * 0.5;
-        camera.pixel00_loc  = viewport_upper_left + 0.5 * (camera.pixel_delta_u + camera.pixel_delta_v);
+        let viewport_upper_left = self.center - self.focus_dist * self.w - (viewport_u + viewport_v) * 0.5;
+        self.pixel00_loc  = viewport_upper_left + 0.5 * (self.pixel_delta_u + self.pixel_delta_v);
+        self.pixel_spread = self.pixel_delta_v.norm() / self.focus_dist;
 
         // Calculate the camera defocus disk basis vectors.
-        let defocus_radius = focus_dist * degree_to_radian(camera.defocus_angle*0.5).tan();
-        camera.defocus_disk_u = camera.u * defocus_radius;
-        camera.defocus_disk_v = camera.v * defocus_radius;
+        let defocus_radius = self.focus_dist * degree_to_radian(self.defocus_angle*0.5).tan();
+        self.defocus_disk_u = self.u * defocus_radius;
+        self.defocus_disk_v = self.v * defocus_radius;
+    }
 
-        camera
+    /// 第一轮每个像素最多的样本数，没有自适应采样时即全部样本
+    fn first_pass_samples(&self) -> u32 {
+        let samples = self.samples_per_pixel.max(0) as u32;
+        match self.adaptive_sampling {
+            // Never more than the average, even when min_samples is larger, so the budget holds.
+            Some(_) => samples.clamp(1, self.max_samples()),
+            None => samples
+        }
     }
 
-    pub fn render(&self, world: &HittableList) {
-        // Render
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
-        for j in 0..self.image_height {
-            eprint!("\rScanlines remaining: {:<10}\r", self.image_height - j);
-            for i in 0..self.image_width {
-                let mut pixel_color = Color::zero();
-                for _ in 0..self.samples_per_pixel {
-                    pixel_color += Camera::ray_color(self.ray(i, j), self.max_depth, world);
-                }
+    /// 每个像素最多的样本数
+    fn max_samples(&self) -> u32 {
+        match self.adaptive_sampling {
+            Some(adaptive) => adaptive.max_samples.max(adaptive.min_samples).max(1),
+            None => self.samples_per_pixel.max(0) as u32
+        }
+    }
+
+    fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            return self.threads;
+        }
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
 
-                write_color(self.pixel_samples_scale * pixel_color);
+    fn tiles(&self) -> Vec<Tile> {
+        let tile_size = self.tile_size.max(1);
+        let mut tiles = Vec::new();
+        for y0 in (0..self.image_height).step_by(tile_size as usize) {
+            for x0 in (0..self.image_width).step_by(tile_size as usize) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + tile_size).min(self.image_width),
+                    y1: (y0 + tile_size).min(self.image_height)
+                });
             }
         }
-        eprintln!("\r{:<30}", "Down.");
+
+        tiles
     }
 
-    fn ray(&self, i: i32, j: i32) ->Ray {
+    /// 渲染块中各像素继续取样本后的状态，按行排列
+    fn render_tile(
+            &self,
+            tile: &Tile,
+            pixels: &[PixelState],
+            targets: &[u32],
+            world: &HittableList,
+            lights: &HittableList,
+            record_aovs: bool
+        ) -> Vec<PixelState> {
+        let mut states = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+        // Strata are laid out for the first pass, samples added to noisy pixels later start new patterns.
+        let mut sampler = self.sampler.create(self.seed, self.first_pass_samples().max(1));
+        for j in tile.y0..tile.y1 {
+            for i in tile.x0..tile.x1 {
+                let index = (j * self.image_width + i) as usize;
+                let mut pixel = pixels[index];
+                self.sample_pixel(i, j, world, lights, sampler.as_mut(), &mut pixel, targets[index], record_aovs);
+                states.push(pixel);
+            }
+        }
+
+        states
+    }
+
+    /// 为像素继续取样本，直到共有 `target` 个样本或已经收敛，`record_aovs` 为真时同时累积各样本
+    /// 第一个交点的辅助输出
+    #[allow(clippy::too_many_arguments)]
+    fn sample_pixel(
+            &self,
+            i: i32,
+            j: i32,
+            world: &HittableList,
+            lights: &HittableList,
+            sampler: &mut dyn Sampler,
+            pixel: &mut PixelState,
+            target: u32,
+            record_aovs: bool
+        ) {
+        // Every pixel owns sample sequences derived from the seed, its location and the sample
+        // index, so it doesn't depend on which thread renders it, in which order, or in how many rounds.
+        sampler.start_pixel(i, j);
+
+        while pixel.stats.count < target {
+            sampler.start_sample(pixel.stats.count);
+            let ray = self.ray(i, j, sampler);
+            let mut first_hit = record_aovs.then(AovSample::default);
+            let sample_color = self.ray_color(ray, self.max_depth, world, lights, sampler, first_hit.as_mut());
+            pixel.color += sample_color;
+            pixel.stats.add(sample_color);
+            if let Some(first_hit) = first_hit {
+                pixel.aov.add(&first_hit);
+            }
+
+            if let Some(adaptive) = self.adaptive_sampling {
+                if adaptive.should_check(pixel.stats.count) && adaptive.converged(pixel.stats.mean(), pixel.stats.variance()) {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn ray(&self, i: i32, j: i32, sampler: &mut dyn Sampler) -> Ray {
         // Construct a camera ray originating from the defocus disk and directed at a randomly
         // sampled point around the pixel location i, j.
-        let offset = self.sample_square();
+        let offset = self.sample_square(sampler);
         let pixel_sample = self.pixel00_loc
             + (i as f64 + offset.x) * self.pixel_delta_u
             + (j as f64 + offset.y) * self.pixel_delta_v;
+        // The lens sample is drawn even without defocus blur, so that every sample uses the same
+        // dimensions for the same purpose.
+        let lens_sample = self.defocus_disk_sample(sampler);
         let ray_origin = if self.defocus_angle <= 0. {
             self.center
         } else {
-            self.defocus_disk_sample()
+            lens_sample
         };
         let ray_direction = pixel_sample - ray_origin;
-        let ray_time = random();
+        let ray_time = sampler.get_1d();
 
-        Ray::new(ray_origin, ray_direction, ray_time)
+        // The cone through the pixel lets textures pick a mip level. Scattered rays don't carry
+        // a cone and use the finest level.
+        Ray::new(ray_origin, ray_direction, ray_time).with_spread(self.pixel_spread)
     }
 
-    fn sample_square(&self) -> Vec3 {
+    fn sample_square(&self, sampler: &mut dyn Sampler) -> Vec3 {
         // Returns the vector to a random point in the [-.5,-.5]-[+.5,+.5] unit square.
-        Vec3::new(random() - 0.5, random() - 0.5, 0.)
+        let (x, y) = sampler.get_2d();
+        Vec3::new(x - 0.5, y - 0.5, 0.)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
+    fn defocus_disk_sample(&self, sampler: &mut dyn Sampler) -> Point3 {
         // Returns a random point in the camera defocus disk.
-        let p = random_unit_disk();
+        let p = sample_unit_disk(sampler.get_2d());
         self.center + p.x * self.defocus_disk_u + p.y * self.defocus_disk_v
     }
 
-    fn ray_color(ray: Ray, depth: i32, world: &HittableList) -> Color {
+    fn ray_color(
+            &self,
+            ray: Ray,
+            depth: i32,
+            world: &HittableList,
+            lights: &HittableList,
+            sampler: &mut dyn Sampler,
+            first_hit: Option<&mut AovSample>
+        ) -> Color {
         // If we've exceeded the ray bounce limit, no more light is gathered.
         if depth <= 0 {
             return Color::zero();
         }
-        if let Some(ref hit_record) = world.hit(&ray, Interval::new(0.001, f64::INFINITY)) {
-            if let Some((scattered, attenuation)) = hit_record.material.scatter(&ray, hit_record) {
-                return attenuation * Camera::ray_color(scattered, depth-1, world);
+
+        // If the ray hits nothing, return the background color.
+        let Some(mut hit_record) = world.hit(&ray, Interval::new(0.001, f64::INFINITY), sampler.rng()) else {
+            return self.background.color(&ray);
+        };
+        hit_record.set_footprint(&ray);
+        let hit_record = &hit_record;
+        if let Some(first_hit) = first_hit {
+            *first_hit = AovSample::new(&ray, hit_record);
+        }
+
+        let color_from_emission = hit_record.material.emitted(&ray, hit_record);
+        let Some(scatter_record) = hit_record.material.scatter(&ray, hit_record, sampler) else {
+            return color_from_emission;
+        };
+        let attenuation = scatter_record.attenuation;
+
+        let material_pdf = match scatter_record.scattered {
+            Scattered::Specular(scattered) => {
+                let sample_color = self.ray_color(scattered, depth-1, world, lights, sampler, None);
+                return color_from_emission + attenuation * sample_color;
+            },
+            Scattered::Pdf(pdf) => pdf
+        };
+
+        // Sample the lights and the material alike, and weight the sample by the density of the
+        // mixture. This is multiple importance sampling with the balance heuristic. An environment
+        // map counts as a light too.
+        let hittable_pdf = HittablePDF::new(lights, hit_record.point);
+        let (light_mixture, mixture_pdf);
+        let light_pdf: Option<&dyn PDF> = match (lights.objects.is_empty(), self.background.pdf()) {
+            (true, None) => None,
+            (false, None) => Some(&hittable_pdf),
+            (true, Some(environment_pdf)) => Some(environment_pdf),
+            (false, Some(environment_pdf)) => {
+                light_mixture = MixturePDF::new(&hittable_pdf, environment_pdf);
+                Some(&light_mixture)
             }
-            return Color::zero();
+        };
+        let pdf: &dyn PDF = match light_pdf {
+            Some(light_pdf) => {
+                mixture_pdf = MixturePDF::new(light_pdf, material_pdf.as_ref());
+                &mixture_pdf
+            },
+            None => material_pdf.as_ref()
+        };
+
+        let scattered = Ray::new(hit_record.point, pdf.generate(sampler), ray.time());
+        let pdf_value = pdf.value(scattered.direction());
+        if pdf_value <= 0. {
+            return color_from_emission;
+        }
+        let scattering = hit_record.material.scattering(&ray, hit_record, &scattered);
+        if scattering == Color::zero() {
+            return color_from_emission;
         }
-        let t = 0.5 * (ray.direction().normalize().y + 1.);
-        (1. - t)*Color::one() + t*Color::new(0.5, 0.7, 1.)
+
+        let sample_color = self.ray_color(scattered, depth-1, world, lights, sampler, None);
+        let color_from_scatter = (attenuation * scattering * sample_color) / pdf_value;
+
+        color_from_emission + color_from_scatter
     }
 }
@@ -1,27 +1,81 @@
+use std::io::{self, Write};
+
 use utils::interval::Interval;
 
 pub type Color = vector3::Vec3;
 
-pub fn write_color(pixel_color: Color) {
-    // Apply a linear to gamma transform for gamma 2
-    let r = linear_to_gamma(pixel_color.x);
-    let g = linear_to_gamma(pixel_color.y);
-    let b = linear_to_gamma(pixel_color.z);
+/// 图片中颜色值的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// 按 sRGB 传递函数编码，常见的 8 位照片和颜色贴图
+    Srgb,
+    /// 线性值，用于浮点图片（HDR、EXR）和粗糙度等数据贴图
+    Linear
+}
+
+impl ColorSpace {
+    /// 图片默认的色彩空间：浮点图片为线性，整数图片为 sRGB
+    pub fn of_image(image: &image::DynamicImage) -> Self {
+        match image {
+            image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => Self::Linear,
+            _ => Self::Srgb
+        }
+    }
+
+    /// 把编码值转换为线性值
+    pub fn decode(self, value: f64) -> f64 {
+        match self {
+            // The sRGB EOTF, with its linear segment near black.
+            Self::Srgb => if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) },
+            Self::Linear => value
+        }
+    }
+
+    /// 把线性值编码，[`ColorSpace::decode`] 的逆
+    pub fn encode(self, value: f64) -> f64 {
+        match self {
+            Self::Srgb => {
+                if value <= 0.0031308 {
+                    12.92 * value.max(0.)
+                } else {
+                    1.055 * value.powf(1. / 2.4) - 0.055
+                }
+            },
+            Self::Linear => value
+        }
+    }
+
+    /// 解码 RGBA 像素的颜色通道，alpha 总是线性的
+    pub fn decode_rgba(self, [r, g, b, a]: [f64; 4]) -> [f64; 4] {
+        [self.decode(r), self.decode(g), self.decode(b), a]
+    }
+}
+
+/// 将线性颜色转换为 8 位 sRGB 分量
+pub fn to_bytes(pixel_color: Color) -> [u8; 3] {
+    // Apply the sRGB transfer function.
+    let r = ColorSpace::Srgb.encode(pixel_color.x);
+    let g = ColorSpace::Srgb.encode(pixel_color.y);
+    let b = ColorSpace::Srgb.encode(pixel_color.z);
 
     // Translate the [0,1] component values to the byte range [0,255].
     const INTERVAL: Interval = Interval { min: 0., max: 0.999 };
-    let r_byte = (INTERVAL.clamp(r) * 256.) as i32;
-    let g_byte = (INTERVAL.clamp(g) * 256.) as i32;
-    let b_byte = (INTERVAL.clamp(b) * 256.) as i32;
+    let r_byte = (INTERVAL.clamp(r) * 256.) as u8;
+    let g_byte = (INTERVAL.clamp(g) * 256.) as u8;
+    let b_byte = (INTERVAL.clamp(b) * 256.) as u8;
 
-    // Write out the pixel color components.
-    println!("{} {} {}", r_byte, g_byte, b_byte);
+    [r_byte, g_byte, b_byte]
 }
 
-fn linear_to_gamma(linear_component: f64) -> f64 {
-    if linear_component > 0. {
-        linear_component.sqrt()
-    } else {
-        0.
-    }
+/// 线性颜色的亮度（Rec. 709 权重）
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// 以 ASCII PPM 格式写出一个像素
+pub fn write_color(out: &mut impl Write, pixel_color: Color) -> io::Result<()> {
+    let [r_byte, g_byte, b_byte] = to_bytes(pixel_color);
+
+    // Write out the pixel color components.
+    writeln!(out, "{} {} {}", r_byte, g_byte, b_byte)
 }
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    color::Color,
+    hittable::{object_key, HitRecord, Hittable},
+    material::{Isotropic, Material},
+    ray::Ray,
+    texture::Texture
+};
+use utils::{interval::{self, Interval}, rng::Rng};
+use vector3::Vec3;
+
+/// 密度均匀的参与介质（烟、雾），由一个封闭的边界物体限定范围
+///
+/// 射线在介质中按指数分布随机采样自由程，穿过介质时可能在内部任意位置发生散射。
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>
+}
+
+impl ConstantMedium {
+    /// 以纹理作为散射反照率创建介质，`boundary` 必须是封闭的凸物体
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, texture: Arc<dyn Texture>) -> Self {
+        Self::with_phase_function(boundary, density, Arc::new(Isotropic::new(texture)))
+    }
+
+    /// 以纯色作为散射反照率创建介质
+    pub fn from_color(boundary: Arc<dyn Hittable>, density: f64, albedo: Color) -> Self {
+        Self::with_phase_function(boundary, density, Arc::new(Isotropic::from_color(albedo)))
+    }
+
+    /// 使用任意相位函数材质创建介质
+    pub fn with_phase_function(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> Self {
+        Self { boundary, neg_inv_density: -density.recip(), phase_function }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord> {
+        // Find where the ray enters and leaves the boundary, regardless of the ray interval.
+        let record1 = self.boundary.hit(ray, interval::UNIVERSE, rng)?;
+        let record2 = self.boundary.hit(ray, Interval::new(record1.t + 0.0001, f64::INFINITY), rng)?;
+
+        let t1 = record1.t.max(ray_t.min).max(0.);
+        let t2 = record2.t.min(ray_t.max);
+        if t1 >= t2 {
+            return None;
+        }
+
+        let ray_length = ray.direction().norm();
+        let distance_inside_boundary = (t2 - t1) * ray_length;
+        let hit_distance = self.neg_inv_density * rng.random().ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = t1 + hit_distance / ray_length;
+        Some(HitRecord {
+            point: ray.at(t),
+            // The normal and face are arbitrary inside a volume.
+            normal: Vec3::from_x(1.),
+            front_face: true,
+            material: self.phase_function.clone(),
+            t,
+            object: object_key(self),
+            ..Default::default()
+        })
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vector3::Point3;
+
+    use crate::{material::Lambertian, sphere::Sphere};
+    use super::*;
+
+    fn medium(density: f64) -> ConstantMedium {
+        let material = Arc::new(Lambertian::from_color(Color::one()));
+        let boundary = Arc::new(Sphere::new_stationary_sphere(Point3::zero(), 1., material));
+        ConstantMedium::from_color(boundary, density, Color::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn test_free_flight_distance() {
+        let mut rng = Rng::new(9);
+        let ray_t = Interval::new(0.001, f64::INFINITY);
+        let ray = Ray::new(Point3::from_z(5.), Vec3::from_z(-1.), 0.);
+
+        // A dense medium scatters right where the ray enters the boundary.
+        let record = medium(1e6).hit(&ray, ray_t, &mut rng).unwrap();
+        assert!((record.t - 4.).abs() < 1e-3);
+
+        // A thin medium lets almost every ray pass through.
+        assert!(medium(1e-9).hit(&ray, ray_t, &mut rng).is_none());
+
+        // A ray starting inside the medium scatters right after its origin.
+        let inside = Ray::new(Point3::zero(), Vec3::from_z(-1.), 0.);
+        let record = medium(1e6).hit(&inside, ray_t, &mut rng).unwrap();
+        assert!(record.t < 0.01);
+
+        // Free-flight distances follow an exponential distribution truncated to the chord of
+        // length 2, whose mean is 1/density - 2 / (e^(2 density) - 1).
+        let density = 2.;
+        let medium = medium(density);
+        let distances: Vec<f64> = (0..20000)
+            .filter_map(|_| medium.hit(&ray, ray_t, &mut rng))
+            .map(|record| record.t - 4.)
+            .collect();
+        let mean = distances.iter().sum::<f64>() / distances.len() as f64;
+        let expected = density.recip() - 2. / ((2. * density).exp() - 1.);
+        assert!((mean - expected).abs() < 0.01, "{} != {}", mean, expected);
+    }
+}
@@ -0,0 +1,272 @@
+//! 降噪
+//!
+//! 边缘保持的 à-trous 小波滤波（Dammertz 等人的方法，权重参照 SVGF），在渲染完成后作用于帧缓冲。
+//! 滤波前先除以反照率，只对光照进行平滑，之后再乘回反照率，因此棋盘格等纹理的细节不会被模糊；
+//! 相邻像素之间的权重还受法线、反照率和按方差归一化的亮度差异约束，几何与材质的边缘得以保留。
+
+use crate::{
+    aov::{Aov, AovBuffers},
+    color::{luminance, Color},
+    frame_buffer::FrameBuffer
+};
+use vector3::Vec3;
+
+/// B3 样条的 5 个系数
+const KERNEL: [f64; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+/// 反照率低于此值的通道不做除法
+const MIN_ALBEDO: f64 = 1e-3;
+
+/// 引导降噪的辅助输出
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    /// 强度，0 表示不降噪，越大越平滑
+    pub strength: f64,
+    /// 迭代次数，第 i 次迭代的采样间隔为 2^i 个像素
+    pub iterations: u32,
+    /// 法线夹角的敏感度，越大越不跨越几何边缘
+    pub normal_sharpness: f64,
+    /// 反照率差异的容差，越小越不跨越材质边缘
+    pub albedo_tolerance: f64
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Self { strength: 1., iterations: 5, normal_sharpness: 128., albedo_tolerance: 0.1 }
+    }
+}
+
+impl Denoiser {
+    /// 降噪需要的辅助输出
+    pub const GUIDES: [Aov; 3] = [Aov::Albedo, Aov::Normal, Aov::Variance];
+
+    pub fn new(strength: f64) -> Self {
+        Self { strength, ..Default::default() }
+    }
+
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// 用 `aovs` 中的 [`Denoiser::GUIDES`] 引导，对 `beauty` 降噪
+    ///
+    /// 缺少反照率时不做解调，缺少法线时不按法线区分，缺少方差时由相邻像素估计。
+    pub fn denoise(&self, beauty: &FrameBuffer, aovs: &AovBuffers) -> FrameBuffer {
+        if self.strength <= 0. || self.iterations == 0 {
+            return beauty.clone();
+        }
+
+        let (width, height) = (beauty.width() as usize, beauty.height() as usize);
+        let guide = |aov| aovs.get(aov).filter(|buffer| {
+            (buffer.width() as usize, buffer.height() as usize) == (width, height)
+        }).map(FrameBuffer::pixels);
+        let albedo: Vec<Color> = match guide(Aov::Albedo) {
+            Some(albedo) => albedo.iter().map(|&albedo| demodulation(albedo)).collect(),
+            None => vec![Color::one(); width * height]
+        };
+        let normals = guide(Aov::Normal);
+
+        let mut illumination: Vec<Color> = beauty.pixels().iter().zip(&albedo)
+            .map(|(&color, &albedo)| color / albedo)
+            .collect();
+        // The variance belongs to the color, dividing by the albedo scales it too.
+        let mut variance: Vec<f64> = match guide(Aov::Variance) {
+            Some(variance) => variance.iter().zip(&albedo).map(|(variance, &albedo)| {
+                variance.x / luminance(albedo).powi(2)
+            }).collect(),
+            None => spatial_variance(&illumination, width, height)
+        };
+
+        let sigma_luminance = 4. * self.strength;
+        for iteration in 0..self.iterations {
+            let step = 1isize << iteration;
+            let filtered_variance = blur_variance(&variance, width, height);
+            let mut next_illumination = Vec::with_capacity(illumination.len());
+            let mut next_variance = Vec::with_capacity(variance.len());
+
+            for y in 0..height {
+                for x in 0..width {
+                    let p = y * width + x;
+                    let luminance_p = luminance(illumination[p]);
+                    let tolerance = sigma_luminance * filtered_variance[p].sqrt() + 1e-6;
+
+                    let (mut sum, mut sum_variance, mut sum_weight) = (Color::zero(), 0., 0.);
+                    for (dy, ky) in KERNEL.iter().enumerate() {
+                        let qy = y as isize + (dy as isize - 2) * step;
+                        if qy < 0 || qy >= height as isize {
+                            continue;
+                        }
+                        for (dx, kx) in KERNEL.iter().enumerate() {
+                            let qx = x as isize + (dx as isize - 2) * step;
+                            if qx < 0 || qx >= width as isize {
+                                continue;
+                            }
+
+                            let q = qy as usize * width + qx as usize;
+                            let weight_luminance = (-(luminance(illumination[q]) - luminance_p).abs() / tolerance).exp();
+                            let weight_normal = normals.map_or(1., |normals| {
+                                normal_weight(normals[p], normals[q], self.normal_sharpness)
+                            });
+                            let weight_albedo = (-(albedo[p] - albedo[q]).squared()
+                                / (self.albedo_tolerance * self.albedo_tolerance)).exp();
+
+                            let weight = kx * ky * weight_luminance * weight_normal * weight_albedo;
+                            sum += weight * illumination[q];
+                            sum_variance += weight * weight * variance[q];
+                            sum_weight += weight;
+                        }
+                    }
+
+                    if sum_weight > 0. {
+                        next_illumination.push(sum / sum_weight);
+                        next_variance.push(sum_variance / (sum_weight * sum_weight));
+                    } else {
+                        next_illumination.push(illumination[p]);
+                        next_variance.push(variance[p]);
+                    }
+                }
+            }
+
+            illumination = next_illumination;
+            variance = next_variance;
+        }
+
+        let pixels = illumination.into_iter().zip(&albedo).map(|(illumination, &albedo)| {
+            illumination * albedo
+        }).collect();
+        FrameBuffer::from_pixels(beauty.width(), beauty.height(), pixels).with_tone_mapping(beauty.tone_mapping())
+    }
+}
+
+/// 解调时除以的颜色，太暗的通道保持不变
+fn demodulation(albedo: Color) -> Color {
+    let channel = |value: f64| if value > MIN_ALBEDO { value } else { 1. };
+    Color::new(channel(albedo.x), channel(albedo.y), channel(albedo.z))
+}
+
+/// 法线夹角的权重，背景（法线为零）只与背景相连
+fn normal_weight(p: Vec3, q: Vec3, sharpness: f64) -> f64 {
+    match (p == Vec3::zero(), q == Vec3::zero()) {
+        (true, true) => 1.,
+        // Normals averaged over a pixel are shorter than one, only their directions are compared.
+        (false, false) => p.normalize().dot(&q.normalize()).max(0.).powf(sharpness),
+        _ => 0.
+    }
+}
+
+/// 没有方差时，用 3×3 邻域内亮度的方差代替
+fn spatial_variance(illumination: &[Color], width: usize, height: usize) -> Vec<f64> {
+    neighborhood(width, height, |neighbors| {
+        let values: Vec<f64> = neighbors.map(|(q, _)| luminance(illumination[q])).collect();
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64
+    })
+}
+
+/// 以 3×3 高斯核平滑方差，减少估计本身的噪声
+fn blur_variance(variance: &[f64], width: usize, height: usize) -> Vec<f64> {
+    neighborhood(width, height, |neighbors| {
+        let (sum, sum_weight) = neighbors.fold((0., 0.), |(sum, sum_weight), (q, weight)| {
+            (sum + weight * variance[q], sum_weight + weight)
+        });
+        sum / sum_weight
+    })
+}
+
+/// 对每个像素调用 `f`，传入图像内 3×3 邻域中的各像素与其高斯权重
+fn neighborhood(
+        width: usize,
+        height: usize,
+        f: impl Fn(&mut dyn Iterator<Item = (usize, f64)>) -> f64
+    ) -> Vec<f64> {
+    const GAUSSIAN: [f64; 3] = [0.25, 0.5, 0.25];
+
+    (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| {
+        let mut neighbors = (0..3).flat_map(|dy| (0..3).map(move |dx| (dx, dy))).filter_map(|(dx, dy)| {
+            let (qx, qy) = ((x + dx).checked_sub(1)?, (y + dy).checked_sub(1)?);
+            (qx < width && qy < height).then(|| (qy * width + qx, GAUSSIAN[dx] * GAUSSIAN[dy]))
+        });
+        f(&mut neighbors)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::rng::Rng;
+
+    const SIZE: u32 = 32;
+    /// 噪声的标准差
+    const NOISE: f64 = 0.3;
+
+    /// 光照为 1 加噪声、反照率为 `albedo(x, y)` 的图像及其引导，和没有噪声的参考图像
+    fn noisy(albedo: impl Fn(u32, u32) -> Color) -> (FrameBuffer, AovBuffers, FrameBuffer) {
+        let mut rng = Rng::new(7);
+        let mut beauty = FrameBuffer::new(SIZE, SIZE);
+        let mut albedos = FrameBuffer::new(SIZE, SIZE);
+        let mut reference = FrameBuffer::new(SIZE, SIZE);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let noise = NOISE * 3f64.sqrt() * rng.random_range(-1., 1.);
+                beauty.set_pixel(x, y, (1. + noise) * albedo(x, y));
+                albedos.set_pixel(x, y, albedo(x, y));
+                reference.set_pixel(x, y, albedo(x, y));
+            }
+        }
+
+        let mut aovs = AovBuffers::default();
+        aovs.insert(Aov::Albedo, albedos);
+        let normals = vec![Vec3::from_z(1.); (SIZE * SIZE) as usize];
+        aovs.insert(Aov::Normal, FrameBuffer::from_pixels(SIZE, SIZE, normals));
+        let variances = (0..SIZE * SIZE).map(|i| {
+            let albedo = albedo(i % SIZE, i / SIZE);
+            Color::isotropic((NOISE * luminance(albedo)).powi(2))
+        }).collect();
+        aovs.insert(Aov::Variance, FrameBuffer::from_pixels(SIZE, SIZE, variances));
+
+        (beauty, aovs, reference)
+    }
+
+    fn error(image: &FrameBuffer, reference: &FrameBuffer) -> f64 {
+        image.pixels().iter().zip(reference.pixels()).map(|(a, b)| (*a - *b).squared()).sum::<f64>()
+            / image.pixels().len() as f64
+    }
+
+    #[test]
+    fn test_flat() {
+        let (beauty, aovs, reference) = noisy(|_, _| Color::isotropic(0.5));
+        let denoised = Denoiser::default().denoise(&beauty, &aovs);
+        assert!(error(&denoised, &reference) < 0.1 * error(&beauty, &reference));
+
+        // A stronger filter smooths more, and zero strength leaves the image alone.
+        let stronger = Denoiser::new(4.).denoise(&beauty, &aovs);
+        assert!(error(&stronger, &reference) < error(&denoised, &reference));
+        assert_eq!(Denoiser::new(0.).denoise(&beauty, &aovs).pixels(), beauty.pixels());
+
+        // Without the variance, it is estimated from the neighbors.
+        let mut guides = AovBuffers::default();
+        guides.insert(Aov::Albedo, aovs.get(Aov::Albedo).unwrap().clone());
+        let estimated = Denoiser::default().denoise(&beauty, &guides);
+        assert!(error(&estimated, &reference) < 0.2 * error(&beauty, &reference));
+    }
+
+    #[test]
+    fn test_texture_edges() {
+        // A checker with 4 pixel squares, the edges fall between pixels.
+        let checker = |x: u32, y: u32| {
+            if (x / 4 + y / 4).is_multiple_of(2) { Color::new(0.9, 0.8, 0.1) } else { Color::new(0.1, 0.2, 0.7) }
+        };
+        let (beauty, aovs, reference) = noisy(checker);
+        let denoised = Denoiser::default().denoise(&beauty, &aovs);
+        assert!(error(&denoised, &reference) < 0.1 * error(&beauty, &reference));
+
+        // Pixels on both sides of every edge keep their own color instead of blending.
+        for x in [3, 4, 7, 8, 11, 12] {
+            for y in 0..SIZE {
+                let (color, expected) = (denoised.pixel(x, y), reference.pixel(x, y));
+                assert!((color - expected).norm() < 0.1, "({}, {}): {} != {}", x, y, color, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! 环境贴图
+//!
+//! 等距柱状投影（经纬度）的 HDR 图片包围整个场景，作为无穷远处的光源。纹理坐标与球体的
+//! [`Sphere::get_sphere_uv`] 一致：u 绕 y 轴从 -x 方向开始，v 从 -y 到 +y，图片的第一行是天顶。
+//!
+//! 按像素亮度建立二维分布对方向做重要性采样，亮的区域（如太阳）被采样得更多。
+
+use std::path::Path;
+
+use crate::{
+    color::{luminance, Color, ColorSpace},
+    pdf::PDF,
+    sampler::Sampler,
+    sphere::Sphere
+};
+use utils::rtweekend::{degree_to_radian, Degree, PI};
+use vector3::{matrix::Mat3, Vec3};
+
+/// 经纬度环境贴图
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    /// 线性颜色，按行存储，第一行为天顶
+    pixels: Vec<Color>,
+    intensity: f64,
+    /// 世界空间到贴图空间的旋转
+    world_to_map: Mat3,
+    map_to_world: Mat3,
+    /// 每一行内按列的分布
+    rows: Vec<Distribution1D>,
+    /// 各行之间的分布
+    marginal: Distribution1D
+}
+
+impl EnvironmentMap {
+    /// 由 `width` x `height` 个按行存储的线性颜色创建环境贴图
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height, "invalid environment map size");
+
+        // Weight each pixel by its luminance and by sin(theta), the area it covers on the sphere.
+        let rows: Vec<Distribution1D> = pixels.chunks(width).enumerate().map(|(y, row)| {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            Distribution1D::new(row.iter().map(|&color| luminance(color).max(0.) * sin_theta).collect())
+        }).collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral).collect());
+
+        Self {
+            width,
+            height,
+            pixels,
+            intensity: 1.,
+            world_to_map: Mat3::identity(),
+            map_to_world: Mat3::identity(),
+            rows,
+            marginal
+        }
+    }
+
+    /// 加载 Radiance HDR（.hdr）、OpenEXR（.exr）或其他格式的图片，整数格式的图片按 sRGB 解码
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, image::ImageError> {
+        let image = image::open(path)?;
+        let color_space = ColorSpace::of_image(&image);
+        let image = image.into_rgb32f();
+        let (width, height) = image.dimensions();
+        let pixels = image.pixels()
+            .map(|pixel| {
+                let [r, g, b] = pixel.0.map(|channel| color_space.decode(channel as f64));
+                Color::new(r, g, b)
+            })
+            .collect();
+
+        Ok(Self::new(width as usize, height as usize, pixels))
+    }
+
+    /// 绕 y 轴旋转贴图 `angle` 度
+    pub fn with_rotation(mut self, angle: Degree) -> Self {
+        self.map_to_world = Mat3::rotation(Vec3::from_y(1.), degree_to_radian(angle));
+        self.world_to_map = self.map_to_world.transpose();
+        self
+    }
+
+    /// 把贴图的亮度乘以 `intensity`
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// 沿 `direction` 方向看到的颜色
+    pub fn color(&self, direction: &Vec3) -> Color {
+        let (u, v) = Sphere::get_sphere_uv(&(self.world_to_map * direction.normalize()));
+        let (x, y) = self.pixel_index(u, v);
+
+        self.intensity * self.pixels[y * self.width + x]
+    }
+
+    /// 纹理坐标所在的像素
+    fn pixel_index(&self, u: f64, v: f64) -> (usize, usize) {
+        // Flip V to image coordinates, the first row is the zenith.
+        let x = (u * self.width as f64) as usize;
+        let y = ((1. - v) * self.height as f64) as usize;
+
+        (x.min(self.width - 1), y.min(self.height - 1))
+    }
+}
+
+impl PDF for EnvironmentMap {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let direction = self.world_to_map * direction.normalize();
+        let sin_theta = (1. - direction.y * direction.y).max(0.).sqrt();
+        if sin_theta == 0. {
+            return 0.;
+        }
+
+        let (u, v) = Sphere::get_sphere_uv(&direction);
+        let (x, y) = self.pixel_index(u, v);
+        let uv_pdf = if self.marginal.integral > 0. {
+            self.rows[y].func[x] / self.marginal.integral
+        } else {
+            1.
+        };
+
+        // Convert the density over the (u, v) square into a density over solid angle.
+        uv_pdf / (2. * PI * PI * sin_theta)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let (u1, u2) = sampler.get_2d();
+        let (image_v, row) = self.marginal.sample(u2);
+        let (u, _) = self.rows[row].sample(u1);
+
+        // Invert the mapping of Sphere::get_sphere_uv.
+        let theta = (1. - image_v) * PI;
+        let phi = u * 2. * PI;
+        let direction = Vec3::new(-theta.sin() * phi.cos(), -theta.cos(), theta.sin() * phi.sin());
+
+        self.map_to_world * direction
+    }
+}
+
+/// [0, 1) 上的分段常数分布
+struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    /// `func` 在 [0, 1) 上的积分
+    integral: f64
+}
+
+impl Distribution1D {
+    fn new(func: Vec<f64>) -> Self {
+        let n = func.len() as f64;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.);
+        for value in &func {
+            cdf.push(cdf.last().unwrap() + value / n);
+        }
+
+        let integral = *cdf.last().unwrap();
+        if integral > 0. {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            // Fall back to a uniform distribution when everything is black.
+            cdf.iter_mut().enumerate().for_each(|(i, c)| *c = i as f64 / n);
+        }
+
+        Self { func, cdf, integral }
+    }
+
+    /// 把 `u` 映射为分布中的一点，返回该点及其所在的段
+    fn sample(&self, u: f64) -> (f64, usize) {
+        let offset = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.func.len() - 1);
+
+        // Place the sample linearly within its segment.
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0. { (u - self.cdf[offset]) / width } else { 0. };
+
+        ((offset as f64 + du) / self.func.len() as f64, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{pdf::SpherePDF, sampler::SamplerKind};
+    use super::*;
+
+    /// 左半边暗、右半边亮并带有一个“太阳”的贴图
+    fn sun_map() -> EnvironmentMap {
+        let (width, height) = (32, 16);
+        let pixels = (0..width * height).map(|i| {
+            let (x, y) = (i % width, i / width);
+            if (x, y) == (20, 5) {
+                Color::new(500., 400., 300.)
+            } else if x >= width / 2 {
+                Color::isotropic(0.5)
+            } else {
+                Color::zero()
+            }
+        }).collect();
+
+        EnvironmentMap::new(width, height, pixels)
+    }
+
+    #[test]
+    fn test_mapping() {
+        // Colors by column: the directions -x, +z, +x and -z fall into consecutive columns.
+        let pixels = [Color::from_x(1.), Color::from_y(1.), Color::from_z(1.), Color::one()];
+        let map = EnvironmentMap::new(4, 1, pixels.to_vec());
+        assert_eq!(map.color(&Vec3::new(-1., 0., 0.01)), Color::from_x(1.));
+        assert_eq!(map.color(&Vec3::from_z(1.)), Color::from_y(1.));
+        assert_eq!(map.color(&Vec3::from_x(1.)), Color::from_z(1.));
+        assert_eq!(map.color(&Vec3::from_z(-1.)), Color::one());
+
+        // Rotating by 90 degrees about y brings +x to where +z was.
+        let map = map.with_rotation(90.).with_intensity(2.);
+        assert_eq!(map.color(&Vec3::from_z(-1.)), Color::from_z(2.));
+    }
+
+    #[test]
+    fn test_importance_sampling() {
+        let map = sun_map().with_rotation(30.);
+        let mut sampler = SamplerKind::Sobol.create(4, 1 << 16);
+        sampler.start_pixel(0, 0);
+
+        // The density integrates to one over the sphere.
+        let count = 1 << 16;
+        let integral = (0..count).map(|index| {
+            sampler.start_sample(index);
+            map.value(&SpherePDF.generate(sampler.as_mut())) * 4. * PI
+        }).sum::<f64>() / count as f64;
+        assert!((integral - 1.).abs() < 0.02, "{}", integral);
+
+        // Samples never land on black pixels, and the estimate of the irradiance-like integral of
+        // the luminance matches the uniform estimate.
+        let (mut sampled, mut uniform) = (0., 0.);
+        for index in 0..count {
+            sampler.start_sample(index);
+            let direction = map.generate(sampler.as_mut());
+            let color = map.color(&direction);
+            assert!(luminance(color) > 0.);
+            sampled += luminance(color) / map.value(&direction);
+            uniform += luminance(map.color(&SpherePDF.generate(sampler.as_mut()))) * 4. * PI;
+        }
+        let (sampled, uniform) = (sampled / count as f64, uniform / count as f64);
+        assert!((sampled / uniform - 1.).abs() < 0.05, "{} != {}", sampled, uniform);
+    }
+
+    #[test]
+    fn test_load() {
+        let image = image::Rgb32FImage::from_fn(4, 2, |x, y| image::Rgb([x as f32 * 4., y as f32, 0.25]));
+        for extension in ["hdr", "exr"] {
+            let path = std::env::temp_dir().join(format!("the_next_week_environment.{}", extension));
+            image.save(&path).unwrap();
+            let map = EnvironmentMap::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!((map.width, map.height), (4, 2));
+            // Radiance HDR keeps 8 bits of mantissa per channel.
+            let pixel = map.pixels[7];
+            assert!((pixel.x - 12.).abs() < 0.1 && (pixel.y - 1.).abs() < 0.01 && (pixel.z - 0.25).abs() < 0.01);
+        }
+    }
+}
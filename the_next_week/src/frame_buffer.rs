@@ -0,0 +1,267 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path
+};
+
+use exr::prelude::{
+    f16,
+    AnyChannel,
+    AnyChannels,
+    Encoding,
+    FlatSamples,
+    Image,
+    Layer,
+    LayerAttributes,
+    SmallVec,
+    WritableImage
+};
+
+use crate::{color::{to_bytes, write_color, Color}, tone_map::ToneMapping};
+
+/// 输出图片格式
+///
+/// PNG 和 PPM 经过 sRGB 编码并截断到 8 位；EXR、HDR 和 PFM 保存未截断的线性值，用于合成和降噪。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// PNG，扩展名 `.png`
+    Png,
+    /// 二进制 PPM（P6），扩展名 `.ppm` 或 `.pnm`
+    Ppm,
+    /// ASCII PPM（P3），没有专门的扩展名，需要显式选择
+    PpmAscii,
+    /// OpenEXR，扩展名 `.exr`
+    Exr(ExrPrecision),
+    /// Radiance RGBE，扩展名 `.hdr`
+    Hdr,
+    /// 32 位浮点的 Portable Float Map，扩展名 `.pfm`
+    Pfm
+}
+
+/// EXR 通道的浮点精度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExrPrecision {
+    /// 16 位半精度，文件更小，足以保存颜色
+    #[default]
+    Half,
+    /// 32 位单精度，用于深度、位置等需要精确值的数据
+    Float
+}
+
+impl OutputFormat {
+    /// 根据文件扩展名（不区分大小写）推断输出格式
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "ppm" | "pnm" => Some(Self::Ppm),
+            "exr" => Some(Self::Exr(ExrPrecision::default())),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            _ => None
+        }
+    }
+
+    /// 保存未截断的线性浮点值，而不是 8 位颜色
+    pub fn is_floating_point(self) -> bool {
+        matches!(self, Self::Exr(_) | Self::Hdr | Self::Pfm)
+    }
+}
+
+/// 帧缓冲，按行优先顺序保存每个像素的线性颜色
+///
+/// 写出 8 位格式时先施加色调映射，浮点格式保存原始的线性值。
+#[derive(Clone)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+    tone_mapping: ToneMapping
+}
+
+impl FrameBuffer {
+    /// 创建全黑的帧缓冲
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::from_pixels(width, height, vec![Color::zero(); (width * height) as usize])
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize, "pixel count does not match {}x{}", width, height);
+        Self { width, height, pixels, tone_mapping: ToneMapping::default() }
+    }
+
+    /// 写出 8 位格式时使用的色调映射
+    pub fn with_tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.tone_mapping
+    }
+
+    pub fn set_tone_mapping(&mut self, tone_mapping: ToneMapping) {
+        self.tone_mapping = tone_mapping;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    /// 转换为 8 位 RGB 图像（色调映射，sRGB 编码并截断）
+    pub fn to_rgb8(&self) -> image::RgbImage {
+        let bytes = self.pixels.iter().flat_map(|&color| to_bytes(self.tone_mapping.apply(color))).collect();
+        image::RgbImage::from_raw(self.width, self.height, bytes).unwrap()
+    }
+
+    /// 保存到文件，格式由扩展名决定，见 [`OutputFormat::from_path`]
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let format = OutputFormat::from_path(path).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported output format: {}", path.display())
+        ))?;
+
+        self.save_with_format(path, format)
+    }
+
+    pub fn save_with_format(&self, path: impl AsRef<Path>, format: OutputFormat) -> io::Result<()> {
+        match format {
+            OutputFormat::Png => self.to_rgb8()
+                .save_with_format(path, image::ImageFormat::Png)
+                .map_err(io::Error::other),
+            OutputFormat::Ppm => {
+                let mut out = BufWriter::new(File::create(path)?);
+                self.write_ppm(&mut out)?;
+                out.flush()
+            },
+            OutputFormat::PpmAscii => {
+                let mut out = BufWriter::new(File::create(path)?);
+                self.write_ppm_ascii(&mut out)?;
+                out.flush()
+            },
+            OutputFormat::Exr(precision) => self.write_exr(path, precision),
+            OutputFormat::Hdr => {
+                let mut out = BufWriter::new(File::create(path)?);
+                self.write_hdr(&mut out)?;
+                out.flush()
+            },
+            OutputFormat::Pfm => {
+                let mut out = BufWriter::new(File::create(path)?);
+                self.write_pfm(&mut out)?;
+                out.flush()
+            }
+        }
+    }
+
+    /// 以 OpenEXR 格式写出 R、G、B 三个通道
+    pub fn write_exr(&self, path: impl AsRef<Path>, precision: ExrPrecision) -> io::Result<()> {
+        write_exr(path, &[("", self)], precision)
+    }
+
+    /// 以 Radiance RGBE 格式写出，每个通道共享 8 位指数
+    pub fn write_hdr(&self, out: &mut impl Write) -> io::Result<()> {
+        let pixels: Vec<_> = self.pixels.iter()
+            .map(|color| image::Rgb([color.x as f32, color.y as f32, color.z as f32].map(|channel| channel.max(0.))))
+            .collect();
+
+        image::codecs::hdr::HdrEncoder::new(out)
+            .encode(&pixels, self.width as usize, self.height as usize)
+            .map_err(io::Error::other)
+    }
+
+    /// 以 PFM 格式写出，小端序的 32 位浮点，按从下到上的顺序存储各行
+    pub fn write_pfm(&self, out: &mut impl Write) -> io::Result<()> {
+        // A negative scale marks little-endian data.
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for row in self.pixels.chunks(self.width as usize).rev() {
+            for color in row {
+                for channel in [color.x, color.y, color.z] {
+                    out.write_all(&(channel as f32).to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 以二进制 PPM（P6）格式写出
+    pub fn write_ppm(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(self.to_rgb8().as_raw())
+    }
+
+    /// 以 ASCII PPM（P3）格式写出
+    pub fn write_ppm_ascii(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "P3\n{} {}\n255", self.width, self.height)?;
+        for &pixel_color in &self.pixels {
+            write_color(out, self.tone_mapping.apply(pixel_color))?;
+        }
+
+        Ok(())
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel ({}, {}) out of bounds", x, y);
+        (y * self.width + x) as usize
+    }
+}
+
+/// 把多个同样大小的帧缓冲写入同一个 OpenEXR 文件
+///
+/// 每个帧缓冲对应三个通道：名称为空时为 `R`、`G`、`B`，否则为 `名称.R`、`名称.G`、`名称.B`，合成软件会把
+/// 后者识别为单独的层。
+pub fn write_exr(path: impl AsRef<Path>, layers: &[(&str, &FrameBuffer)], precision: ExrPrecision) -> io::Result<()> {
+    let (width, height) = layers.first()
+        .map(|(_, buffer)| (buffer.width, buffer.height))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no layers to write"))?;
+
+    let mut channels = SmallVec::new();
+    for &(name, buffer) in layers {
+        if (buffer.width, buffer.height) != (width, height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("layer `{}` is {}x{}, expected {}x{}", name, buffer.width, buffer.height, width, height)
+            ));
+        }
+
+        for (channel, component) in ["R", "G", "B"].into_iter().zip(0..) {
+            let values = buffer.pixels.iter().map(|color| color[component]);
+            let samples = match precision {
+                ExrPrecision::Half => FlatSamples::F16(values.map(f16::from_f64).collect()),
+                ExrPrecision::Float => FlatSamples::F32(values.map(|value| value as f32).collect())
+            };
+            let channel = if name.is_empty() { channel.to_string() } else { format!("{}.{}", name, channel) };
+            channels.push(AnyChannel::new(channel.as_str(), samples));
+        }
+    }
+
+    let layer = Layer::new(
+        (width as usize, height as usize),
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(channels)
+    );
+    Image::from_layer(layer).write().to_file(path).map_err(io::Error::other)
+}
@@ -1,23 +1,53 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{aabb::AABB, material::{InvalidMaterial, Material}, ray::Ray};
-use utils::interval::Interval;
+use crate::{
+    aabb::AABB,
+    color::Color,
+    material::{InvalidMaterial, Material, ALPHA_CUTOFF},
+    ray::Ray,
+    sampler::Sampler,
+    texture::Texture
+};
+use utils::{interval::Interval, rng::Rng};
 use vector3::{Point3, Vec3};
 
-pub trait Hittable {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord>;
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord>;
     fn bounding_box(&self) -> &AABB;
+
+    /// 从 `origin` 沿 `direction` 看向物体的立体角概率密度，与 [`Hittable::random`] 的分布一致，默认不支持采样
+    ///
+    /// 和 `random` 一样把材质镂空的部分也算作物体的一部分，不考虑 alpha
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.
+    }
+
+    /// 从 `origin` 指向物体上随机一点的方向，镂空的部分也会被采到
+    fn random(&self, _origin: &Point3, _sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::from_x(1.)
+    }
 }
 
 #[derive(Clone)]
 pub struct HitRecord {
     pub point: Point3,
     pub normal: Vec3,
-    pub material: Rc<dyn Material>,
+    pub material: Arc<dyn Material>,
     pub t: f64,
     pub u: f64,
     pub v: f64,
-    pub front_face: bool
+    /// 纹理坐标 u 增大的方向 dP/du，不一定是单位向量，可能为零；各向异性材质用它确定方向
+    pub tangent: Vec3,
+    pub front_face: bool,
+    /// 光线锥在交点处覆盖的纹理坐标范围，0 表示未知
+    pub footprint: f64,
+    /// 被击中的物体的标识，见 [`object_key`]，0 表示未知
+    pub object: usize
+}
+
+/// 物体的标识（其地址），在同一次渲染中区分不同的物体
+pub fn object_key<T>(object: &T) -> usize {
+    object as *const T as usize
 }
 
 impl HitRecord {
@@ -31,6 +61,22 @@ impl HitRecord {
             -outward_normal
         }
     }
+
+    /// 由光线锥在交点处的宽度估计纹理坐标空间中的足迹，忽略掠射时沿表面的拉伸
+    pub fn set_footprint(&mut self, ray: &Ray) {
+        let length = self.tangent.norm();
+        self.footprint = if length > 0. { ray.cone_width(self.t) / length } else { 0. };
+    }
+
+    /// 在交点处按足迹查询纹理
+    pub fn texture_value(&self, texture: &dyn Texture) -> Color {
+        texture.filtered_value(self.u, self.v, &self.point, self.footprint)
+    }
+
+    /// 交点没有被材质镂空
+    pub fn is_opaque(&self) -> bool {
+        self.material.alpha(self) >= ALPHA_CUTOFF
+    }
 }
 
 impl Default for HitRecord {
@@ -38,24 +84,14 @@ impl Default for HitRecord {
         Self {
             point: Point3::default(),
             normal: Vec3::default(),
-            material: Rc::new(InvalidMaterial),
+            material: Arc::new(InvalidMaterial),
             t: 0.,
             u: 0.,
             v: 0.,
-            front_face: false
+            tangent: Vec3::zero(),
+            front_face: false,
+            footprint: 0.,
+            object: 0
         }
     }
 }
-
-#[derive(Default)]
-pub struct InvalidHittable;
-
-impl Hittable for InvalidHittable {
-    fn hit(&self, _ray: &Ray, _ray_t: Interval) -> Option<HitRecord> {
-        unimplemented!("InvalidHittable hit")
-    }
-
-    fn bounding_box(&self) -> &AABB {
-        unimplemented!("InvalidHittable bounding_box")
-    }
-}
@@ -1,23 +1,24 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{aabb::AABB, hittable::{HitRecord, Hittable}, ray::Ray};
-use utils::interval::Interval;
+use crate::{aabb::AABB, hittable::{HitRecord, Hittable}, ray::Ray, sampler::Sampler};
+use utils::{interval::Interval, rng::Rng};
+use vector3::{Point3, Vec3};
 
 #[derive(Default)]
 pub struct HittableList {
-    pub objects: Vec<Rc<dyn Hittable>>,
+    pub objects: Vec<Arc<dyn Hittable>>,
     bbox: AABB
 }
 
 impl HittableList {
-    pub fn from_hittable(object: Rc<dyn Hittable>) -> Self {
+    pub fn from_hittable(object: Arc<dyn Hittable>) -> Self {
         let mut hittable_list = HittableList::default();
         hittable_list.add(object);
 
         hittable_list
     }
 
-    pub fn add(&mut self, object: Rc<dyn Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.bbox = AABB::combine(&self.bbox, object.bounding_box());
         self.objects.push(object);
     }
@@ -26,12 +27,12 @@ impl HittableList {
         self.objects.clear();
     }
 
-    pub fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    pub fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord> {
         let mut close_st = ray_t.max;
 
         // 使用filter_map来收集可能的结果
         let hit_record = self.objects.iter().filter_map(|hittable| {
-            if let Some(record) = hittable.hit(ray, Interval::new(ray_t.min, close_st)) {
+            if let Some(record) = hittable.hit(ray, Interval::new(ray_t.min, close_st), rng) {
                 close_st = record.t;
                 Some(record)
             } else {
@@ -46,3 +47,27 @@ impl HittableList {
         &self.bbox
     }
 }
+
+impl Hittable for HittableList {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord> {
+        HittableList::hit(self, ray, ray_t, rng)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        HittableList::bounding_box(self)
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        // Every object is chosen with the same probability.
+        let weight = (self.objects.len() as f64).recip();
+        self.objects.iter().map(|object| weight * object.pdf_value(origin, direction)).sum()
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        if self.objects.is_empty() {
+            return Vec3::from_x(1.);
+        }
+        let index = (sampler.get_1d() * self.objects.len() as f64) as usize;
+        self.objects[index.min(self.objects.len() - 1)].random(origin, sampler)
+    }
+}
@@ -1,8 +1,22 @@
 pub mod hittable_list;
 pub mod material;
 pub mod camera;
+pub mod sampler;
+pub mod pdf;
+pub mod bxdf;
+pub mod background;
+pub mod environment;
 pub mod sphere;
+pub mod quad;
+pub mod mesh;
+pub mod transform;
+pub mod constant_medium;
+pub mod obj;
 pub mod color;
+pub mod frame_buffer;
+pub mod tone_map;
+pub mod aov;
+pub mod denoise;
 
 pub mod perlin;
 pub mod rtw_image;
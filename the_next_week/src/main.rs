@@ -1,21 +1,405 @@
-use the_next_week::scenes::{
-    bouncing_spheres::bouncing_spheres,
-    checkered_spheres::checkered_spheres,
-    earth::earth,
-    perlin_spheres::perlin_spheres
+use std::{error::Error, io, path::PathBuf, process::ExitCode, sync::Arc, time::Instant};
+
+use clap::{Parser, ValueEnum};
+use the_next_week::{
+    aov::Aov,
+    background::Background,
+    bvh::{BVHLayout, BVHOptions, SplitMethod},
+    denoise::Denoiser,
+    environment::EnvironmentMap,
+    frame_buffer::{ExrPrecision, OutputFormat},
+    sampler::{AdaptiveSampling, SamplerKind},
+    scenes::{self, scene_file, Scene},
+    tone_map::ToneMapOperator
 };
+use utils::rng::Rng;
 
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-fn main() {
-    const SCENE_ID: i32 = 4;
+/// Ray Tracing: The Next Week
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Name of a built-in scene (see --list-scenes), or path to a TOML scene file
+    #[arg(default_value = "perlin_spheres")]
+    scene: String,
+
+    /// Output image, the format is chosen by extension (.png, .ppm, .pnm, or unclamped linear .exr, .hdr, .pfm).
+    /// An ASCII PPM is written to stdout if omitted
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write a .ppm or .pnm output as ASCII (P3) instead of binary (P6)
+    #[arg(long)]
+    ascii: bool,
+
+    /// Exposure compensation in stops, applied before tone mapping
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+
+    /// Tone curve for 8-bit outputs, overrides the scene's own setting
+    #[arg(long, value_enum)]
+    tone_map: Option<ToneMapMode>,
+
+    /// Luminance mapped to white by the extended Reinhard curve
+    #[arg(long, default_value_t = 4.)]
+    white_point: f64,
+
+    /// Auxiliary passes rendered alongside the image, as layers of an .exr output or as
+    /// separate images named like image.normal.png
+    #[arg(long, value_enum, value_delimiter = ',', requires = "output")]
+    aov: Vec<AovMode>,
+
+    /// Denoise the image guided by the albedo, normal and variance passes, optionally with a strength
+    /// (default 1, larger is smoother)
+    #[arg(long, num_args = 0..=1, default_missing_value = "1")]
+    denoise: Option<f64>,
+
+    /// Precision of the channels of an .exr output
+    #[arg(long, value_enum, default_value_t = ExrPrecisionMode::Half)]
+    exr_precision: ExrPrecisionMode,
+
+    /// Rendered image width in pixel count
+    #[arg(short = 'w', long, value_parser = clap::value_parser!(i32).range(1..))]
+    width: Option<i32>,
+
+    /// Count of random samples for each pixel
+    #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+    samples: Option<i32>,
+
+    /// Maximum number of ray bounces into scene
+    #[arg(short = 'd', long, value_parser = clap::value_parser!(i32).range(0..))]
+    max_depth: Option<i32>,
+
+    /// Number of render threads, 0 means one per available core
+    #[arg(short = 'j', long)]
+    threads: Option<usize>,
+
+    /// Seed of the random number generator
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Strategy used to place the samples of each pixel
+    #[arg(long, value_enum)]
+    sampler: Option<SamplerMode>,
+
+    /// Adaptive sampling: pixels stop once their relative error falls below the threshold (default 0.05),
+    /// and the samples they save go to noisy pixels, so --samples stays the average per pixel
+    #[arg(long, num_args = 0..=1, default_missing_value = "0.05")]
+    adaptive: Option<f64>,
+
+    /// Samples each pixel takes before adaptive sampling may stop it, also the interval between checks;
+    /// capped at --samples
+    #[arg(long)]
+    min_samples: Option<u32>,
+
+    /// Samples a single pixel takes at most with adaptive sampling, four times --samples by default
+    #[arg(long)]
+    max_samples: Option<u32>,
+
+    /// Equirectangular environment map (.hdr, .exr) that replaces the scene's background
+    #[arg(long)]
+    environment: Option<PathBuf>,
+
+    /// Rotation of the environment map about the y axis in degrees
+    #[arg(long, default_value_t = 0., requires = "environment")]
+    env_rotation: f64,
+
+    /// Brightness multiplier of the environment map
+    #[arg(long, default_value_t = 1., requires = "environment")]
+    env_intensity: f64,
+
+    /// BVH construction strategy, overrides the scene's own setting
+    #[arg(long, value_enum)]
+    bvh: Option<BvhMode>,
+
+    /// Memory layout of the BVH
+    #[arg(long, value_enum)]
+    bvh_layout: Option<LayoutMode>,
+
+    /// Maximum number of objects in a BVH leaf
+    #[arg(long)]
+    leaf_size: Option<usize>,
+
+    /// Print BVH statistics and the render time
+    #[arg(long)]
+    stats: bool,
+
+    /// List the available scenes and exit
+    #[arg(long)]
+    list_scenes: bool
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum BvhMode {
+    /// Test every object in turn
+    #[value(name = "none")]
+    Off,
+    /// Split at the median along the longest axis
+    Median,
+    /// Binned surface area heuristic
+    Sah
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LayoutMode {
+    /// Separately allocated tree nodes
+    Tree,
+    /// Flat array of nodes traversed without recursion
+    Linear
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SamplerMode {
+    /// Independent uniform random samples
+    Independent,
+    /// Jittered samples, one in each stratum
+    Stratified,
+    /// Owen-scrambled Halton sequence
+    Halton,
+    /// Owen-scrambled Sobol sequence, best with a power of two samples
+    Sobol
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ToneMapMode {
+    /// Clip values above one
+    Clamp,
+    /// Reinhard's L / (1 + L) on luminance
+    Reinhard,
+    /// Reinhard with a white point (see --white-point)
+    ReinhardExtended,
+    /// Fitted ACES filmic curve
+    Aces,
+    /// Uncharted 2 filmic curve by John Hable
+    Hable,
+    /// AgX, desaturates highlights towards white
+    #[value(name = "agx")]
+    AgX,
+    /// Luminance bands around middle gray, for checking exposure
+    FalseColor
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AovMode {
+    /// Shading normal in world space
+    Normal,
+    /// Distance along the camera ray
+    Depth,
+    /// Hit point in world space
+    Position,
+    /// Surface color
+    Albedo,
+    /// Texture coordinates
+    Uv,
+    /// Object numbers
+    ObjectId,
+    /// Material numbers
+    MaterialId,
+    /// Time within the shutter interval
+    Time,
+    /// Variance of the pixel luminance
+    Variance,
+    /// Samples taken by each pixel, as a heatmap in 8-bit formats
+    SampleCount,
+    /// All of the above
+    All
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExrPrecisionMode {
+    /// 16-bit half floats
+    Half,
+    /// 32-bit floats
+    Float
+}
 
-    match SCENE_ID {
-        1 => bouncing_spheres(),
-        2 => checkered_spheres(),
-        3 => earth(),
-        4 => perlin_spheres(),
-        _ => panic!("Invalid scene id")
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    if cli.list_scenes {
+        scenes::names().for_each(|name| println!("{}", name));
+        return ExitCode::SUCCESS;
+    }
+
+    match render(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn render(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    // Randomly generated scenes draw from the same seed as the renderer, so they are reproducible too.
+    let mut rng = Rng::new(cli.seed.unwrap_or_default());
+    let mut scene = load_scene(&cli.scene, &mut rng)?;
+
+    if let Some(mode) = cli.bvh {
+        let split = match mode {
+            BvhMode::Off => None,
+            BvhMode::Median => Some(SplitMethod::Median),
+            BvhMode::Sah => Some(SplitMethod::Sah)
+        };
+        scene.bvh = split.map(|split| BVHOptions { split, ..scene.bvh.unwrap_or_default() });
+    }
+    if let Some(options) = scene.bvh.as_mut() {
+        if let Some(leaf_size) = cli.leaf_size {
+            options.max_leaf_size = leaf_size;
+        }
+        match cli.bvh_layout {
+            Some(LayoutMode::Tree) => options.layout = BVHLayout::Tree,
+            Some(LayoutMode::Linear) => options.layout = BVHLayout::Linear,
+            None => {}
+        }
+    }
+    let bvh_stats = scene.build_bvh();
+    if cli.stats {
+        match bvh_stats {
+            Some(stats) => eprintln!("BVH: {}", stats),
+            None => eprintln!("BVH: none")
+        }
+    }
+
+    let Scene { world, lights, mut camera, .. } = scene;
+
+    if let Some(width) = cli.width {
+        camera.image_width = width;
+    }
+    if let Some(samples) = cli.samples {
+        camera.samples_per_pixel = samples;
+    }
+    if let Some(max_depth) = cli.max_depth {
+        camera.max_depth = max_depth;
+    }
+    if let Some(threads) = cli.threads {
+        camera.threads = threads;
+    }
+    if let Some(seed) = cli.seed {
+        camera.seed = seed;
+    }
+    if let Some(mode) = cli.sampler {
+        camera.sampler = match mode {
+            SamplerMode::Independent => SamplerKind::Independent,
+            SamplerMode::Stratified => SamplerKind::Stratified,
+            SamplerMode::Halton => SamplerKind::Halton,
+            SamplerMode::Sobol => SamplerKind::Sobol
+        };
+    }
+    if let Some(threshold) = cli.adaptive {
+        let samples = camera.samples_per_pixel.max(1) as u32;
+        camera.adaptive_sampling.get_or_insert(AdaptiveSampling::new(16, 4 * samples, threshold)).threshold = threshold;
+    }
+    if let Some(adaptive) = &mut camera.adaptive_sampling {
+        if let Some(min_samples) = cli.min_samples {
+            adaptive.min_samples = min_samples;
+        }
+        if let Some(max_samples) = cli.max_samples {
+            adaptive.max_samples = max_samples;
+        }
+        adaptive.min_samples = adaptive.min_samples.min(camera.samples_per_pixel.max(1) as u32);
     }
+    if let Some(mode) = cli.tone_map {
+        camera.tone_mapping.operator = match mode {
+            ToneMapMode::Clamp => ToneMapOperator::Clamp,
+            ToneMapMode::Reinhard => ToneMapOperator::Reinhard,
+            ToneMapMode::ReinhardExtended => ToneMapOperator::ReinhardExtended { white: cli.white_point },
+            ToneMapMode::Aces => ToneMapOperator::Aces,
+            ToneMapMode::Hable => ToneMapOperator::Hable,
+            ToneMapMode::AgX => ToneMapOperator::AgX,
+            ToneMapMode::FalseColor => ToneMapOperator::FalseColor
+        };
+    }
+    if let Some(exposure) = cli.exposure {
+        camera.tone_mapping.exposure = exposure;
+    }
+    if let Some(path) = &cli.environment {
+        let map = EnvironmentMap::load(path)
+            .map_err(|err| format!("failed to load {}: {}", path.display(), err))?
+            .with_rotation(cli.env_rotation)
+            .with_intensity(cli.env_intensity);
+        camera.background = Background::Environment(Arc::new(map));
+    }
+
+    let mut aovs = Vec::new();
+    for mode in &cli.aov {
+        let selected: &[Aov] = match mode {
+            AovMode::Normal => &[Aov::Normal],
+            AovMode::Depth => &[Aov::Depth],
+            AovMode::Position => &[Aov::Position],
+            AovMode::Albedo => &[Aov::Albedo],
+            AovMode::Uv => &[Aov::Uv],
+            AovMode::ObjectId => &[Aov::ObjectId],
+            AovMode::MaterialId => &[Aov::MaterialId],
+            AovMode::Time => &[Aov::Time],
+            AovMode::Variance => &[Aov::Variance],
+            AovMode::SampleCount => &[Aov::SampleCount],
+            AovMode::All => &Aov::ALL
+        };
+        for &aov in selected {
+            if !aovs.contains(&aov) {
+                aovs.push(aov);
+            }
+        }
+    }
+
+    // The denoiser's guides and the sample counts for the statistics are rendered too, but only the
+    // requested passes are saved.
+    let mut passes = aovs.clone();
+    if cli.denoise.is_some() {
+        passes.extend(Denoiser::GUIDES.into_iter().filter(|aov| !aovs.contains(aov)));
+    }
+    let count_samples = cli.stats && camera.adaptive_sampling.is_some();
+    if count_samples && !passes.contains(&Aov::SampleCount) {
+        passes.push(Aov::SampleCount);
+    }
+
+    let start = Instant::now();
+    let (mut image, mut aov_buffers) = camera.render_with_aovs(&world, &lights, &passes);
+    if cli.stats {
+        eprintln!("Render time: {:.2?}", start.elapsed());
+    }
+    if let Some(counts) = aov_buffers.get(Aov::SampleCount).filter(|_| count_samples) {
+        let total: f64 = counts.pixels().iter().map(|count| count.x).sum();
+        eprintln!("Samples per pixel: {:.1} on average", total / counts.pixels().len() as f64);
+    }
+    if let Some(strength) = cli.denoise {
+        let start = Instant::now();
+        image = Denoiser::new(strength).denoise(&image, &aov_buffers);
+        if cli.stats {
+            eprintln!("Denoise time: {:.2?}", start.elapsed());
+        }
+    }
+    aov_buffers.retain(|aov| aovs.contains(&aov));
+    match &cli.output {
+        Some(path) => {
+            let format = match OutputFormat::from_path(path) {
+                Some(OutputFormat::Exr(_)) => OutputFormat::Exr(match cli.exr_precision {
+                    ExrPrecisionMode::Half => ExrPrecision::Half,
+                    ExrPrecisionMode::Float => ExrPrecision::Float
+                }),
+                Some(OutputFormat::Ppm) if cli.ascii => OutputFormat::PpmAscii,
+                Some(format) => format,
+                None => return Err(format!("unsupported output format: {}", path.display()).into())
+            };
+            aov_buffers.save(&image, path, format)?
+        },
+        None => image.write_ppm_ascii(&mut io::stdout().lock())?
+    }
+
+    Ok(())
+}
+
+fn load_scene(scene: &str, rng: &mut Rng) -> Result<Scene, Box<dyn Error>> {
+    if let Some(scene) = scenes::by_name(scene, rng) {
+        return Ok(scene);
+    }
+    if scene.ends_with(".toml") {
+        return Ok(scene_file::load(scene, rng)?);
+    }
+
+    let names = scenes::names().collect::<Vec<_>>().join(", ");
+    Err(format!("unknown scene `{}`, expected a TOML file or one of: {}", scene, names).into())
 }
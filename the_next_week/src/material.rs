@@ -1,48 +1,123 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{color::Color, hittable::HitRecord, ray::Ray, texture::{SolidColor, Texture}};
-use vector3::extension::{cos_theta, random_unit_vector, reflect, refract};
-use utils::rtweekend::random;
+use crate::{
+    bxdf::{
+        fresnel_conductor,
+        fresnel_dielectric,
+        BxDF,
+        BxDFPDF,
+        ConductorBxDF,
+        DielectricBxDF,
+        PrincipledBxDF,
+        TrowbridgeReitz
+    },
+    color::{luminance, Color},
+    hittable::HitRecord,
+    pdf::{CosinePDF, SpherePDF, PDF},
+    ray::Ray,
+    sampler::Sampler,
+    texture::{SolidColor, Texture}
+};
+use utils::rtweekend::PI;
+use vector3::{extension::{cos_theta, reflect, refract, sample_unit_vector}, onb::ONB, Vec3};
 
-pub trait Material {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)>;
+pub trait Material: Send + Sync {
+    /// 散射入射光线，随机决策取自 `sampler` 的下一组维度
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord>;
+
+    /// 散射到 `scattered` 方向的概率密度，只对按概率密度散射的材质有意义
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        0.
+    }
+
+    /// 散射到 `scattered` 方向时每个通道的权重，乘以 `attenuation` 后等于 BSDF 与余弦之积，
+    /// 默认各通道都等于 [`Material::scattering_pdf`]
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Color {
+        Color::isotropic(self.scattering_pdf(ray_in, hit_record, scattered))
+    }
+
+    /// 材质自身发出的光，默认不发光
+    fn emitted(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Color {
+        Color::zero()
+    }
+
+    /// 交点处的不透明度，低于 [`ALPHA_CUTOFF`] 的交点被剔除，光线直接穿过；默认完全不透明
+    fn alpha(&self, _hit_record: &HitRecord) -> f64 {
+        1.
+    }
+
+    /// 交点处表面的颜色（反照率），用于辅助输出和降噪；默认为黑色
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::zero()
+    }
+}
+
+/// 镂空（alpha cutout）的阈值
+pub const ALPHA_CUTOFF: f64 = 0.5;
+
+/// 材质的散射结果
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    pub scattered: Scattered
+}
+
+/// 散射光线的分布
+pub enum Scattered {
+    /// 镜面散射，直接追踪给定的光线，不参与重要性采样
+    Specular(Ray),
+    /// 按概率密度分布散射，积分器可以把它和朝向光源的分布混合
+    Pdf(Box<dyn PDF>)
 }
 
 #[derive(Default)]
 pub struct InvalidMaterial;
 
 impl Material for InvalidMaterial {
-    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         unimplemented!("InvalidMaterial scatter")
     }
 }
 
 pub struct Lambertian {
-    texture: Rc<dyn Texture>
+    texture: Arc<dyn Texture>,
+    alpha: Option<Arc<dyn Texture>>
 }
 
 impl Lambertian {
-    pub fn new(texture: Rc<dyn Texture>) -> Self {
-        Self { texture }
+    pub fn new(texture: Arc<dyn Texture>) -> Self {
+        Self { texture, alpha: None }
     }
 
     pub fn from_color(albedo: Color) -> Self {
-        Self { texture: Rc::new(SolidColor::new(albedo)) }
+        Self::new(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// 用纹理 `alpha` 的 alpha 通道镂空表面，如树叶、栅栏
+    pub fn with_alpha(mut self, alpha: Arc<dyn Texture>) -> Self {
+        self.alpha = Some(alpha);
+        self
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = hit_record.normal + random_unit_vector();
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let attenuation = hit_record.texture_value(self.texture.as_ref());
+        let pdf = Box::new(CosinePDF::new(&hit_record.normal));
 
-        // Catch degenerate scatter direction
-        if scatter_direction.near_zero() {
-            scatter_direction = hit_record.normal;
-        }
-        let scattered = Ray::new(hit_record.point, scatter_direction, ray_in.time());
-        let attenuation = self.texture.value(hit_record.u, hit_record.v, &hit_record.point);
+        Some(ScatterRecord { attenuation, scattered: Scattered::Pdf(pdf) })
+    }
 
-        Some((scattered, attenuation))
+    fn scattering_pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = hit_record.normal.dot(&scattered.direction().normalize());
+        (cos_theta / PI).max(0.)
+    }
+
+    fn alpha(&self, hit_record: &HitRecord) -> f64 {
+        texture_alpha(&self.alpha, hit_record)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        hit_record.texture_value(self.texture.as_ref())
     }
 }
 
@@ -59,16 +134,20 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         let reflected = reflect(ray_in.direction(), &hit_record.normal)
-            .normalize() + self.fuzz * random_unit_vector();
+            .normalize() + self.fuzz * sample_unit_vector(sampler.get_2d());
         let scattered = Ray::new(hit_record.point, reflected, ray_in.time());
         let attenuation = self.albedo;
         if scattered.direction().dot(&hit_record.normal) > 0. {
-            return Some((scattered, attenuation));
+            return Some(ScatterRecord { attenuation, scattered: Scattered::Specular(scattered) });
         }
         None
     }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        self.albedo
+    }
 }
 
 #[derive(Default)]
@@ -92,7 +171,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
         let ri = if hit_record.front_face {
             self.refraction_index.recip()
         } else {
@@ -104,7 +183,7 @@ impl Material for Dielectric {
         let sin_theta = (1. - cos_theta.powi(2)).sqrt();
 
         let direction = if ri * sin_theta > 1. ||
-            Dielectric::reflectance(cos_theta, ri) > random() {
+            Dielectric::reflectance(cos_theta, ri) > sampler.get_1d() {
             reflect(&unit_direction, &hit_record.normal) // 反射
         } else {
             refract(&unit_direction, &hit_record.normal, ri) // 折射
@@ -112,6 +191,398 @@ impl Material for Dielectric {
         let scattered = Ray::new(hit_record.point, direction, ray_in.time());
         let attenuation = Color::one();
 
-        Some((scattered, attenuation))
+        Some(ScatterRecord { attenuation, scattered: Scattered::Specular(scattered) })
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::one()
+    }
+}
+
+/// 常见金属在红、绿、蓝三个波长上的复折射率
+#[derive(Debug, Clone, Copy)]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminium
+}
+
+impl ConductorPreset {
+    /// 复折射率的实部 eta 和虚部（消光系数）k
+    pub fn ior(self) -> (Color, Color) {
+        match self {
+            Self::Gold => (Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603)),
+            Self::Copper => (Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142)),
+            Self::Aluminium => (Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837))
+        }
+    }
+}
+
+/// 导体（金属）表面，GGX 微表面反射，Fresnel 项由每个通道的复折射率 `eta` + i`k` 计算
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz
+}
+
+impl Conductor {
+    /// `roughness` 为 0 时是理想镜面，`anisotropy` 使表面沿切线方向更粗糙
+    pub fn new(eta: Color, k: Color, roughness: f64, anisotropy: f64) -> Self {
+        Self { eta, k, distribution: TrowbridgeReitz::from_roughness(roughness, anisotropy) }
+    }
+
+    pub fn from_preset(preset: ConductorPreset, roughness: f64, anisotropy: f64) -> Self {
+        let (eta, k) = preset.ior();
+        Self::new(eta, k, roughness, anisotropy)
+    }
+
+    fn bxdf(&self) -> ConductorBxDF {
+        ConductorBxDF { distribution: self.distribution, eta: self.eta, k: self.k }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let unit_direction = ray_in.direction().normalize();
+        if self.distribution.is_smooth() {
+            let cos_theta = cos_theta(&unit_direction, &hit_record.normal);
+            let reflected = reflect(&unit_direction, &hit_record.normal);
+            let scattered = Ray::new(hit_record.point, reflected, ray_in.time());
+            let attenuation = fresnel_conductor(cos_theta, self.eta, self.k);
+            return Some(ScatterRecord { attenuation, scattered: Scattered::Specular(scattered) });
+        }
+
+        let pdf = BxDFPDF::new(self.bxdf(), shading_frame(hit_record), -unit_direction);
+        Some(ScatterRecord { attenuation: Color::one(), scattered: Scattered::Pdf(Box::new(pdf)) })
+    }
+
+    fn scattering_pdf(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        let (wo, wi) = local_directions(ray_in, hit_record, scattered);
+        self.bxdf().pdf(&wo, &wi)
+    }
+
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Color {
+        let (wo, wi) = local_directions(ray_in, hit_record, scattered);
+        self.bxdf().f(&wo, &wi) * wi.z.abs()
+    }
+
+    /// 正入射时的反射率
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        fresnel_conductor(1., self.eta, self.k)
+    }
+}
+
+/// 粗糙电介质（如磨砂玻璃），GGX 微表面反射和透射，使用完整的 Fresnel 公式
+pub struct RoughDielectric {
+    refraction_index: f64,
+    distribution: TrowbridgeReitz
+}
+
+impl RoughDielectric {
+    /// `roughness` 为 0 时是光滑的玻璃，`anisotropy` 使表面沿切线方向更粗糙
+    pub fn new(refraction_index: f64, roughness: f64, anisotropy: f64) -> Self {
+        Self { refraction_index, distribution: TrowbridgeReitz::from_roughness(roughness, anisotropy) }
+    }
+
+    /// 着色法线总是朝向入射光线一侧，所以相对折射率取决于从哪一面射入
+    fn bxdf(&self, hit_record: &HitRecord) -> DielectricBxDF {
+        let eta = if hit_record.front_face {
+            self.refraction_index
+        } else {
+            self.refraction_index.recip()
+        };
+        DielectricBxDF { distribution: self.distribution, eta }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let unit_direction = ray_in.direction().normalize();
+        let bxdf = self.bxdf(hit_record);
+        if self.distribution.is_smooth() {
+            let cos_theta = cos_theta(&unit_direction, &hit_record.normal);
+            let (direction, attenuation) = if fresnel_dielectric(cos_theta, bxdf.eta) > sampler.get_1d() {
+                (reflect(&unit_direction, &hit_record.normal), Color::one())
+            } else {
+                // Radiance is scaled by the change in solid angle across the interface.
+                let refracted = refract(&unit_direction, &hit_record.normal, bxdf.eta.recip());
+                (refracted, Color::isotropic(bxdf.eta.powi(-2)))
+            };
+            let scattered = Ray::new(hit_record.point, direction, ray_in.time());
+            return Some(ScatterRecord { attenuation, scattered: Scattered::Specular(scattered) });
+        }
+
+        let pdf = BxDFPDF::new(bxdf, shading_frame(hit_record), -unit_direction);
+        Some(ScatterRecord { attenuation: Color::one(), scattered: Scattered::Pdf(Box::new(pdf)) })
+    }
+
+    fn scattering_pdf(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        let (wo, wi) = local_directions(ray_in, hit_record, scattered);
+        self.bxdf(hit_record).pdf(&wo, &wi)
+    }
+
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Color {
+        let (wo, wi) = local_directions(ray_in, hit_record, scattered);
+        self.bxdf(hit_record).f(&wo, &wi) * wi.z.abs()
+    }
+
+    fn albedo(&self, _hit_record: &HitRecord) -> Color {
+        Color::one()
+    }
+}
+
+/// Disney 风格的原理化（uber）材质，用一组直观的参数覆盖塑料、金属、玻璃、布料和清漆等外观
+///
+/// 每个参数都是纹理，标量参数取纹理颜色的亮度，取值范围为 [0, 1]：
+///
+/// - `base_color`：漫反射颜色、金属的正入射反射率和透射颜色
+/// - `metallic`：在电介质和金属之间混合
+/// - `roughness`、`anisotropy`：高光、金属和透射的微表面粗糙度，粗糙度至少为 0.04
+/// - `specular`：电介质高光的强度，0.5 对应折射率 1.5；`specular_tint` 把高光染成基础色
+/// - `sheen`：掠射角处漫反射偏向 sheen 颜色，用于布料；`sheen_tint` 把它染成基础色
+/// - `clearcoat`、`clearcoat_roughness`：覆盖在表面上的一层折射率为 1.5 的清漆
+/// - `transmission`：电介质基底中透射（玻璃）所占的比例，折射率为 `refraction_index`
+/// - `emission`、`emission_strength`：发出的光及其亮度倍数；`two_sided` 为真时背面也发光
+/// - `alpha`：用该纹理的 alpha 通道镂空表面
+///
+/// 可以只覆盖需要的参数：
+///
+/// ```
+/// use std::sync::Arc;
+/// use the_next_week::{material::Principled, texture::SolidColor};
+/// use vector3::Vec3;
+///
+/// let gold = Principled {
+///     base_color: Arc::new(SolidColor::new(Vec3::new(1., 0.78, 0.34))),
+///     metallic: Principled::constant(1.),
+///     roughness: Principled::constant(0.3),
+///     ..Default::default()
+/// };
+/// ```
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    pub anisotropy: Arc<dyn Texture>,
+    pub specular: Arc<dyn Texture>,
+    pub specular_tint: Arc<dyn Texture>,
+    pub sheen: Arc<dyn Texture>,
+    pub sheen_tint: Arc<dyn Texture>,
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_roughness: Arc<dyn Texture>,
+    pub transmission: Arc<dyn Texture>,
+    pub refraction_index: f64,
+    pub emission: Arc<dyn Texture>,
+    pub emission_strength: Arc<dyn Texture>,
+    pub two_sided: bool,
+    pub alpha: Option<Arc<dyn Texture>>
+}
+
+impl Principled {
+    /// 取值处处为 `value` 的标量参数
+    pub fn constant(value: f64) -> Arc<dyn Texture> {
+        Arc::new(SolidColor::new(Color::isotropic(value)))
+    }
+
+    /// 在交点处求出各个参数
+    fn bxdf(&self, hit_record: &HitRecord) -> PrincipledBxDF {
+        let scalar = |texture: &Arc<dyn Texture>| luminance(hit_record.texture_value(texture.as_ref())).clamp(0., 1.);
+
+        // Keep the lobes rough enough to be importance sampled together with the lights.
+        let roughness = scalar(&self.roughness).max(0.04);
+        let clearcoat_roughness = scalar(&self.clearcoat_roughness).max(0.04);
+        let eta = if hit_record.front_face {
+            self.refraction_index
+        } else {
+            self.refraction_index.recip()
+        };
+        let bxdf = PrincipledBxDF {
+            base_color: hit_record.texture_value(self.base_color.as_ref()),
+            metallic: scalar(&self.metallic),
+            specular: scalar(&self.specular),
+            specular_tint: scalar(&self.specular_tint),
+            sheen: scalar(&self.sheen),
+            sheen_tint: scalar(&self.sheen_tint),
+            clearcoat: scalar(&self.clearcoat),
+            transmission: scalar(&self.transmission),
+            distribution: TrowbridgeReitz::from_roughness(roughness, scalar(&self.anisotropy)),
+            clearcoat_distribution: TrowbridgeReitz::from_roughness(clearcoat_roughness, 0.),
+            eta
+        };
+
+        // Inside a transmissive object only the glass interface is seen from the back.
+        if !hit_record.front_face && bxdf.transmission > 0. {
+            return PrincipledBxDF { metallic: 0., sheen: 0., clearcoat: 0., transmission: 1., ..bxdf };
+        }
+        bxdf
+    }
+}
+
+impl Default for Principled {
+    /// 灰白色、中等粗糙度的塑料
+    fn default() -> Self {
+        Self {
+            base_color: Self::constant(0.8),
+            metallic: Self::constant(0.),
+            roughness: Self::constant(0.5),
+            anisotropy: Self::constant(0.),
+            specular: Self::constant(0.5),
+            specular_tint: Self::constant(0.),
+            sheen: Self::constant(0.),
+            sheen_tint: Self::constant(0.5),
+            clearcoat: Self::constant(0.),
+            clearcoat_roughness: Self::constant(0.1),
+            transmission: Self::constant(0.),
+            refraction_index: 1.5,
+            emission: Self::constant(0.),
+            emission_strength: Self::constant(1.),
+            two_sided: false,
+            alpha: None
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let wo = -ray_in.direction().normalize();
+        let pdf = BxDFPDF::new(self.bxdf(hit_record), shading_frame(hit_record), wo);
+        Some(ScatterRecord { attenuation: Color::one(), scattered: Scattered::Pdf(Box::new(pdf)) })
+    }
+
+    fn scattering_pdf(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> f64 {
+        let (wo, wi) = local_directions(ray_in, hit_record, scattered);
+        self.bxdf(hit_record).pdf(&wo, &wi)
+    }
+
+    fn scattering(&self, ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> Color {
+        let (wo, wi) = local_directions(ray_in, hit_record, scattered);
+        self.bxdf(hit_record).f(&wo, &wi) * wi.z.abs()
+    }
+
+    fn emitted(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        textured_emission(&self.emission, &self.emission_strength, self.two_sided, hit_record)
+    }
+
+    fn alpha(&self, hit_record: &HitRecord) -> f64 {
+        texture_alpha(&self.alpha, hit_record)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        hit_record.texture_value(self.base_color.as_ref())
+    }
+}
+
+/// 以法线为 z 轴、切线为 x 轴的着色坐标系
+fn shading_frame(hit_record: &HitRecord) -> ONB {
+    ONB::from_tangent(&hit_record.normal, &hit_record.tangent)
+}
+
+/// 着色坐标系下指向观察者的 `wo` 和指向散射方向的 `wi`
+fn local_directions(ray_in: &Ray, hit_record: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+    let frame = shading_frame(hit_record);
+    (frame.local(-ray_in.direction().normalize()), frame.local(scattered.direction().normalize()))
+}
+
+/// 漫反射光源，默认只从正面向各个方向均匀发光，不反射光线
+pub struct DiffuseLight {
+    texture: Arc<dyn Texture>,
+    strength: Arc<dyn Texture>,
+    two_sided: bool,
+    alpha: Option<Arc<dyn Texture>>
+}
+
+impl DiffuseLight {
+    pub fn new(texture: Arc<dyn Texture>) -> Self {
+        Self { texture, strength: Arc::new(SolidColor::new(Color::one())), two_sided: false, alpha: None }
+    }
+
+    pub fn from_color(emit: Color) -> Self {
+        Self::new(Arc::new(SolidColor::new(emit)))
+    }
+
+    /// 把发光颜色乘以纹理 `strength` 的亮度
+    pub fn with_strength(mut self, strength: Arc<dyn Texture>) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// 正反两面都发光
+    pub fn with_two_sided(mut self, two_sided: bool) -> Self {
+        self.two_sided = two_sided;
+        self
+    }
+
+    /// 用纹理 `alpha` 的 alpha 通道镂空光源
+    pub fn with_alpha(mut self, alpha: Arc<dyn Texture>) -> Self {
+        self.alpha = Some(alpha);
+        self
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray, _hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        None
+    }
+
+    fn emitted(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Color {
+        textured_emission(&self.texture, &self.strength, self.two_sided, hit_record)
+    }
+
+    fn alpha(&self, hit_record: &HitRecord) -> f64 {
+        texture_alpha(&self.alpha, hit_record)
+    }
+
+    /// 发光颜色，不含亮度倍数
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        hit_record.texture_value(self.texture.as_ref())
+    }
+}
+
+/// 发光颜色乘以亮度纹理的亮度，单面发光时背面不发光
+fn textured_emission(
+        emission: &Arc<dyn Texture>,
+        strength: &Arc<dyn Texture>,
+        two_sided: bool,
+        hit_record: &HitRecord
+    ) -> Color {
+    if !hit_record.front_face && !two_sided {
+        return Color::zero();
+    }
+    hit_record.texture_value(emission.as_ref()) * luminance(hit_record.texture_value(strength.as_ref()))
+}
+
+/// 纹理 `alpha` 在交点处的不透明度，没有纹理时完全不透明
+fn texture_alpha(alpha: &Option<Arc<dyn Texture>>, hit_record: &HitRecord) -> f64 {
+    alpha.as_ref().map_or(1., |alpha| alpha.alpha(hit_record.u, hit_record.v, &hit_record.point))
+}
+
+/// 各向同性的相位函数，向所有方向均匀散射，用于参与介质
+pub struct Isotropic {
+    texture: Arc<dyn Texture>
+}
+
+impl Isotropic {
+    pub fn new(texture: Arc<dyn Texture>) -> Self {
+        Self { texture }
+    }
+
+    pub fn from_color(albedo: Color) -> Self {
+        Self { texture: Arc::new(SolidColor::new(albedo)) }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<ScatterRecord> {
+        let attenuation = hit_record.texture_value(self.texture.as_ref());
+
+        Some(ScatterRecord { attenuation, scattered: Scattered::Pdf(Box::new(SpherePDF)) })
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _scattered: &Ray) -> f64 {
+        1. / (4. * PI)
+    }
+
+    fn albedo(&self, hit_record: &HitRecord) -> Color {
+        hit_record.texture_value(self.texture.as_ref())
     }
 }
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    bvh::{BVHOptions, LinearBVH},
+    hittable::{object_key, HitRecord, Hittable},
+    material::Material,
+    ray::Ray
+};
+use utils::{interval::Interval, rng::Rng};
+use vector3::{Point3, Vec3};
+
+/// 网格的顶点属性数组，被网格中所有三角形共享
+#[derive(Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>
+}
+
+/// 三角形的一个顶点：位置索引，以及可选的法线索引和纹理坐标索引
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexIndex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>
+}
+
+impl VertexIndex {
+    pub fn new(position: usize, normal: Option<usize>, uv: Option<usize>) -> Self {
+        Self { position, normal, uv }
+    }
+}
+
+/// 网格中的三角形，通过索引引用共享的顶点数据
+pub struct Triangle {
+    data: Arc<MeshData>,
+    vertices: [VertexIndex; 3],
+    material: Arc<dyn Material>,
+    bbox: AABB
+}
+
+impl Triangle {
+    pub fn new(data: Arc<MeshData>, vertices: [VertexIndex; 3], material: Arc<dyn Material>) -> Self {
+        let [p0, p1, p2] = vertices.map(|vertex| data.positions[vertex.position]);
+        let bbox = AABB::combine(&AABB::from_points(p0, p1), &AABB::from_points(p0, p2));
+
+        Self { data, vertices, material, bbox }
+    }
+
+    /// 若三个顶点都有法线，按重心坐标插值得到着色法线
+    fn shading_normal(&self, b0: f64, b1: f64, b2: f64) -> Option<Vec3> {
+        let [n0, n1, n2] = self.vertices.map(|vertex| vertex.normal);
+        let normals = &self.data.normals;
+        let normal = b0 * normals[n0?] + b1 * normals[n1?] + b2 * normals[n2?];
+
+        (normal.squared() > 1e-16).then(|| normal.normalize())
+    }
+
+    /// 沿纹理坐标 u 增大的方向 dP/du，没有纹理坐标时沿 `edge1`
+    fn tangent(&self, edge1: Vec3, edge2: Vec3) -> Vec3 {
+        let [t0, t1, t2] = self.vertices.map(|vertex| vertex.uv);
+        let uvs = &self.data.uvs;
+        let (Some(t0), Some(t1), Some(t2)) = (t0, t1, t2) else {
+            return edge1;
+        };
+
+        // Solve edge = du * dP/du + dv * dP/dv for dP/du.
+        let (du1, dv1) = (uvs[t1].0 - uvs[t0].0, uvs[t1].1 - uvs[t0].1);
+        let (du2, dv2) = (uvs[t2].0 - uvs[t0].0, uvs[t2].1 - uvs[t0].1);
+        let det = du1 * dv2 - dv1 * du2;
+        if det.abs() < 1e-12 {
+            return edge1;
+        }
+
+        (dv2 * edge1 - dv1 * edge2) / det
+    }
+
+    /// 若三个顶点都有纹理坐标，按重心坐标插值
+    fn uv(&self, b0: f64, b1: f64, b2: f64) -> Option<(f64, f64)> {
+        let [t0, t1, t2] = self.vertices.map(|vertex| vertex.uv);
+        let uvs = &self.data.uvs;
+        let (uv0, uv1, uv2) = (uvs[t0?], uvs[t1?], uvs[t2?]);
+
+        Some((
+            b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+            b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1
+        ))
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord> {
+        // Möller–Trumbore ray-triangle intersection.
+        let [p0, p1, p2] = self.vertices.map(|vertex| self.data.positions[vertex.position]);
+        let edge1 = p1 - p0;
+        let edge2 = p2 - p0;
+
+        let pvec = ray.direction().cross(&edge2);
+        let det = edge1.dot(&pvec);
+        // No hit if the ray is parallel to the triangle.
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = det.recip();
+
+        let tvec = *ray.origin() - p0;
+        let b1 = tvec.dot(&pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(&edge1);
+        let b2 = ray.direction().dot(&qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+
+        let t = edge2.dot(&qvec) * inv_det;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let b0 = 1. - b1 - b2;
+        let (u, v) = self.uv(b0, b1, b2).unwrap_or((b1, b2));
+        let mut hit_record = HitRecord {
+            point: ray.at(t),
+            material: self.material.clone(),
+            t,
+            u,
+            v,
+            tangent: self.tangent(edge1, edge2),
+            object: object_key(self),
+            ..Default::default()
+        };
+        // The geometric normal decides which side was hit, the interpolated normal is used for
+        // shading and is flipped to the same side.
+        hit_record.set_face_normal(ray, edge1.cross(&edge2).normalize());
+        if let Some(shading_normal) = self.shading_normal(b0, b1, b2) {
+            hit_record.normal = if hit_record.front_face { shading_normal } else { -shading_normal };
+        }
+
+        hit_record.is_opaque().then_some(hit_record)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+}
+
+/// 三角形网格，内部用 BVH 组织所有三角形
+pub struct Mesh {
+    name: String,
+    triangles: Vec<Arc<dyn Hittable>>,
+    bvh: LinearBVH
+}
+
+impl Mesh {
+    /// 用共享的顶点数据和三角形列表创建网格，`faces` 不能为空
+    pub fn new(
+            name: impl Into<String>,
+            data: Arc<MeshData>,
+            faces: &[[VertexIndex; 3]],
+            material: Arc<dyn Material>
+        ) -> Self {
+        assert!(!faces.is_empty(), "mesh has no faces");
+
+        let triangles: Vec<Arc<dyn Hittable>> = faces.iter()
+            .map(|face| Arc::new(Triangle::new(data.clone(), *face, material.clone())) as Arc<dyn Hittable>)
+            .collect();
+        let mut objects = triangles.clone();
+        let bvh = LinearBVH::new(&mut objects, &BVHOptions::default());
+
+        Self { name: name.into(), triangles, bvh }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 网格中的所有三角形，可以直接加入更大的 BVH 中
+    pub fn triangles(&self) -> &[Arc<dyn Hittable>] {
+        &self.triangles
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord> {
+        // The whole mesh is one object, rather than each of its triangles.
+        let mut hit_record = self.bvh.hit(ray, ray_t, rng)?;
+        hit_record.object = object_key(self);
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        self.bvh.bounding_box()
+    }
+}
@@ -0,0 +1,218 @@
+//! Wavefront OBJ 模型加载
+//!
+//! 支持 `v`、`vt`、`vn`、`f`（包括负数索引和多边形）以及 `g`/`o` 分组，其他语句会被忽略。
+//! 多边形按扇形拆分为三角形，每个分组生成一个 [`Mesh`]。
+
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc
+};
+
+use crate::{
+    material::Material,
+    mesh::{Mesh, MeshData, VertexIndex}
+};
+use vector3::Vec3;
+
+/// 加载 OBJ 文件时的错误
+#[derive(Debug)]
+pub enum ObjError {
+    /// 读取文件失败
+    Io(PathBuf, io::Error),
+    /// 第 `line` 行（从 1 开始）格式错误
+    Parse { line: usize, message: String }
+}
+
+impl Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "failed to read {}: {}", path.display(), err),
+            Self::Parse { line, message } => write!(f, "line {}: {}", line, message)
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            Self::Parse { .. } => None
+        }
+    }
+}
+
+/// 从文件加载 OBJ 模型，所有分组使用同一种材质
+pub fn load(path: impl AsRef<Path>, material: Arc<dyn Material>) -> Result<Vec<Mesh>, ObjError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|err| ObjError::Io(path.to_path_buf(), err))?;
+
+    parse(&text, material)
+}
+
+/// 解析 OBJ 文本，返回每个非空分组对应的网格
+pub fn parse(text: &str, material: Arc<dyn Material>) -> Result<Vec<Mesh>, ObjError> {
+    let mut data = MeshData::default();
+    // Faces of each group, in the order the groups appear.
+    let mut groups: Vec<(String, Vec<[VertexIndex; 3]>)> = vec![(String::from("default"), Vec::new())];
+
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ObjError::Parse { line: index + 1, message };
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(&mut tokens).map_err(error)?;
+                data.positions.push(Vec3::new(x, y, z));
+            },
+            "vn" => {
+                let [x, y, z] = parse_floats(&mut tokens).map_err(error)?;
+                data.normals.push(Vec3::new(x, y, z));
+            },
+            "vt" => {
+                let [u, v] = parse_floats(&mut tokens).map_err(error)?;
+                data.uvs.push((u, v));
+            },
+            "f" => {
+                let vertices = tokens
+                    .map(|token| parse_vertex(token, &data))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(error)?;
+                if vertices.len() < 3 {
+                    return Err(error(format!("face has {} vertices, expected at least 3", vertices.len())));
+                }
+
+                // Triangulate the polygon as a fan around its first vertex.
+                let faces = &mut groups.last_mut().unwrap().1;
+                for pair in vertices[1..].windows(2) {
+                    faces.push([vertices[0], pair[0], pair[1]]);
+                }
+            },
+            "g" | "o" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                groups.push((name, Vec::new()));
+            },
+            _ => {}
+        }
+    }
+
+    let data = Arc::new(data);
+    Ok(groups.into_iter()
+        .filter(|(_, faces)| !faces.is_empty())
+        .map(|(name, faces)| Mesh::new(name, data.clone(), &faces, material.clone()))
+        .collect())
+}
+
+/// 读取 N 个浮点数，多余的分量（如 `v` 的 w 分量）会被忽略
+fn parse_floats<'a, const N: usize>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<[f64; N], String> {
+    let mut values = [0.; N];
+    for value in values.iter_mut() {
+        let token = tokens.next().ok_or_else(|| format!("expected {} numbers", N))?;
+        *value = token.parse().map_err(|_| format!("invalid number `{}`", token))?;
+    }
+
+    Ok(values)
+}
+
+/// 解析面的一个顶点：`v`、`v/vt`、`v//vn` 或 `v/vt/vn`
+fn parse_vertex(token: &str, data: &MeshData) -> Result<VertexIndex, String> {
+    let mut parts = token.split('/');
+    let position = resolve_index(parts.next(), data.positions.len(), token)?
+        .ok_or_else(|| format!("missing vertex index in `{}`", token))?;
+    let uv = resolve_index(parts.next(), data.uvs.len(), token)?;
+    let normal = resolve_index(parts.next(), data.normals.len(), token)?;
+
+    Ok(VertexIndex::new(position, normal, uv))
+}
+
+/// 将 OBJ 的索引（从 1 开始，负数表示倒数）转换为数组下标
+fn resolve_index(part: Option<&str>, len: usize, token: &str) -> Result<Option<usize>, String> {
+    let Some(part) = part.filter(|part| !part.is_empty()) else {
+        return Ok(None);
+    };
+    let index: isize = part.parse().map_err(|_| format!("invalid index in `{}`", token))?;
+    let resolved = match index {
+        1.. => index - 1,
+        ..=-1 => len as isize + index,
+        0 => return Err(format!("index 0 in `{}`", token))
+    };
+    if resolved < 0 || resolved as usize >= len {
+        return Err(format!("index out of range in `{}`", token));
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::{assert_f64_eq, interval::Interval, rng::Rng};
+    use vector3::Point3;
+
+    use crate::{hittable::Hittable, material::Lambertian, ray::Ray};
+    use super::*;
+
+    const SQUARE: &str = "
+        # unit square in the xy plane, split into two groups
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 1
+        g first
+        f 1/1/1 2/2/1 3/3/1 4/4/1
+        g second
+        f -4//-1 -2//-1 -1//-1
+    ";
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::from_color(Vec3::one()))
+    }
+
+    #[test]
+    fn test_parse_groups() {
+        let meshes = parse(SQUARE, material()).unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].name(), "first");
+        assert_eq!(meshes[0].triangles().len(), 2);
+        assert_eq!(meshes[1].name(), "second");
+        assert_eq!(meshes[1].triangles().len(), 1);
+    }
+
+    #[test]
+    fn test_mesh_hit() {
+        let meshes = parse(SQUARE, material()).unwrap();
+        let ray = Ray::new(Point3::new(0.25, 0.75, 2.), Vec3::from_z(-1.), 0.);
+        let record = meshes[0].hit(&ray, Interval::new(0.001, f64::INFINITY), &mut Rng::new(0)).unwrap();
+
+        assert_f64_eq!(record.t, 2.);
+        assert_f64_eq!(record.u, 0.25);
+        assert_f64_eq!(record.v, 0.75);
+        assert!(record.front_face);
+        assert_eq!(record.normal, Vec3::from_z(1.));
+
+        let ray = Ray::new(Point3::new(1.25, 0.5, 2.), Vec3::from_z(-1.), 0.);
+        assert!(meshes[0].hit(&ray, Interval::new(0.001, f64::INFINITY), &mut Rng::new(0)).is_none());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse("v 0 0 0\nv 1 0 0\nf 1 2", material()).err().unwrap();
+        assert!(matches!(err, ObjError::Parse { line: 3, .. }));
+
+        let err = parse("v 0 0 0\nf 1 2 3", material()).err().unwrap();
+        assert!(matches!(err, ObjError::Parse { line: 2, .. }));
+
+        let err = parse("v 0 zero 0", material()).err().unwrap();
+        assert!(matches!(err, ObjError::Parse { line: 1, .. }));
+    }
+}
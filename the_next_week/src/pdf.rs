@@ -0,0 +1,181 @@
+//! 方向的概率密度函数
+//!
+//! 积分器按某个概率密度生成散射方向，再用该方向的密度值对贡献加权。把材质的密度和指向光源的密度混合，
+//! 就能在不引入偏差的情况下更多地朝光源采样。
+
+use crate::{hittable::Hittable, sampler::Sampler};
+use utils::rtweekend::PI;
+use vector3::{extension::{sample_cosine_direction, sample_unit_vector}, onb::ONB, Point3, Vec3};
+
+/// 单位球面上的方向分布，密度以立体角为测度
+pub trait PDF {
+    /// 方向 `direction` 的概率密度
+    fn value(&self, direction: &Vec3) -> f64;
+
+    /// 按该分布生成一个方向
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3;
+}
+
+/// 整个球面上的均匀分布
+pub struct SpherePDF;
+
+impl PDF for SpherePDF {
+    fn value(&self, _direction: &Vec3) -> f64 {
+        1. / (4. * PI)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        sample_unit_vector(sampler.get_2d())
+    }
+}
+
+/// 法线所在半球上按余弦加权的分布，与 Lambertian 反射一致
+pub struct CosinePDF {
+    uvw: ONB
+}
+
+impl CosinePDF {
+    pub fn new(w: &Vec3) -> Self {
+        Self { uvw: ONB::new(w) }
+    }
+}
+
+impl PDF for CosinePDF {
+    fn value(&self, direction: &Vec3) -> f64 {
+        let cosine_theta = direction.normalize().dot(&self.uvw.w());
+        (cosine_theta / PI).max(0.)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.uvw.transform(sample_cosine_direction(sampler.get_2d()))
+    }
+}
+
+/// 从 `origin` 朝物体上的点采样的分布，通常用于光源
+pub struct HittablePDF<'a> {
+    objects: &'a dyn Hittable,
+    origin: Point3
+}
+
+impl<'a> HittablePDF<'a> {
+    pub fn new(objects: &'a dyn Hittable, origin: Point3) -> Self {
+        Self { objects, origin }
+    }
+}
+
+impl PDF for HittablePDF<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        self.objects.pdf_value(&self.origin, direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        self.objects.random(&self.origin, sampler)
+    }
+}
+
+/// 两个分布各占一半的混合
+///
+/// 按混合分布采样并除以混合密度，相当于使用平衡启发式（balance heuristic）的单样本多重重要性采样。
+pub struct MixturePDF<'a> {
+    p: [&'a dyn PDF; 2]
+}
+
+impl<'a> MixturePDF<'a> {
+    pub fn new(p0: &'a dyn PDF, p1: &'a dyn PDF) -> Self {
+        Self { p: [p0, p1] }
+    }
+}
+
+impl PDF for MixturePDF<'_> {
+    fn value(&self, direction: &Vec3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        if sampler.get_1d() < 0.5 {
+            self.p[0].generate(sampler)
+        } else {
+            self.p[1].generate(sampler)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        hittable_list::HittableList,
+        material::DiffuseLight,
+        quad::Quad,
+        sampler::SamplerKind,
+        sphere::Sphere,
+        transform::Transform
+    };
+    use super::*;
+
+    /// 用均匀分布的方向估计 `pdf` 在整个球面上的积分
+    fn integral(pdf: &dyn PDF) -> f64 {
+        let mut sampler = SamplerKind::Sobol.create(1, 1 << 16);
+        sampler.start_pixel(0, 0);
+        let count = 1 << 16;
+        (0..count).map(|index| {
+            sampler.start_sample(index);
+            pdf.value(&SpherePDF.generate(sampler.as_mut())) * 4. * PI
+        }).sum::<f64>() / count as f64
+    }
+
+    /// `pdf` 生成的方向的密度都为正
+    fn generates_supported(pdf: &dyn PDF) -> bool {
+        let mut sampler = SamplerKind::Independent.create(2, 1);
+        sampler.start_pixel(0, 0);
+        (0..1000).all(|_| pdf.value(&pdf.generate(sampler.as_mut())) > 0.)
+    }
+
+    #[test]
+    fn test_cosine_pdf() {
+        let pdf = CosinePDF::new(&Vec3::new(1., 2., 3.));
+        assert!((integral(&pdf) - 1.).abs() < 0.01);
+        assert!(generates_supported(&pdf));
+    }
+
+    #[test]
+    fn test_hittable_pdf() {
+        let light = Arc::new(DiffuseLight::from_color(Vec3::one()));
+        let sphere = Arc::new(Sphere::new_stationary_sphere(Point3::new(0., 0., -3.), 1., light.clone()));
+        let quad = Arc::new(Quad::new(Point3::new(-1., 2., -1.), Vec3::from_x(2.), Vec3::from_z(1.), light.clone()));
+        let triangle = Arc::new(Quad::triangle(Point3::new(2., -1., 0.), Vec3::from_y(2.), Vec3::from_z(2.), light.clone()));
+        let disk = Arc::new(Quad::disk(Point3::from_y(-2.), 1., Vec3::from_y(1.), light.clone()));
+        // An ellipsoid that has been squashed, rotated and moved.
+        let ellipsoid = Arc::new(Transform::translate(
+            Arc::new(Transform::rotate(
+                Arc::new(Transform::scale(sphere.clone(), Vec3::new(0.5, 1., 2.))),
+                Vec3::new(1., 1., 0.),
+                30.
+            )),
+            Vec3::new(0., 0., 4.)
+        ));
+        let mut list = HittableList::default();
+        list.add(quad.clone());
+        list.add(ellipsoid.clone());
+
+        let objects: [&dyn Hittable; 6] = [&*sphere, &*quad, &*triangle, &*disk, &*ellipsoid, &list];
+        for object in objects {
+            let pdf = HittablePDF::new(object, Point3::zero());
+            assert!((integral(&pdf) - 1.).abs() < 0.02, "{}", integral(&pdf));
+            assert!(generates_supported(&pdf));
+        }
+
+        // From inside a sphere every direction hits it.
+        let pdf = HittablePDF::new(&*sphere, Point3::new(0., 0.5, -3.));
+        assert!((integral(&pdf) - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mixture_pdf() {
+        let cosine = CosinePDF::new(&Vec3::from_y(1.));
+        let pdf = MixturePDF::new(&cosine, &SpherePDF);
+        assert!((integral(&pdf) - 1.).abs() < 0.01);
+        assert!((pdf.value(&Vec3::from_y(1.)) - 0.5 / PI - 0.125 / PI).abs() < 1e-12);
+    }
+}
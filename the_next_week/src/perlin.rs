@@ -1,4 +1,4 @@
-use utils::rtweekend::{random, random_range};
+use utils::rng::Rng;
 use vector3::Point3;
 
 const POINT_COUNT: usize = 256;
@@ -14,15 +14,15 @@ pub struct Perlin {
 
 impl Perlin {
 
-    pub fn new() -> Self {
+    pub fn new(rng: &mut Rng) -> Self {
         let mut perlin = Self::default();
 
         for i in 0..POINT_COUNT {
-            perlin.randfloat[i] = random();
+            perlin.randfloat[i] = rng.random();
         }
-        Self::perlin_generate_perm(&mut perlin.perm_x);
-        Self::perlin_generate_perm(&mut perlin.perm_y);
-        Self::perlin_generate_perm(&mut perlin.perm_z);
+        Self::perlin_generate_perm(&mut perlin.perm_x, rng);
+        Self::perlin_generate_perm(&mut perlin.perm_y, rng);
+        Self::perlin_generate_perm(&mut perlin.perm_z, rng);
 
         perlin
     }
@@ -36,10 +36,10 @@ impl Perlin {
         let i = p.x.floor() as usize;
         let j = p.y.floor() as usize;
         let k = p.z.floor() as usize;
-        for di in 0..2 {
-            for dj in 0..2 {
-                for dk in 0..2 {
-                    c[di][dj][dk] = self.randfloat[
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, value) in row.iter_mut().enumerate() {
+                    *value = self.randfloat[
                         self.perm_x[(i+di) & 255] ^
                         self.perm_y[(j+dj) & 255] ^
                         self.perm_z[(k+dk) & 255]
@@ -51,16 +51,16 @@ impl Perlin {
         Self::trilinear_interp(&c, u, v, w)
     }
 
-    fn perlin_generate_perm(perm: &mut PermArr) {
-        for i in 0..POINT_COUNT {
-            perm[i] = i;
+    fn perlin_generate_perm(perm: &mut PermArr, rng: &mut Rng) {
+        for (i, p) in perm.iter_mut().enumerate() {
+            *p = i;
         }
-        Self::permute(perm, POINT_COUNT);
+        Self::permute(perm, POINT_COUNT, rng);
     }
 
-    fn permute(perm: &mut PermArr, n: usize) {
+    fn permute(perm: &mut PermArr, n: usize, rng: &mut Rng) {
         for i in (1..n).rev() {
-            let target = random_range::<usize>(0, i);
+            let target = rng.random_range::<usize>(0, i);
 
             perm.swap(i, target);
         }
@@ -69,13 +69,13 @@ impl Perlin {
     fn trilinear_interp(c: &TriArr, u: f64, v: f64, w: f64) -> f64 {
         let mut accum = 0.;
 
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, value) in row.iter().enumerate() {
                     accum += (i as f64 * u + (1-i) as f64 * (1.-u))
                            * (j as f64 * v + (1-j) as f64 * (1.-v))
                            * (k as f64 * w + (1-k) as f64 * (1.-w))
-                           * c[i][j][k];
+                           * value;
                 }
             }
         }
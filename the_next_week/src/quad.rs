@@ -0,0 +1,331 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{object_key, HitRecord, Hittable},
+    hittable_list::HittableList,
+    material::Material,
+    ray::Ray,
+    sampler::Sampler
+};
+use utils::{interval::Interval, rng::Rng, rtweekend::PI};
+use vector3::{extension::sample_unit_disk, Point3, Vec3};
+
+/// 平面图元在其所在平面内的形状
+#[derive(Clone, Copy)]
+enum Shape {
+    /// 平行四边形，`q`、`q+u`、`q+v`、`q+u+v` 为四个顶点
+    Parallelogram,
+    /// 三角形，`q`、`q+u`、`q+v` 为三个顶点
+    Triangle,
+    /// 椭圆，`q` 为圆心，`u`、`v` 为两个半轴
+    Ellipse
+}
+
+/// 平面图元：平行四边形（四边形）、三角形、圆盘和椭圆
+///
+/// 所有形状共用同一套射线与平面求交，只在判断交点是否位于形状内部时有所区别。
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    shape: Shape,
+    material: Arc<dyn Material>,
+    bbox: AABB,
+    normal: Vec3,
+    d: f64,
+    area: f64
+}
+
+impl Quad {
+    /// 创建以 `q` 为顶点、`u` 和 `v` 为两条边的四边形
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        // Compute the bounding box of all four vertices.
+        let bbox_diagonal1 = AABB::from_points(q, q + u + v);
+        let bbox_diagonal2 = AABB::from_points(q + u, q + v);
+        let bbox = AABB::combine(&bbox_diagonal1, &bbox_diagonal2);
+
+        Self::with_shape(q, u, v, Shape::Parallelogram, material, bbox)
+    }
+
+    /// 创建以 `q`、`q+u`、`q+v` 为顶点的三角形
+    pub fn triangle(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let bbox = AABB::combine(&AABB::from_points(q, q + u), &AABB::from_points(q, q + v));
+
+        Self::with_shape(q, u, v, Shape::Triangle, material, bbox)
+    }
+
+    /// 创建以 `center` 为中心、`u` 和 `v` 为两个半轴的椭圆
+    pub fn ellipse(center: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let bbox = AABB::combine(
+            &AABB::from_points(center - u - v, center + u + v),
+            &AABB::from_points(center - u + v, center + u - v)
+        );
+
+        Self::with_shape(center, u, v, Shape::Ellipse, material, bbox)
+    }
+
+    /// 创建以 `center` 为中心、`radius` 为半径、法线朝向 `normal` 的圆盘
+    pub fn disk(center: Point3, radius: f64, normal: Vec3, material: Arc<dyn Material>) -> Self {
+        // Build two perpendicular semi-axes such that u x v points along the normal.
+        let n = normal.normalize();
+        let a = if n.x.abs() > 0.9 { Vec3::from_y(1.) } else { Vec3::from_x(1.) };
+        let u = a.cross(&n).normalize();
+        let v = n.cross(&u);
+
+        Self::ellipse(center, radius * u, radius * v, material)
+    }
+
+    fn with_shape(q: Point3, u: Vec3, v: Vec3, shape: Shape, material: Arc<dyn Material>, bbox: AABB) -> Self {
+        let n = u.cross(&v);
+        let normal = n.normalize();
+        let area = match shape {
+            Shape::Parallelogram => n.norm(),
+            Shape::Triangle => 0.5 * n.norm(),
+            Shape::Ellipse => PI * n.norm()
+        };
+
+        Self {
+            q,
+            u,
+            v,
+            w: n / n.dot_self(),
+            shape,
+            material,
+            bbox,
+            normal,
+            d: normal.dot(&q),
+            area
+        }
+    }
+
+    /// 给定交点在平面坐标系中的坐标 (alpha, beta)，若交点位于形状内部则返回其纹理坐标
+    fn is_interior(&self, alpha: f64, beta: f64) -> Option<(f64, f64)> {
+        let unit_interval = Interval::new(0., 1.);
+        match self.shape {
+            Shape::Parallelogram => (unit_interval.contains(alpha) && unit_interval.contains(beta))
+                .then_some((alpha, beta)),
+            Shape::Triangle => (alpha > 0. && beta > 0. && alpha + beta < 1.)
+                .then_some((alpha, beta)),
+            Shape::Ellipse => (alpha * alpha + beta * beta < 1.)
+                .then_some((alpha * 0.5 + 0.5, beta * 0.5 + 0.5))
+        }
+    }
+
+    /// 形状上均匀分布的一点
+    fn sample_point(&self, (a, b): (f64, f64)) -> Point3 {
+        match self.shape {
+            Shape::Parallelogram => self.q + a * self.u + b * self.v,
+            Shape::Triangle => {
+                // Fold the upper half of the parallelogram back onto the triangle.
+                let (a, b) = if a + b > 1. { (1. - a, 1. - b) } else { (a, b) };
+                self.q + a * self.u + b * self.v
+            },
+            Shape::Ellipse => {
+                let p = sample_unit_disk((a, b));
+                self.q + p.x * self.u + p.y * self.v
+            }
+        }
+    }
+
+    /// 光线与平面形状的交点，不考虑材质的镂空
+    fn intersect(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let denom = self.normal.dot(ray.direction());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        // Return false if the hit point parameter t is outside the ray interval.
+        let t = (self.d - self.normal.dot(ray.origin())) / denom;
+        if !ray_t.contains(t) {
+            return None;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates.
+        let intersection = ray.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = self.w.dot(&planar_hitpt_vector.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar_hitpt_vector));
+        let (u, v) = self.is_interior(alpha, beta)?;
+
+        // Ray hits the 2D shape; set the rest of the hit record and return true.
+        let mut hit_record = HitRecord {
+            point: intersection,
+            material: self.material.clone(),
+            t,
+            u,
+            v,
+            // The ellipse maps [-1, 1] onto [0, 1], which doubles dP/du.
+            tangent: if matches!(self.shape, Shape::Ellipse) { 2. * self.u } else { self.u },
+            object: object_key(self),
+            ..Default::default()
+        };
+        hit_record.set_face_normal(ray, self.normal);
+
+        Some(hit_record)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord> {
+        self.intersect(ray, ray_t).filter(HitRecord::is_opaque)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let Some(hit_record) = self.intersect(&Ray::new(*origin, *direction, 0.), Interval::new(0.001, f64::INFINITY)) else {
+            return 0.;
+        };
+
+        // Convert the uniform density over the area into a density over solid angle.
+        let distance_squared = hit_record.t * hit_record.t * direction.squared();
+        let cosine = (direction.dot(&self.normal) / direction.norm()).abs();
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        self.sample_point(sampler.get_2d()) - *origin
+    }
+}
+
+/// 创建以 `a`、`b` 为对角顶点的长方体，由六个四边形组成
+pub fn make_box(a: Point3, b: Point3, material: Arc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::default();
+
+    // Construct the two opposite vertices with the minimum and maximum coordinates.
+    let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+    let dx = Vec3::from_x(max.x - min.x);
+    let dy = Vec3::from_y(max.y - min.y);
+    let dz = Vec3::from_z(max.z - min.z);
+
+    // front
+    sides.add(Arc::new(Quad::new(Point3::new(min.x, min.y, max.z), dx, dy, material.clone())));
+    // right
+    sides.add(Arc::new(Quad::new(Point3::new(max.x, min.y, max.z), -dz, dy, material.clone())));
+    // back
+    sides.add(Arc::new(Quad::new(Point3::new(max.x, min.y, min.z), -dx, dy, material.clone())));
+    // left
+    sides.add(Arc::new(Quad::new(Point3::new(min.x, min.y, min.z), dz, dy, material.clone())));
+    // top
+    sides.add(Arc::new(Quad::new(Point3::new(min.x, max.y, max.z), dx, -dz, material.clone())));
+    // bottom
+    sides.add(Arc::new(Quad::new(Point3::new(min.x, min.y, min.z), dx, dz, material)));
+
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::assert_f64_eq;
+
+    use crate::{color::Color, material::Lambertian, sampler::IndependentSampler, texture::Texture};
+    use vector3::extension::sample_unit_vector;
+    use super::*;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::from_color(Vec3::one()))
+    }
+
+    fn hit_at(object: &Quad, x: f64, y: f64) -> Option<HitRecord> {
+        let ray = Ray::new(Point3::new(x, y, 5.), Vec3::from_z(-1.), 0.);
+        object.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut Rng::new(0))
+    }
+
+    #[test]
+    fn test_bounding_box_padding() {
+        let quad = Quad::new(Point3::zero(), Vec3::from_x(1.), Vec3::from_y(1.), material());
+        let bbox = quad.bounding_box();
+        assert!(bbox.z.size() > 0.);
+        assert!(bbox.z.contains(0.));
+    }
+
+    #[test]
+    fn test_quad_hit() {
+        let quad = Quad::new(Point3::zero(), Vec3::from_x(2.), Vec3::from_y(4.), material());
+        let record = hit_at(&quad, 0.5, 1.).unwrap();
+        assert_f64_eq!(record.t, 5.);
+        assert_f64_eq!(record.u, 0.25);
+        assert_f64_eq!(record.v, 0.25);
+        assert!(record.front_face);
+        assert_eq!(record.normal, Vec3::from_z(1.));
+
+        assert!(hit_at(&quad, 2.5, 1.).is_none());
+        assert!(hit_at(&quad, 0.5, -0.1).is_none());
+    }
+
+    #[test]
+    fn test_planar_shapes() {
+        let triangle = Quad::triangle(Point3::zero(), Vec3::from_x(1.), Vec3::from_y(1.), material());
+        assert!(hit_at(&triangle, 0.2, 0.2).is_some());
+        assert!(hit_at(&triangle, 0.6, 0.6).is_none());
+
+        let disk = Quad::disk(Point3::zero(), 1., Vec3::from_z(1.), material());
+        let record = hit_at(&disk, 0., 0.).unwrap();
+        assert_f64_eq!(record.u, 0.5);
+        assert_f64_eq!(record.v, 0.5);
+        assert!(record.front_face);
+        assert!(hit_at(&disk, 0.75, 0.75).is_none());
+
+        let ellipse = Quad::ellipse(Point3::zero(), Vec3::from_x(2.), Vec3::from_y(1.), material());
+        assert!(hit_at(&ellipse, 1.9, 0.).is_some());
+        assert!(hit_at(&ellipse, 0., 1.1).is_none());
+    }
+
+    /// 左半边透明、右半边不透明的纹理
+    struct HalfTransparent;
+
+    impl Texture for HalfTransparent {
+        fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+            Color::one()
+        }
+
+        fn alpha(&self, u: f64, _v: f64, _p: &Point3) -> f64 {
+            if u < 0.5 { 0. } else { 1. }
+        }
+    }
+
+    #[test]
+    fn test_alpha_cutout() {
+        let material = Arc::new(Lambertian::from_color(Vec3::one()).with_alpha(Arc::new(HalfTransparent)));
+        let quad = Quad::new(Point3::zero(), Vec3::from_x(2.), Vec3::from_y(1.), material.clone());
+        assert!(hit_at(&quad, 0.5, 0.5).is_none());
+        assert!(hit_at(&quad, 1.5, 0.5).is_some());
+
+        let triangle = Quad::triangle(Point3::zero(), Vec3::from_x(2.), Vec3::from_y(1.), material);
+        assert!(hit_at(&triangle, 0.5, 0.2).is_none());
+        assert!(hit_at(&triangle, 1.2, 0.2).is_some());
+    }
+
+    #[test]
+    fn test_alpha_cutout_light_pdf() {
+        let material = Arc::new(Lambertian::from_color(Vec3::one()).with_alpha(Arc::new(HalfTransparent)));
+        let light = Quad::new(Point3::zero(), Vec3::from_x(2.), Vec3::from_y(1.), material);
+        let origin = Point3::new(1., 0.5, 1.);
+
+        // Every sampled direction has a density, including those towards the transparent half.
+        let mut sampler = IndependentSampler::new(0);
+        sampler.start_pixel(0, 0);
+        for _ in 0..1000 {
+            let direction = light.random(&origin, &mut sampler);
+            assert!(light.pdf_value(&origin, &direction) > 0.);
+        }
+
+        // The density integrates to one over the sphere of directions.
+        let mut rng = Rng::new(0);
+        let n = 200_000;
+        let integral = (0..n).map(|_| {
+            let direction = sample_unit_vector((rng.random(), rng.random()));
+            light.pdf_value(&origin, &direction) * 4. * PI
+        }).sum::<f64>() / n as f64;
+        assert!((integral - 1.).abs() < 0.02, "{}", integral);
+    }
+}
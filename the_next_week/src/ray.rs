@@ -5,12 +5,19 @@ use vector3::{Point3, Vec3};
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
-    time: f64
+    time: f64,
+    /// 光线锥的张角（弧度），锥的宽度随距离线性增大；0 表示不跟踪光线锥
+    spread: f64
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3, time: f64) -> Self {
-        Self { origin, direction, time }
+        Self { origin, direction, time, spread: 0. }
+    }
+
+    pub fn with_spread(mut self, spread: f64) -> Self {
+        self.spread = spread;
+        self
     }
 
     pub fn at(&self, t: f64) -> Point3 {
@@ -28,4 +35,9 @@ impl Ray {
     pub fn time(&self) -> f64 {
         self.time
     }
+
+    /// 光线锥在参数 `t` 处的宽度
+    pub fn cone_width(&self, t: f64) -> f64 {
+        self.spread * t * self.direction.norm()
+    }
 }
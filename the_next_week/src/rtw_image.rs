@@ -1,40 +1,54 @@
+use crate::color::ColorSpace;
+
 #[derive(Default)]
 pub struct RTWImage {
-    rgb_image: image::RgbImage,
     /// 图片宽度
     image_width: u32,
     /// 图片高度
     image_height: u32,
-    /// 图片像素数据
-    bdata: Vec<[u8; 3]>
+    /// mip 金字塔，第 0 层为原图，之后每层的宽高减半，保存解码后的线性值
+    mip_levels: Vec<MipLevel>
+}
+
+/// mip 金字塔中的一层
+struct MipLevel {
+    width: u32,
+    height: u32,
+    texels: Vec<[f64; 4]>
 }
 
 impl RTWImage {
-    pub fn new(image_filename: &str) -> RTWImage {
-        Self::default().load(image_filename)
+    /// 加载图片，色彩空间取决于图片格式，见 [`ColorSpace::of_image`]
+    pub fn new(image_filename: &str) -> Result<RTWImage, image::ImageError> {
+        Self::default().load(image_filename, None)
     }
 
-    pub fn load(mut self, image_filename: &str) -> RTWImage {
-        self.rgb_image = image::open(image_filename).unwrap().to_rgb8();
-
-        (self.image_width, self.image_height) = self.rgb_image.dimensions();
-        self.rgb_image.pixels().for_each(|pixel| {
-            let rgb = image::Rgb([pixel.0[0], pixel.0[1], pixel.0[2]]);
-            self.bdata.push(rgb.0);
-        });
-
-        self
+    /// 加载图片，按 `color_space` 解码
+    pub fn with_color_space(image_filename: &str, color_space: ColorSpace) -> Result<RTWImage, image::ImageError> {
+        Self::default().load(image_filename, Some(color_space))
     }
 
-    pub fn pixel_data(&self, x: u32, y: u32) -> [u8; 3] {
-        let rgb: [u8; 3] = [255, 0, 255];
-        if self.bdata.is_empty() {
-            return rgb;
-        }
+    /// 加载图片，文件不存在或无法解码时返回错误
+    pub fn load(mut self, image_filename: &str, color_space: Option<ColorSpace>) -> Result<RTWImage, image::ImageError> {
+        let image = image::open(image_filename)?;
+        let color_space = color_space.unwrap_or_else(|| ColorSpace::of_image(&image));
+        (self.image_width, self.image_height) = (image.width(), image.height());
+
+        // Filter in linear space, with the full precision of 16-bit and floating-point images.
+        let texels: Vec<[f64; 4]> = if image.as_rgb32f().is_some() || image.as_rgba32f().is_some() {
+            image.to_rgba32f().pixels().map(|pixel| pixel.0.map(|channel| channel as f64)).collect()
+        } else {
+            image.to_rgba16().pixels().map(|pixel| pixel.0.map(|channel| channel as f64 / u16::MAX as f64)).collect()
+        };
+        let texels = texels.into_iter().map(|texel| color_space.decode_rgba(texel)).collect();
+        self.build_mip_levels(texels);
 
-        let pixel = self.rgb_image.get_pixel(x, y);
+        Ok(self)
+    }
 
-        pixel.0
+    /// 没有加载任何像素数据
+    pub fn is_empty(&self) -> bool {
+        self.mip_levels.is_empty()
     }
 
     pub fn width(&self) -> u32 {
@@ -44,4 +58,48 @@ impl RTWImage {
     pub fn height(&self) -> u32 {
         self.image_height
     }
+
+    /// mip 金字塔的层数，最后一层只有一个像素
+    pub fn mip_level_count(&self) -> usize {
+        self.mip_levels.len()
+    }
+
+    /// 第 `level` 层的宽和高
+    pub fn mip_level_size(&self, level: usize) -> (u32, u32) {
+        let level = &self.mip_levels[level];
+        (level.width, level.height)
+    }
+
+    /// 第 `level` 层像素的 RGBA 值，坐标必须在该层范围内
+    pub fn texel(&self, level: usize, x: u32, y: u32) -> [f64; 4] {
+        let level = &self.mip_levels[level];
+        level.texels[(y * level.width + x) as usize]
+    }
+
+    /// 由第 0 层的线性值逐层用 2x2 的盒式滤波缩小，奇数边长的最后一行或一列并入前一个像素
+    fn build_mip_levels(&mut self, texels: Vec<[f64; 4]>) {
+        self.mip_levels = vec![MipLevel { width: self.image_width, height: self.image_height, texels }];
+
+        while let Some(level) = self.mip_levels.last().filter(|level| level.width > 1 || level.height > 1) {
+            let (width, height) = ((level.width / 2).max(1), (level.height / 2).max(1));
+            let mut texels = Vec::with_capacity((width * height) as usize);
+            for y in 0..height {
+                for x in 0..width {
+                    // The source footprint of this texel, widened to swallow a trailing odd row or column.
+                    let x_end = if x + 1 == width { level.width } else { (2 * x + 2).min(level.width) };
+                    let y_end = if y + 1 == height { level.height } else { (2 * y + 2).min(level.height) };
+                    let (mut sum, mut count) = ([0.; 4], 0.);
+                    for sy in 2 * y..y_end {
+                        for sx in 2 * x..x_end {
+                            let texel = level.texels[(sy * level.width + sx) as usize];
+                            sum.iter_mut().zip(texel).for_each(|(sum, channel)| *sum += channel);
+                            count += 1.;
+                        }
+                    }
+                    texels.push(sum.map(|channel| channel / count));
+                }
+            }
+            self.mip_levels.push(MipLevel { width, height, texels });
+        }
+    }
 }
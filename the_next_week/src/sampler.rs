@@ -0,0 +1,581 @@
+//! 像素采样器
+//!
+//! 采样器为每个像素样本依次提供 [0, 1) 中的样本值：像素内的位置（2D）、镜头上的位置（2D）、
+//! 时间（1D），之后每次弹射为材质散射提供若干维度。分层和低差异序列让同一像素的样本分布得更均匀，
+//! 在相同的 `samples_per_pixel` 下噪点更少。
+//!
+//! 所有采样器都只由种子、像素坐标和样本序号决定，因此渲染结果与线程数无关。
+//!
+//! 自适应采样让误差已经足够小的像素提前停止，把样本留给噪点多的区域，见 [`AdaptiveSampling`]。
+
+use utils::{rng::Rng, rtweekend::mix_seed};
+
+/// 采样器的公共接口
+pub trait Sampler {
+    /// 开始像素 `(i, j)`，重置随机数序列
+    fn start_pixel(&mut self, i: i32, j: i32);
+
+    /// 开始当前像素的第 `index` 个样本，从第一个维度开始取值
+    ///
+    /// 随机数序列也由 `index` 决定，因此一个像素可以先取一部分样本，之后再接着取。
+    fn start_sample(&mut self, index: u32);
+
+    /// 下一个维度的样本值
+    fn get_1d(&mut self) -> f64;
+
+    /// 下两个维度的样本值
+    fn get_2d(&mut self) -> (f64, f64);
+
+    /// 当前像素的随机数生成器，用于不需要分层的随机决策（如介质中的自由程）
+    fn rng(&mut self) -> &mut Rng;
+}
+
+/// 采样策略
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerKind {
+    /// 互相独立的均匀随机样本
+    #[default]
+    Independent,
+    /// 抖动网格：每个维度被划分为与样本数相当的层，每层一个样本
+    Stratified,
+    /// Owen 置乱的 Halton 序列
+    Halton,
+    /// Owen 置乱的 Sobol 序列，各维度对之间打乱样本顺序
+    Sobol
+}
+
+impl SamplerKind {
+    /// 创建采样器，`samples_per_pixel` 用于划分分层
+    pub fn create(self, seed: u64, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        match self {
+            Self::Independent => Box::new(IndependentSampler::new(seed)),
+            Self::Stratified => Box::new(StratifiedSampler::new(seed, samples_per_pixel)),
+            Self::Halton => Box::new(HaltonSampler::new(seed)),
+            Self::Sobol => Box::new(SobolSampler::new(seed))
+        }
+    }
+}
+
+/// 自适应采样
+///
+/// 每个像素先取 `min_samples` 个样本，之后每取 `min_samples` 个样本检查一次：像素颜色亮度的标准误差
+/// 不超过亮度的 `threshold` 倍时停止，否则继续，第一轮最多取每像素的平均样本数。提前收敛的像素省下的样本
+/// 随后按轮分给误差最大的像素，每轮 `min_samples` 个，单个像素最多 `max_samples` 个，总样本数不超过
+/// 像素数乘以平均样本数，`min_samples` 比平均样本数多时第一轮也只取平均样本数。亮度低于 [`AdaptiveSampling::MIN_LUMINANCE`] 时按它计算，避免暗处为了微小的
+/// 绝对误差用尽样本。前 `min_samples` 个样本都没有照到光的像素方差为零，会被当作已经收敛，因此间接光照为主的
+/// 场景需要较大的 `min_samples`。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub max_samples: u32,
+    /// 允许的相对误差
+    pub threshold: f64
+}
+
+impl AdaptiveSampling {
+    pub const MIN_LUMINANCE: f64 = 0.01;
+
+    pub fn new(min_samples: u32, max_samples: u32, threshold: f64) -> Self {
+        Self { min_samples, max_samples, threshold }
+    }
+
+    /// 取了 `samples` 个样本后是否应该检查误差
+    pub fn should_check(&self, samples: u32) -> bool {
+        samples.is_multiple_of(self.min_samples.max(1))
+    }
+
+    /// 平均亮度为 `mean`、其方差为 `variance` 的像素的相对误差
+    pub fn relative_error(&self, mean: f64, variance: f64) -> f64 {
+        variance.sqrt() / mean.max(Self::MIN_LUMINANCE)
+    }
+
+    /// 平均亮度为 `mean`、其方差为 `variance` 的像素是否已经收敛
+    pub fn converged(&self, mean: f64, variance: f64) -> bool {
+        self.relative_error(mean, variance) <= self.threshold
+    }
+}
+
+/// 独立均匀采样，每个样本值都直接取自随机数生成器
+pub struct IndependentSampler {
+    seed: u64,
+    /// 当前像素的坐标
+    pixel: (i32, i32),
+    rng: Rng
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, pixel: (0, 0), rng: Rng::new(seed) }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel(&mut self, i: i32, j: i32) {
+        self.pixel = (i, j);
+        self.rng = Rng::derive(self.seed, &[i as u64, j as u64]);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        let (i, j) = self.pixel;
+        self.rng = Rng::derive(self.seed, &[i as u64, j as u64, index as u64]);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.random()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.random(), self.rng.random())
+    }
+
+    fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+}
+
+/// 每个样本在各维度上的位置，供确定性的采样器使用
+struct SampleState {
+    seed: u64,
+    /// 当前像素的哈希值
+    pixel_hash: u64,
+    index: u32,
+    dimension: u64,
+    rng: Rng
+}
+
+impl SampleState {
+    fn new(seed: u64) -> Self {
+        Self { seed, pixel_hash: seed, index: 0, dimension: 0, rng: Rng::new(seed) }
+    }
+
+    fn start_pixel(&mut self, i: i32, j: i32) {
+        self.pixel_hash = mix_seed(self.seed, &[i as u64, j as u64]);
+        self.rng = Rng::new(self.pixel_hash);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.index = index;
+        self.dimension = 0;
+        // Two values keep the stream apart from the dimension hashes of the pixel.
+        self.rng = Rng::derive(self.pixel_hash, &[index as u64, u64::MAX]);
+    }
+
+    /// 消耗 `count` 个维度，返回它们的哈希值，同一像素的同一维度在各样本间保持一致
+    fn next_dimensions(&mut self, count: u64) -> u64 {
+        let hash = mix_seed(self.pixel_hash, &[self.dimension]);
+        self.dimension += count;
+        hash
+    }
+}
+
+/// 抖动网格采样
+///
+/// 1D 维度被划分为 `samples_per_pixel` 层，2D 维度被划分为接近正方形的网格。每个维度用不同的随机排列
+/// 把样本分配到各层，层内位置随机抖动。超出 `samples_per_pixel` 的样本（如自适应采样追加的样本）每组
+/// 重新分层，使用新的排列。
+pub struct StratifiedSampler {
+    state: SampleState,
+    samples_per_pixel: u32,
+    /// 2D 网格的列数和行数
+    x_strata: u32,
+    y_strata: u32
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: u32) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let x_strata = (samples_per_pixel as f64).sqrt().ceil() as u32;
+        let y_strata = samples_per_pixel.div_ceil(x_strata);
+
+        Self { state: SampleState::new(seed), samples_per_pixel, x_strata, y_strata }
+    }
+
+    /// 消耗 `dimensions` 个维度，返回当前样本在 `count` 层中所在的层
+    fn stratum(&mut self, dimensions: u64, count: u32) -> u32 {
+        let hash = self.state.next_dimensions(dimensions);
+        let index = self.state.index;
+        // Each group of `count` samples covers every stratum once, in its own order.
+        let hash = mix_seed(hash, &[(index / count) as u64]);
+
+        permutation_element(index % count, count, hash as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel(&mut self, i: i32, j: i32) {
+        self.state.start_pixel(i, j);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let count = self.samples_per_pixel;
+        let stratum = self.stratum(1, count);
+
+        (stratum as f64 + self.state.rng.random()) / count as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let stratum = self.stratum(2, self.x_strata * self.y_strata);
+        let (x, y) = (stratum % self.x_strata, stratum / self.x_strata);
+
+        (
+            (x as f64 + self.state.rng.random()) / self.x_strata as f64,
+            (y as f64 + self.state.rng.random()) / self.y_strata as f64
+        )
+    }
+
+    fn rng(&mut self) -> &mut Rng {
+        &mut self.state.rng
+    }
+}
+
+/// Halton 序列采样
+///
+/// 第 d 个维度是以第 d 个素数为底的根式反演，每个像素使用不同的 Owen 置乱。维度超过素数表后退化为独立采样。
+pub struct HaltonSampler {
+    state: SampleState
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { state: SampleState::new(seed) }
+    }
+
+    fn sample(&mut self) -> f64 {
+        let dimension = self.state.dimension as usize;
+        let hash = self.state.next_dimensions(1);
+        match PRIMES.get(dimension) {
+            Some(&base) => owen_scrambled_radical_inverse(base, self.state.index as u64, hash),
+            None => self.state.rng.random()
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel(&mut self, i: i32, j: i32) {
+        self.state.start_pixel(i, j);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.sample()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.sample(), self.sample())
+    }
+
+    fn rng(&mut self) -> &mut Rng {
+        &mut self.state.rng
+    }
+}
+
+/// Sobol 序列采样
+///
+/// 每对维度都使用 Sobol 序列的前两个维度，再用 Owen 置乱打乱样本顺序和各位数字，使不同维度对互不相关
+/// （Burley, "Practical Hash-based Owen Scrambling", 2020）。样本数为 2 的幂时分布最均匀。
+pub struct SobolSampler {
+    state: SampleState
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { state: SampleState::new(seed) }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel(&mut self, i: i32, j: i32) {
+        self.state.start_pixel(i, j);
+    }
+
+    fn start_sample(&mut self, index: u32) {
+        self.state.start_sample(index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let hash = self.state.next_dimensions(1);
+        let index = nested_uniform_scramble(self.state.index, hash as u32);
+
+        to_unit(nested_uniform_scramble(sobol(index, 0), (hash >> 32) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let hash = self.state.next_dimensions(2);
+        let index = nested_uniform_scramble(self.state.index, hash as u32);
+        let x_seed = mix_seed(hash, &[0]) as u32;
+        let y_seed = mix_seed(hash, &[1]) as u32;
+
+        (
+            to_unit(nested_uniform_scramble(sobol(index, 0), x_seed)),
+            to_unit(nested_uniform_scramble(sobol(index, 1), y_seed))
+        )
+    }
+
+    fn rng(&mut self) -> &mut Rng {
+        &mut self.state.rng
+    }
+}
+
+/// 小于 1 的最大浮点数
+const ONE_MINUS_EPSILON: f64 = 1. - f64::EPSILON / 2.;
+
+/// Halton 序列各维度的底数
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+    137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193, 197, 199, 211, 223,
+    227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307, 311
+];
+
+/// 32 位定点数转换为 [0, 1) 中的浮点数
+fn to_unit(x: u32) -> f64 {
+    x as f64 / (1_u64 << 32) as f64
+}
+
+/// 由 `seed` 决定的 [0, n) 的随机排列中第 `i` 个元素（Kensler, "Correlated Multi-Jittered Sampling", 2013）
+fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    // Hash within the next power of two and walk the cycle until the result falls inside [0, n).
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+
+    ((i as u64 + seed as u64) % n as u64) as u32
+}
+
+/// 以 `base` 为底的根式反演，每一位数字都按其更高位数字的哈希值随机排列
+fn owen_scrambled_radical_inverse(base: u64, mut a: u64, hash: u64) -> f64 {
+    let inv_base = (base as f64).recip();
+    let mut inv_base_m = 1.;
+    let mut reversed_digits = 0_u64;
+
+    // Stop once further digits can no longer change the result.
+    while 1. - inv_base_m < 1. {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_hash = mix_seed(hash, &[reversed_digits]) as u32;
+        let digit = permutation_element(digit as u32, base as u32, digit_hash) as u64;
+        reversed_digits = reversed_digits * base + digit;
+        inv_base_m *= inv_base;
+        a = next;
+    }
+
+    (reversed_digits as f64 * inv_base_m).min(ONE_MINUS_EPSILON)
+}
+
+/// Sobol 序列的第 `dimension`（0 或 1）个维度，32 位定点数
+fn sobol(mut index: u32, dimension: usize) -> u32 {
+    if dimension == 0 {
+        // The first dimension is the van der Corput sequence.
+        return index.reverse_bits();
+    }
+
+    // Direction numbers of the second dimension, primitive polynomial x + 1.
+    let mut result = 0;
+    let mut v = 1_u32 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+
+    result
+}
+
+/// 基于哈希的 Owen 置乱（Laine-Karras 置换作用于反转后的位）
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+
+    x.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [SamplerKind; 4] = [
+        SamplerKind::Independent,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol
+    ];
+
+    /// 像素中前 `count` 个样本跳过 `skip` 对维度之后的两个维度
+    fn points(sampler: &mut dyn Sampler, count: u32, skip: usize) -> Vec<(f64, f64)> {
+        (0..count).map(|index| {
+            sampler.start_sample(index);
+            for _ in 0..skip {
+                sampler.get_2d();
+            }
+            sampler.get_2d()
+        }).collect()
+    }
+
+    #[test]
+    fn test_adaptive_sampling() {
+        let adaptive = AdaptiveSampling::new(16, 256, 0.05);
+        assert!(!adaptive.should_check(8) && adaptive.should_check(16) && adaptive.should_check(48));
+
+        // The error is relative to the brightness, down to a floor.
+        assert!(adaptive.converged(1., 0.04 * 0.04));
+        assert!(!adaptive.converged(1., 0.06 * 0.06));
+        assert!(!adaptive.converged(0.1, 0.01 * 0.01));
+        assert!(adaptive.converged(0., 0.));
+        assert!(!adaptive.converged(0., 0.001 * 0.001));
+    }
+
+    #[test]
+    fn test_permutation_element() {
+        for n in [1, 5, 16, 100] {
+            let mut elements: Vec<u32> = (0..n).map(|i| permutation_element(i, n, 0x1234_5678)).collect();
+            elements.sort();
+            assert!(elements.iter().copied().eq(0..n));
+        }
+    }
+
+    #[test]
+    fn test_sample_range() {
+        for kind in KINDS {
+            let mut sampler = kind.create(3, 16);
+            sampler.start_pixel(5, 7);
+            for index in 0..64 {
+                sampler.start_sample(index);
+                for _ in 0..100 {
+                    let u = sampler.get_1d();
+                    assert!((0. ..1.).contains(&u), "{:?}: {}", kind, u);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        for kind in KINDS {
+            let mut a = kind.create(3, 16);
+            let mut b = kind.create(3, 16);
+            b.start_pixel(1, 1);
+            points(b.as_mut(), 4, 0);
+
+            a.start_pixel(2, 9);
+            b.start_pixel(2, 9);
+            assert_eq!(points(a.as_mut(), 16, 1), points(b.as_mut(), 16, 1), "{:?}", kind);
+
+            // A pixel that resumes sampling later gets the same samples as if it had never stopped.
+            let sample = |sampler: &mut dyn Sampler, index| {
+                sampler.start_sample(index);
+                (sampler.get_2d(), sampler.rng().random())
+            };
+            a.start_pixel(2, 9);
+            let continuous: Vec<_> = (0..16).map(|index| sample(a.as_mut(), index)).collect();
+            b.start_pixel(2, 9);
+            let mut resumed: Vec<_> = (0..8).map(|index| sample(b.as_mut(), index)).collect();
+            b.start_pixel(2, 9);
+            resumed.extend((8..16).map(|index| sample(b.as_mut(), index)));
+            assert_eq!(continuous, resumed, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn test_stratification() {
+        // 16 samples of the stratified and Sobol samplers fall one into each cell of a 4x4 grid in
+        // every pair of dimensions, 6 Halton samples one into each cell of a 2x3 grid in the first.
+        let cells = |points: &[(f64, f64)], nx: usize, ny: usize| {
+            let mut cells: Vec<usize> = points.iter()
+                .map(|&(x, y)| (y * ny as f64) as usize * nx + (x * nx as f64) as usize)
+                .collect();
+            cells.sort();
+            cells
+        };
+        for (kind, count, nx, ny, pairs) in [
+            (SamplerKind::Stratified, 16, 4, 4, 3),
+            (SamplerKind::Sobol, 16, 4, 4, 3),
+            (SamplerKind::Halton, 6, 2, 3, 1)
+        ] {
+            let mut sampler = kind.create(11, count);
+            for pixel in 0..8 {
+                sampler.start_pixel(pixel, 0);
+                for skip in 0..pairs {
+                    let points = points(sampler.as_mut(), count, skip);
+                    assert!(cells(&points, nx, ny).into_iter().eq(0..count as usize), "{:?}", kind);
+                }
+            }
+        }
+
+        // Samples past the count, like those adaptive sampling adds, are stratified again in a new order.
+        let mut sampler = SamplerKind::Stratified.create(11, 16);
+        sampler.start_pixel(0, 0);
+        let points = points(sampler.as_mut(), 32, 0);
+        let (first, second) = points.split_at(16);
+        assert!(cells(second, 4, 4).into_iter().eq(0..16));
+        let order = |points: &[(f64, f64)]| -> Vec<usize> {
+            points.iter().map(|&(x, y)| (y * 4.) as usize * 4 + (x * 4.) as usize).collect()
+        };
+        assert_ne!(order(first), order(second));
+    }
+
+    #[test]
+    fn test_lower_error() {
+        // Integrate f(x, y) = x y over the unit square with 16 samples in many pixels.
+        let mean_squared_error = |kind: SamplerKind| {
+            let mut sampler = kind.create(5, 16);
+            let mut sum = 0.;
+            for pixel in 0..256 {
+                sampler.start_pixel(pixel, 0);
+                let estimate = (0..16).map(|index| {
+                    sampler.start_sample(index);
+                    sampler.get_1d();
+                    let (x, y) = sampler.get_2d();
+                    x * y
+                }).sum::<f64>() / 16.;
+                sum += (estimate - 0.25).powi(2);
+            }
+            sum / 256.
+        };
+
+        let independent = mean_squared_error(SamplerKind::Independent);
+        for kind in [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+            let error = mean_squared_error(kind);
+            assert!(error < 0.5 * independent, "{:?}: {} >= {}", kind, error, independent);
+        }
+    }
+}
@@ -1,4 +1,68 @@
+use utils::rng::Rng;
+
+use crate::{
+    bvh::{self, BVHOptions, BVHStats},
+    camera::Camera,
+    hittable_list::HittableList
+};
+
 pub mod bouncing_spheres;
 pub mod checkered_spheres;
+pub mod cornell_box;
+pub mod cornell_smoke;
 pub mod earth;
 pub mod perlin_spheres;
+pub mod quads;
+pub mod simple_light;
+
+pub mod scene_file;
+
+/// 场景：待渲染的物体以及默认相机
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: Camera,
+    /// 渲染时直接采样的物体，通常是场景中的光源
+    pub lights: HittableList,
+    /// 渲染前组织物体所用的 BVH 参数，`None` 表示直接遍历物体列表
+    pub bvh: Option<BVHOptions>
+}
+
+impl Scene {
+    /// 按 `bvh` 参数把场景中的物体组织为 BVH，返回构建统计，应在渲染前调用一次
+    pub fn build_bvh(&mut self) -> Option<BVHStats> {
+        let options = self.bvh?;
+        if self.world.objects.is_empty() {
+            return None;
+        }
+
+        let (bvh, stats) = bvh::build(&mut self.world.objects, &options);
+        self.world = HittableList::from_hittable(bvh);
+
+        Some(stats)
+    }
+}
+
+/// 构建场景的函数
+pub type SceneBuilder = fn(&mut Rng) -> Scene;
+
+/// 所有内置场景及其名称
+pub const SCENES: [(&str, SceneBuilder); 8] = [
+    ("bouncing_spheres", bouncing_spheres::bouncing_spheres),
+    ("checkered_spheres", checkered_spheres::checkered_spheres),
+    ("cornell_box", cornell_box::cornell_box),
+    ("cornell_smoke", cornell_smoke::cornell_smoke),
+    ("earth", earth::earth),
+    ("perlin_spheres", perlin_spheres::perlin_spheres),
+    ("quads", quads::quads),
+    ("simple_light", simple_light::simple_light)
+];
+
+/// 内置场景的名称列表
+pub fn names() -> impl Iterator<Item = &'static str> {
+    SCENES.iter().map(|(name, _)| *name)
+}
+
+/// 按名称构建内置场景，随机生成的内容取自 `rng`
+pub fn by_name(name: &str, rng: &mut Rng) -> Option<Scene> {
+    SCENES.iter().find(|(scene_name, _)| *scene_name == name).map(|(_, build)| build(rng))
+}
@@ -1,49 +1,47 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use utils::rtweekend::{random, random_range};
+use utils::rng::Rng;
 use vector3::{Point3, Vec3};
 use crate::{
-    bvh::BVHNode,
+    bvh::BVHOptions,
     camera::Camera,
     color::Color,
     hittable_list::HittableList,
     material::{Dielectric, Lambertian, Metal},
+    scenes::Scene,
     sphere::Sphere,
     texture::CheckerTexture
 };
 
-pub fn bouncing_spheres() {
+pub fn bouncing_spheres(rng: &mut Rng) -> Scene {
     // World
     let mut world = HittableList::default();
 
-    let checker = Rc::new(CheckerTexture::from_color(
+    let checker = Arc::new(CheckerTexture::from_color(
         0.32,
         Color::new(0.2, 0.3, 0.1),
         Color::isotropic(0.9)
     ));
-    world.add(Rc::new(Sphere::new_stationary_sphere(
+    world.add(Arc::new(Sphere::new_stationary_sphere(
         Point3::from_y(-1000.),
         1000.,
-        Rc::new(Lambertian::new(checker.clone()))
+        Arc::new(Lambertian::new(checker.clone()))
     )));
 
-    // let ground_material = Rc::new(Lambertian::from_color(Color::new(0.5, 0.5, 0.5)));
-    // world.add(Rc::new(Sphere::new_stationary_sphere(Point3::from_y(-1000.), 1000., ground_material)));
+    // let ground_material = Arc::new(Lambertian::from_color(Color::new(0.5, 0.5, 0.5)));
+    // world.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_y(-1000.), 1000., ground_material)));
 
-    generate_sphere_random(&mut world);
+    generate_sphere_random(&mut world, rng);
 
-    let material_1 = Rc::new(Dielectric::new(1.5));
-    let material_2 = Rc::new(Lambertian::from_color(Color::new(0.4, 0.2, 0.1)));
-    let material_3 = Rc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.));
+    let material_1 = Arc::new(Dielectric::new(1.5));
+    let material_2 = Arc::new(Lambertian::from_color(Color::new(0.4, 0.2, 0.1)));
+    let material_3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.));
 
-    world.add(Rc::new(Sphere::new_stationary_sphere(Point3::from_y(1.), 1., material_1)));
-    world.add(Rc::new(Sphere::new_stationary_sphere(Point3::new(-4., 1., 0.), 1., material_2)));
-    world.add(Rc::new(Sphere::new_stationary_sphere(Point3::new(4., 1., 0.), 1., material_3)));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_y(1.), 1., material_1)));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::new(-4., 1., 0.), 1., material_2)));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::new(4., 1., 0.), 1., material_3)));
 
-    world = HittableList::from_hittable(Rc::new(BVHNode::from_hittable_list(&mut world)));
-
-    // Camera render
-    Camera::new(
+    let camera = Camera::new(
         16./9.,
         400,
         100,
@@ -54,36 +52,38 @@ pub fn bouncing_spheres() {
         Vec3::from_y(1.),
         0.6,
         10.
-    ).render(&world);
+    );
+
+    Scene { world, camera, lights: HittableList::default(), bvh: Some(BVHOptions::default()) }
 }
 
-fn generate_sphere_random(world: &mut HittableList) {
+fn generate_sphere_random(world: &mut HittableList, rng: &mut Rng) {
     for j in -10..11 {
         for i in -10..11 {
-            let which_material = random();
+            let which_material = rng.random();
             let center = Point3::new(
-                j as f64 + 0.9 * random(),
+                j as f64 + 0.9 * rng.random(),
                 0.2,
-                i as f64 + 0.9 * random()
+                i as f64 + 0.9 * rng.random()
             );
 
             if (center - Point3::new(4., 0.2, 0.)).norm() > 0.9 {
                 if which_material < 0.7 {
                     // diffuse
-                    let albedo = Color::random() * Color::random();
-                    let material = Rc::new(Lambertian::from_color(albedo));
-                    let end = center + Vec3::from_y(random_range(0., 0.5));
-                    world.add(Rc::new(Sphere::new_moving_sphere(center, end, 0.2, material)));
+                    let albedo = Color::random(rng) * Color::random(rng);
+                    let material = Arc::new(Lambertian::from_color(albedo));
+                    let end = center + Vec3::from_y(rng.random_range(0., 0.5));
+                    world.add(Arc::new(Sphere::new_moving_sphere(center, end, 0.2, material)));
                 } else if which_material < 0.9 {
                     // metal
-                    let albedo = Color::random_range(0.5, 1.);
-                    let fuzz = random_range(0., 0.5);
-                    let material = Rc::new(Metal::new(albedo, fuzz));
-                    world.add(Rc::new(Sphere::new_stationary_sphere(center, 0.2, material)));
+                    let albedo = Color::random_range(0.5, 1., rng);
+                    let fuzz = rng.random_range(0., 0.5);
+                    let material = Arc::new(Metal::new(albedo, fuzz));
+                    world.add(Arc::new(Sphere::new_stationary_sphere(center, 0.2, material)));
                 } else {
                     // glass
-                    let material = Rc::new(Dielectric::new(1.5));
-                    world.add(Rc::new(Sphere::new_stationary_sphere(center, 0.2, material)));
+                    let material = Arc::new(Dielectric::new(1.5));
+                    world.add(Arc::new(Sphere::new_stationary_sphere(center, 0.2, material)));
                 }
             }
         }
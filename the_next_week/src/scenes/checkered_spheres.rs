@@ -1,37 +1,38 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use utils::rng::Rng;
 use vector3::{Point3, Vec3};
 use crate::{
     camera::Camera,
     color::Color,
     hittable_list::HittableList,
     material::Lambertian,
+    scenes::Scene,
     sphere::Sphere,
     texture::CheckerTexture
 };
 
-pub fn checkered_spheres() {
+pub fn checkered_spheres(_rng: &mut Rng) -> Scene {
     // World
     let mut world = HittableList::default();
 
-    let checker = Rc::new(CheckerTexture::from_color(
+    let checker = Arc::new(CheckerTexture::from_color(
         0.32,
         Color::new(0.2, 0.3, 0.1),
         Color::isotropic(0.9)
     ));
-    world.add(Rc::new(Sphere::new_stationary_sphere(
+    world.add(Arc::new(Sphere::new_stationary_sphere(
         Point3::from_y(-10.),
         10.,
-        Rc::new(Lambertian::new(checker.clone()))
+        Arc::new(Lambertian::new(checker.clone()))
     )));
-    world.add(Rc::new(Sphere::new_stationary_sphere(
+    world.add(Arc::new(Sphere::new_stationary_sphere(
         Point3::from_y(10.),
         10.,
-        Rc::new(Lambertian::new(checker.clone()))
+        Arc::new(Lambertian::new(checker.clone()))
     )));
 
-    // Camera render
-    Camera::new(
+    let camera = Camera::new(
         16./9.,
         400,
         100,
@@ -42,5 +43,7 @@ pub fn checkered_spheres() {
         Vec3::from_y(1.),
         0.,
         10.
-    ).render(&world);
+    );
+
+    Scene { world, camera, lights: HittableList::default(), bvh: None }
 }
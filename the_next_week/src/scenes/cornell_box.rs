@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use utils::rng::Rng;
+use vector3::{Point3, Vec3};
+use crate::{
+    background::Background,
+    camera::Camera,
+    color::Color,
+    hittable_list::HittableList,
+    material::{DiffuseLight, Lambertian},
+    quad::{make_box, Quad},
+    scenes::Scene,
+    transform::Transform
+};
+
+pub fn cornell_box(_rng: &mut Rng) -> Scene {
+    let mut world = HittableList::default();
+
+    let red = Arc::new(Lambertian::from_color(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::from_color(Color::isotropic(0.73)));
+    let green = Arc::new(Lambertian::from_color(Color::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::from_color(Color::isotropic(15.)));
+
+    world.add(Arc::new(Quad::new(Point3::from_x(555.), Vec3::from_y(555.), Vec3::from_z(555.), green)));
+    world.add(Arc::new(Quad::new(Point3::zero(), Vec3::from_y(555.), Vec3::from_z(555.), red)));
+    let light = Arc::new(Quad::new(Point3::new(343., 554., 332.), Vec3::from_x(-130.), Vec3::from_z(-105.), light));
+    world.add(light.clone());
+    world.add(Arc::new(Quad::new(Point3::zero(), Vec3::from_x(555.), Vec3::from_z(555.), white.clone())));
+    world.add(Arc::new(Quad::new(Point3::isotropic(555.), Vec3::from_x(-555.), Vec3::from_z(-555.), white.clone())));
+    world.add(Arc::new(Quad::new(Point3::from_z(555.), Vec3::from_x(555.), Vec3::from_y(555.), white.clone())));
+
+    let box1 = Arc::new(make_box(Point3::zero(), Point3::new(165., 330., 165.), white.clone()));
+    let box1 = Arc::new(Transform::rotate_y(box1, 15.));
+    world.add(Arc::new(Transform::translate(box1, Vec3::new(265., 0., 295.))));
+
+    let box2 = Arc::new(make_box(Point3::zero(), Point3::isotropic(165.), white));
+    let box2 = Arc::new(Transform::rotate_y(box2, -18.));
+    world.add(Arc::new(Transform::translate(box2, Vec3::new(130., 0., 65.))));
+
+    let mut camera = Camera::new(
+        1.,
+        600,
+        200,
+        50,
+        40.,
+        Point3::new(278., 278., -800.),
+        Point3::new(278., 278., 0.),
+        Vec3::from_y(1.),
+        0.,
+        10.
+    );
+    camera.background = Background::Solid(Color::zero());
+
+    Scene { world, camera, lights: HittableList::from_hittable(light), bvh: None }
+}
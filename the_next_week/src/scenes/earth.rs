@@ -1,26 +1,29 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use utils::rng::Rng;
 use vector3::{Point3, Vec3};
 use crate::{
     camera::Camera,
     hittable_list::HittableList,
     material::Lambertian,
+    scenes::Scene,
     sphere::Sphere,
     texture::ImageTexture
 };
 
-const IMAGE_FILENAME: &str = "the_next_week/asserts/earthmap.jpg";
+// Resolved against the crate rather than the working directory, so the scene runs from anywhere.
+const IMAGE_FILENAME: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/asserts/earthmap.jpg");
 
-pub fn earth() {
-    let earth_texture = Rc::new(ImageTexture::new(IMAGE_FILENAME));
-    let earth_surface = Rc::new(Lambertian::new(earth_texture));
-    let globe = Rc::new(Sphere::new_stationary_sphere(
+pub fn earth(_rng: &mut Rng) -> Scene {
+    let earth_texture = Arc::new(ImageTexture::new(IMAGE_FILENAME).expect("failed to load the earth texture"));
+    let earth_surface = Arc::new(Lambertian::new(earth_texture));
+    let globe = Arc::new(Sphere::new_stationary_sphere(
         Point3::zero(),
         2.,
         earth_surface
     ));
 
-    Camera::new(
+    let camera = Camera::new(
         16./9.,
         400,
         100,
@@ -31,5 +34,7 @@ pub fn earth() {
         Vec3::from_y(1.),
         0.,
         10.
-    ).render(&HittableList::from_hittable(globe));
+    );
+
+    Scene { world: HittableList::from_hittable(globe), camera, lights: HittableList::default(), bvh: None }
 }
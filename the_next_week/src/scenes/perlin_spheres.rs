@@ -1,33 +1,35 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
+use utils::rng::Rng;
 use vector3::{Point3, Vec3};
 use crate::{
     camera::Camera,
     hittable_list::HittableList,
     material::Lambertian,
+    scenes::Scene,
     sphere::Sphere,
     texture::NoiseTexture
 };
 
 
-pub fn perlin_spheres() {
+pub fn perlin_spheres(rng: &mut Rng) -> Scene {
     let mut world = HittableList::default();
-    let perlin_texture = Rc::new(NoiseTexture::new());
-    let perlin_material = Rc::new(Lambertian::new(perlin_texture));
+    let perlin_texture = Arc::new(NoiseTexture::new(rng));
+    let perlin_material = Arc::new(Lambertian::new(perlin_texture));
 
-    world.add(Rc::new(Sphere::new_stationary_sphere(
+    world.add(Arc::new(Sphere::new_stationary_sphere(
         Point3::from_y(-1000.),
         1000.,
         perlin_material.clone()
     )));
 
-    world.add(Rc::new(Sphere::new_stationary_sphere(
+    world.add(Arc::new(Sphere::new_stationary_sphere(
         Point3::from_y(2.),
         2.,
         perlin_material
     )));
 
-    Camera::new(
+    let camera = Camera::new(
         16./9.,
         400,
         100,
@@ -38,5 +40,7 @@ pub fn perlin_spheres() {
         Vec3::from_y(1.),
         0.,
         10.
-    ).render(&world);
+    );
+
+    Scene { world, camera, lights: HittableList::default(), bvh: None }
 }
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use utils::rng::Rng;
+use vector3::{Point3, Vec3};
+use crate::{
+    camera::Camera,
+    color::Color,
+    hittable_list::HittableList,
+    material::Lambertian,
+    quad::Quad,
+    scenes::Scene
+};
+
+pub fn quads(_rng: &mut Rng) -> Scene {
+    let mut world = HittableList::default();
+
+    // Materials
+    let left_red = Arc::new(Lambertian::from_color(Color::new(1., 0.2, 0.2)));
+    let back_green = Arc::new(Lambertian::from_color(Color::new(0.2, 1., 0.2)));
+    let right_blue = Arc::new(Lambertian::from_color(Color::new(0.2, 0.2, 1.)));
+    let upper_orange = Arc::new(Lambertian::from_color(Color::new(1., 0.5, 0.)));
+    let lower_teal = Arc::new(Lambertian::from_color(Color::new(0.2, 0.8, 0.8)));
+
+    // Quads
+    world.add(Arc::new(Quad::new(Point3::new(-3., -2., 5.), Vec3::from_z(-4.), Vec3::from_y(4.), left_red)));
+    world.add(Arc::new(Quad::new(Point3::new(-2., -2., 0.), Vec3::from_x(4.), Vec3::from_y(4.), back_green)));
+    world.add(Arc::new(Quad::new(Point3::new(3., -2., 1.), Vec3::from_z(4.), Vec3::from_y(4.), right_blue)));
+    world.add(Arc::new(Quad::new(Point3::new(-2., 3., 1.), Vec3::from_x(4.), Vec3::from_z(4.), upper_orange)));
+    world.add(Arc::new(Quad::new(Point3::new(-2., -3., 5.), Vec3::from_x(4.), Vec3::from_z(-4.), lower_teal)));
+
+    let camera = Camera::new(
+        1.,
+        400,
+        100,
+        50,
+        80.,
+        Point3::from_z(9.),
+        Point3::zero(),
+        Vec3::from_y(1.),
+        0.,
+        10.
+    );
+
+    Scene { world, camera, lights: HittableList::default(), bvh: None }
+}
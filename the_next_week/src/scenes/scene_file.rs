@@ -0,0 +1,906 @@
+//! TOML 场景描述文件
+//!
+//! 场景文件由相机参数、具名纹理、具名材质和物体列表组成：
+//!
+//! ```toml
+//! bvh = { split = "sah", max_leaf_size = 4 }
+//!
+//! [camera]
+//! image_width = 400
+//! vertical_fov = 20.0
+//! look_from = [13.0, 2.0, 3.0]
+//! look_at = [0.0, 0.0, 0.0]
+//!
+//! [textures.checker]
+//! type = "checker"
+//! scale = 0.32
+//! even = [0.2, 0.3, 0.1]
+//! odd = [0.9, 0.9, 0.9]
+//!
+//! [materials.ground]
+//! type = "lambertian"
+//! albedo = "checker"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, -1000.0, 0.0]
+//! radius = 1000.0
+//! material = "ground"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, 1.0, 0.0]
+//! radius = 1.0
+//! material = { type = "dielectric", refraction_index = 1.5 }
+//! ```
+//!
+//! 图片纹理、环境贴图和模型的相对路径以场景文件所在目录为基准。
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc
+};
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+    background::Background,
+    bvh::{BVHLayout, BVHOptions, SplitMethod},
+    camera::Camera,
+    color::ColorSpace,
+    constant_medium::ConstantMedium,
+    environment::EnvironmentMap,
+    hittable::Hittable,
+    hittable_list::HittableList,
+    material::{
+        Conductor,
+        ConductorPreset,
+        Dielectric,
+        DiffuseLight,
+        Isotropic,
+        Lambertian,
+        Material,
+        Metal,
+        Principled,
+        RoughDielectric
+    },
+    obj::{self, ObjError},
+    quad::{make_box, Quad},
+    sampler::{AdaptiveSampling, SamplerKind},
+    scenes::Scene,
+    sphere::Sphere,
+    texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture, TextureFilter, UvTransform, WrapMode},
+    tone_map::{ToneMapOperator, ToneMapping},
+    transform::Transform
+};
+use utils::{rng::Rng, rtweekend::degree_to_radian};
+use vector3::{matrix::Mat4, Vec3};
+
+/// 加载场景文件时的错误
+#[derive(Debug)]
+pub enum SceneError {
+    /// 读取文件失败
+    Io(PathBuf, io::Error),
+    /// 文件不是合法的场景描述
+    Parse(toml::de::Error),
+    /// 引用了未定义的纹理
+    UnknownTexture(String),
+    /// 引用了未定义的材质
+    UnknownMaterial(String),
+    /// 纹理之间存在循环引用
+    TextureCycle(String),
+    /// 加载网格模型失败
+    Obj(ObjError),
+    /// 加载纹理图片或环境贴图失败
+    Image(PathBuf, image::ImageError),
+    /// 变换不可逆（如缩放系数为零或旋转轴为零向量）
+    SingularTransform
+}
+
+impl Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "failed to read {}: {}", path.display(), err),
+            Self::Parse(err) => write!(f, "invalid scene file: {}", err),
+            Self::UnknownTexture(name) => write!(f, "unknown texture `{}`", name),
+            Self::UnknownMaterial(name) => write!(f, "unknown material `{}`", name),
+            Self::TextureCycle(name) => write!(f, "texture `{}` references itself", name),
+            Self::Obj(err) => write!(f, "invalid OBJ model: {}", err),
+            Self::Image(path, err) => write!(f, "failed to load {}: {}", path.display(), err),
+            Self::SingularTransform => write!(f, "transform is not invertible")
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(_, err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::Obj(err) => Some(err),
+            Self::Image(_, err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<toml::de::Error> for SceneError {
+    fn from(err: toml::de::Error) -> Self {
+        Self::Parse(err)
+    }
+}
+
+/// 从文件加载场景
+pub fn load(path: impl AsRef<Path>, rng: &mut Rng) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).map_err(|err| SceneError::Io(path.to_path_buf(), err))?;
+
+    parse(&text, path.parent().unwrap_or(Path::new("")), rng)
+}
+
+/// 解析场景描述，`base_dir` 是相对路径的基准目录，随机生成的内容（如噪声纹理）取自 `rng`
+pub fn parse(text: &str, base_dir: &Path, rng: &mut Rng) -> Result<Scene, SceneError> {
+    let desc: SceneDesc = toml::from_str(text)?;
+    let mut loader = Loader {
+        desc: &desc,
+        base_dir,
+        rng,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        resolving: Vec::new()
+    };
+
+    let mut world = HittableList::default();
+    for object in &desc.objects {
+        world.add(loader.object(object)?);
+    }
+    let mut lights = HittableList::default();
+    for light in &desc.lights {
+        lights.add(loader.object(light)?);
+    }
+
+    Ok(Scene { world, camera: desc.camera.build(base_dir)?, lights, bvh: desc.bvh.options() })
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    /// 是否用 BVH 组织所有物体
+    #[serde(default)]
+    bvh: BvhDesc,
+    #[serde(default)]
+    camera: CameraDesc,
+    /// 具名纹理，可以被材质和其他纹理引用
+    #[serde(default)]
+    textures: HashMap<String, TextureDesc>,
+    /// 具名材质，可以被物体引用
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+    /// 渲染时直接采样的物体，只用于生成朝向光源的方向，通常与 `objects` 中的光源相同
+    ///
+    /// 目前只有球体、四边形、三角形、椭圆、圆盘及它们的变换支持采样，其材质不影响结果。
+    #[serde(default)]
+    lights: Vec<ObjectDesc>
+}
+
+/// BVH 设置：`true`/`false`，或者给出构建参数的表
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BvhDesc {
+    Enabled(bool),
+    Options(BvhOptionsDesc)
+}
+
+impl Default for BvhDesc {
+    fn default() -> Self {
+        Self::Enabled(false)
+    }
+}
+
+impl BvhDesc {
+    fn options(&self) -> Option<BVHOptions> {
+        match self {
+            Self::Enabled(enabled) => enabled.then(BVHOptions::default),
+            Self::Options(options) => Some(BVHOptions {
+                split: match options.split {
+                    SplitDesc::Median => SplitMethod::Median,
+                    SplitDesc::Sah => SplitMethod::Sah
+                },
+                layout: match options.layout {
+                    LayoutDesc::Tree => BVHLayout::Tree,
+                    LayoutDesc::Linear => BVHLayout::Linear
+                },
+                max_leaf_size: options.max_leaf_size,
+                bins: options.bins
+            })
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BvhOptionsDesc {
+    /// 划分方法，默认 `median`
+    split: SplitDesc,
+    /// 遍历时的存储方式，默认 `linear`
+    layout: LayoutDesc,
+    /// 叶节点最多包含的物体数
+    max_leaf_size: usize,
+    /// SAH 划分时每个轴上的桶数
+    bins: usize
+}
+
+impl Default for BvhOptionsDesc {
+    fn default() -> Self {
+        let options = BVHOptions::default();
+        Self {
+            split: SplitDesc::Median,
+            layout: LayoutDesc::Linear,
+            max_leaf_size: options.max_leaf_size,
+            bins: options.bins
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SplitDesc {
+    /// 沿最长轴按中位数划分
+    Median,
+    /// 按表面积启发式划分
+    Sah
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LayoutDesc {
+    /// 指针连接的树
+    Tree,
+    /// 按深度优先顺序展开的数组
+    Linear
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SamplerDesc {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDesc {
+    aspect_ratio: f64,
+    #[serde(deserialize_with = "positive")]
+    image_width: i32,
+    #[serde(deserialize_with = "positive")]
+    samples_per_pixel: i32,
+    #[serde(deserialize_with = "non_negative")]
+    max_depth: i32,
+    vertical_fov: f64,
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    vup: [f64; 3],
+    defocus_angle: f64,
+    focus_dist: f64,
+    /// 背景颜色或环境贴图，省略时为天空渐变
+    background: Option<BackgroundDesc>,
+    /// 像素采样策略，默认 `independent`
+    sampler: SamplerDesc,
+    /// 开启自适应采样，每个像素平均仍取 `samples_per_pixel` 个样本
+    adaptive: Option<AdaptiveDesc>,
+    /// 曝光补偿（EV）
+    exposure: f64,
+    /// 写出 8 位图片时的色调映射，默认 `clamp`
+    tone_map: ToneMapDesc,
+    /// `reinhard_extended` 映射为白色的亮度，默认 4
+    white_point: f64
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ToneMapDesc {
+    Clamp,
+    Reinhard,
+    ReinhardExtended,
+    Aces,
+    Hable,
+    #[serde(rename = "agx")]
+    AgX,
+    /// 按亮度的伪彩色
+    FalseColor
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16. / 9.,
+            image_width: 400,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            vertical_fov: 90.,
+            look_from: [0., 0., 0.],
+            look_at: [0., 0., -1.],
+            vup: [0., 1., 0.],
+            defocus_angle: 0.,
+            focus_dist: 10.,
+            background: None,
+            sampler: SamplerDesc::Independent,
+            adaptive: None,
+            exposure: 0.,
+            tone_map: ToneMapDesc::Clamp,
+            white_point: 4.
+        }
+    }
+}
+
+impl CameraDesc {
+    fn build(&self, base_dir: &Path) -> Result<Camera, SceneError> {
+        let mut camera = Camera::new(
+            self.aspect_ratio,
+            self.image_width,
+            self.samples_per_pixel,
+            self.max_depth,
+            self.vertical_fov,
+            vec3(self.look_from),
+            vec3(self.look_at),
+            vec3(self.vup),
+            self.defocus_angle,
+            self.focus_dist
+        );
+        match &self.background {
+            Some(BackgroundDesc::Solid(color)) => camera.background = Background::Solid(vec3(*color)),
+            Some(BackgroundDesc::Environment(environment)) => {
+                let path = base_dir.join(&environment.path);
+                let map = EnvironmentMap::load(&path).map_err(|err| SceneError::Image(path, err))?
+                    .with_rotation(environment.rotation)
+                    .with_intensity(environment.intensity);
+                camera.background = Background::Environment(Arc::new(map));
+            },
+            None => {}
+        }
+        camera.sampler = match self.sampler {
+            SamplerDesc::Independent => SamplerKind::Independent,
+            SamplerDesc::Stratified => SamplerKind::Stratified,
+            SamplerDesc::Halton => SamplerKind::Halton,
+            SamplerDesc::Sobol => SamplerKind::Sobol
+        };
+        camera.adaptive_sampling = self.adaptive.as_ref().map(|adaptive| {
+            let samples = self.samples_per_pixel.max(1) as u32;
+            AdaptiveSampling::new(adaptive.min_samples.min(samples), adaptive.max_samples.unwrap_or(4 * samples), adaptive.threshold)
+        });
+        let operator = match self.tone_map {
+            ToneMapDesc::Clamp => ToneMapOperator::Clamp,
+            ToneMapDesc::Reinhard => ToneMapOperator::Reinhard,
+            ToneMapDesc::ReinhardExtended => ToneMapOperator::ReinhardExtended { white: self.white_point },
+            ToneMapDesc::Aces => ToneMapOperator::Aces,
+            ToneMapDesc::Hable => ToneMapOperator::Hable,
+            ToneMapDesc::AgX => ToneMapOperator::AgX,
+            ToneMapDesc::FalseColor => ToneMapOperator::FalseColor
+        };
+        camera.tone_mapping = ToneMapping::new(operator).with_exposure(self.exposure);
+
+        Ok(camera)
+    }
+}
+
+/// 背景：颜色 `[r, g, b]`，或者环境贴图
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BackgroundDesc {
+    Solid([f64; 3]),
+    Environment(EnvironmentDesc)
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AdaptiveDesc {
+    /// 允许的相对误差，默认 0.05
+    threshold: f64,
+    /// 每个像素至少取的样本数，默认 16，超过 `samples_per_pixel` 时取 `samples_per_pixel`
+    min_samples: u32,
+    /// 每个像素最多取的样本数，省略时为 `samples_per_pixel` 的 4 倍
+    max_samples: Option<u32>
+}
+
+impl Default for AdaptiveDesc {
+    fn default() -> Self {
+        Self { threshold: 0.05, min_samples: 16, max_samples: None }
+    }
+}
+
+/// 环境贴图
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    /// .hdr、.exr 等经纬度图片
+    path: PathBuf,
+    /// 绕 y 轴旋转的角度
+    #[serde(default)]
+    rotation: f64,
+    /// 亮度倍数
+    #[serde(default = "default_intensity")]
+    intensity: f64
+}
+
+/// 读取正整数
+fn positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    let value = i32::deserialize(deserializer)?;
+    if value < 1 {
+        return Err(de::Error::custom(format!("expected a positive integer, found {}", value)));
+    }
+    Ok(value)
+}
+
+/// 读取非负整数
+fn non_negative<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    let value = i32::deserialize(deserializer)?;
+    if value < 0 {
+        return Err(de::Error::custom(format!("expected a non-negative integer, found {}", value)));
+    }
+    Ok(value)
+}
+
+fn default_intensity() -> f64 {
+    1.
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { color: [f64; 3] },
+    Checker { scale: f64, even: TextureRef, odd: TextureRef },
+    Image {
+        path: PathBuf,
+        /// 插值方式，默认 `bilinear`
+        #[serde(default)]
+        filter: TextureFilterDesc,
+        /// 平铺方式，默认 `clamp`
+        #[serde(default)]
+        wrap: WrapModeDesc,
+        /// 纹理坐标的缩放 `[u, v]`
+        scale: Option<[f64; 2]>,
+        /// 纹理坐标的偏移 `[u, v]`
+        #[serde(default)]
+        offset: [f64; 2],
+        /// 逆时针旋转的角度
+        #[serde(default)]
+        rotation: f64,
+        /// mip 层级偏移，正数更模糊
+        #[serde(default)]
+        lod_bias: f64,
+        /// 省略时 8 位和 16 位图片按 sRGB 解码，浮点图片视为线性；粗糙度、法线等数据贴图应标记为 `linear`
+        color_space: Option<ColorSpaceDesc>
+    },
+    Noise
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TextureFilterDesc {
+    Nearest,
+    #[default]
+    Bilinear,
+    Bicubic
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ColorSpaceDesc {
+    Srgb,
+    Linear
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WrapModeDesc {
+    Repeat,
+    Mirror,
+    #[default]
+    Clamp
+}
+
+/// 纹理引用：纹理名称或纯色
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Color([f64; 3]),
+    Name(String)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    /// `alpha` 引用的纹理的 alpha 通道小于 0.5 的地方被镂空（如带透明通道的 PNG 树叶、栅栏）
+    Lambertian { albedo: TextureRef, alpha: Option<TextureRef> },
+    Metal { albedo: [f64; 3], #[serde(default)] fuzz: f64 },
+    Dielectric { refraction_index: f64 },
+    /// GGX 微表面金属
+    Conductor {
+        ior: ConductorIorDesc,
+        /// 0 为理想镜面
+        #[serde(default)]
+        roughness: f64,
+        /// 沿纹理 u 方向更粗糙
+        #[serde(default)]
+        anisotropy: f64
+    },
+    /// GGX 微表面玻璃，`roughness` 和 `anisotropy` 与 `conductor` 相同
+    RoughDielectric {
+        refraction_index: f64,
+        #[serde(default)]
+        roughness: f64,
+        #[serde(default)]
+        anisotropy: f64
+    },
+    /// Disney 风格的原理化材质
+    Principled(Box<PrincipledDesc>),
+    DiffuseLight {
+        emit: TextureRef,
+        /// 亮度倍数
+        strength: Option<ParamRef>,
+        /// 背面也发光
+        #[serde(default)]
+        two_sided: bool,
+        /// 镂空，与 `lambertian` 相同
+        alpha: Option<TextureRef>
+    },
+    /// 介质的相位函数
+    Isotropic { albedo: TextureRef }
+}
+
+/// 原理化材质的参数，省略的参数取 [`Principled::default`] 中的值
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PrincipledDesc {
+    base_color: Option<TextureRef>,
+    metallic: Option<ParamRef>,
+    roughness: Option<ParamRef>,
+    anisotropy: Option<ParamRef>,
+    specular: Option<ParamRef>,
+    specular_tint: Option<ParamRef>,
+    sheen: Option<ParamRef>,
+    sheen_tint: Option<ParamRef>,
+    clearcoat: Option<ParamRef>,
+    clearcoat_roughness: Option<ParamRef>,
+    transmission: Option<ParamRef>,
+    refraction_index: Option<f64>,
+    emission: Option<TextureRef>,
+    emission_strength: Option<ParamRef>,
+    /// 背面也发光
+    two_sided: bool,
+    /// 镂空，与 `lambertian` 相同
+    alpha: Option<TextureRef>
+}
+
+/// 标量参数：数值或纹理引用（取纹理颜色的亮度）
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ParamRef {
+    Value(f64),
+    Texture(TextureRef)
+}
+
+/// 导体的复折射率：预设金属名称或每个通道的 `eta`、`k`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConductorIorDesc {
+    Preset(ConductorPresetDesc),
+    Complex { eta: [f64; 3], k: [f64; 3] }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ConductorPresetDesc {
+    Gold,
+    Copper,
+    Aluminium
+}
+
+/// 材质引用：材质名称或内联材质
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaterialRef {
+    Name(String),
+    Inline(MaterialDesc)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere { center: [f64; 3], radius: f64, material: MaterialRef },
+    MovingSphere { origin: [f64; 3], end: [f64; 3], radius: f64, material: MaterialRef },
+    Quad { q: [f64; 3], u: [f64; 3], v: [f64; 3], material: MaterialRef },
+    Triangle { q: [f64; 3], u: [f64; 3], v: [f64; 3], material: MaterialRef },
+    Ellipse { center: [f64; 3], u: [f64; 3], v: [f64; 3], material: MaterialRef },
+    Disk { center: [f64; 3], radius: f64, normal: [f64; 3], material: MaterialRef },
+    Box { a: [f64; 3], b: [f64; 3], material: MaterialRef },
+    /// OBJ 模型
+    Mesh { path: PathBuf, material: MaterialRef },
+    /// 包装另一个物体，依次施加 `scale`、`rotate` 和 `translate`
+    Transform {
+        object: Box<ObjectDesc>,
+        scale: Option<[f64; 3]>,
+        rotate: Option<RotateDesc>,
+        translate: Option<[f64; 3]>
+    },
+    /// 把封闭的边界物体 `boundary` 填充为密度为 `density` 的烟雾，`material` 通常为 `isotropic`
+    ConstantMedium { boundary: Box<ObjectDesc>, density: f64, material: MaterialRef }
+}
+
+/// 绕轴 `axis` 旋转 `angle` 度
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RotateDesc {
+    axis: [f64; 3],
+    angle: f64
+}
+
+/// 将描述中的名称解析为纹理和材质，每个具名对象只创建一次
+struct Loader<'a> {
+    desc: &'a SceneDesc,
+    base_dir: &'a Path,
+    rng: &'a mut Rng,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    /// 正在解析的纹理名称，用于检测循环引用
+    resolving: Vec<String>
+}
+
+impl Loader<'_> {
+    fn texture_ref(&mut self, texture: &TextureRef) -> Result<Arc<dyn Texture>, SceneError> {
+        match texture {
+            TextureRef::Color(color) => Ok(Arc::new(SolidColor::new(vec3(*color)))),
+            TextureRef::Name(name) => self.named_texture(name)
+        }
+    }
+
+    fn param_ref(&mut self, param: &ParamRef) -> Result<Arc<dyn Texture>, SceneError> {
+        match param {
+            ParamRef::Value(value) => Ok(Principled::constant(*value)),
+            ParamRef::Texture(texture) => self.texture_ref(texture)
+        }
+    }
+
+    fn named_texture(&mut self, name: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(texture.clone());
+        }
+        if self.resolving.iter().any(|resolving| resolving == name) {
+            return Err(SceneError::TextureCycle(name.to_string()));
+        }
+        let desc = self.desc.textures.get(name)
+            .ok_or_else(|| SceneError::UnknownTexture(name.to_string()))?;
+
+        self.resolving.push(name.to_string());
+        let texture = self.texture(desc);
+        self.resolving.pop();
+
+        let texture = texture?;
+        self.textures.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    fn texture(&mut self, desc: &TextureDesc) -> Result<Arc<dyn Texture>, SceneError> {
+        Ok(match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::new(vec3(*color))),
+            TextureDesc::Checker { scale, even, odd } => {
+                let even = self.texture_ref(even)?;
+                let odd = self.texture_ref(odd)?;
+                Arc::new(CheckerTexture::new(*scale, even, odd))
+            },
+            TextureDesc::Image { path, filter, wrap, scale, offset, rotation, lod_bias, color_space } => {
+                let path = self.base_dir.join(path);
+                let filter = match filter {
+                    TextureFilterDesc::Nearest => TextureFilter::Nearest,
+                    TextureFilterDesc::Bilinear => TextureFilter::Bilinear,
+                    TextureFilterDesc::Bicubic => TextureFilter::Bicubic
+                };
+                let wrap = match wrap {
+                    WrapModeDesc::Repeat => WrapMode::Repeat,
+                    WrapModeDesc::Mirror => WrapMode::Mirror,
+                    WrapModeDesc::Clamp => WrapMode::Clamp
+                };
+                let [scale_u, scale_v] = scale.unwrap_or([1., 1.]);
+                let transform = UvTransform {
+                    scale: (scale_u, scale_v),
+                    offset: (offset[0], offset[1]),
+                    rotation: *rotation
+                };
+                let filename = path.to_string_lossy();
+                let texture = match color_space {
+                    Some(ColorSpaceDesc::Srgb) => ImageTexture::with_color_space(&filename, ColorSpace::Srgb),
+                    Some(ColorSpaceDesc::Linear) => ImageTexture::with_color_space(&filename, ColorSpace::Linear),
+                    None => ImageTexture::new(&filename)
+                }.map_err(|err| SceneError::Image(path.clone(), err))?;
+                Arc::new(texture
+                    .with_filter(filter)
+                    .with_wrap(wrap)
+                    .with_transform(transform)
+                    .with_lod_bias(*lod_bias))
+            },
+            TextureDesc::Noise => Arc::new(NoiseTexture::new(self.rng))
+        })
+    }
+
+    fn material_ref(&mut self, material: &MaterialRef) -> Result<Arc<dyn Material>, SceneError> {
+        match material {
+            MaterialRef::Inline(desc) => self.material(desc),
+            MaterialRef::Name(name) => {
+                if let Some(material) = self.materials.get(name) {
+                    return Ok(material.clone());
+                }
+                let desc = self.desc.materials.get(name)
+                    .ok_or_else(|| SceneError::UnknownMaterial(name.to_string()))?;
+                let material = self.material(desc)?;
+                self.materials.insert(name.to_string(), material.clone());
+                Ok(material)
+            }
+        }
+    }
+
+    fn material(&mut self, desc: &MaterialDesc) -> Result<Arc<dyn Material>, SceneError> {
+        Ok(match desc {
+            MaterialDesc::Lambertian { albedo, alpha } => {
+                let mut lambertian = Lambertian::new(self.texture_ref(albedo)?);
+                if let Some(alpha) = alpha {
+                    lambertian = lambertian.with_alpha(self.texture_ref(alpha)?);
+                }
+                Arc::new(lambertian)
+            },
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal::new(vec3(*albedo), *fuzz)),
+            MaterialDesc::Dielectric { refraction_index } => Arc::new(Dielectric::new(*refraction_index)),
+            MaterialDesc::Conductor { ior, roughness, anisotropy } => {
+                let (eta, k) = match ior {
+                    ConductorIorDesc::Preset(preset) => match preset {
+                        ConductorPresetDesc::Gold => ConductorPreset::Gold,
+                        ConductorPresetDesc::Copper => ConductorPreset::Copper,
+                        ConductorPresetDesc::Aluminium => ConductorPreset::Aluminium
+                    }.ior(),
+                    ConductorIorDesc::Complex { eta, k } => (vec3(*eta), vec3(*k))
+                };
+                Arc::new(Conductor::new(eta, k, *roughness, *anisotropy))
+            },
+            MaterialDesc::RoughDielectric { refraction_index, roughness, anisotropy } => {
+                Arc::new(RoughDielectric::new(*refraction_index, *roughness, *anisotropy))
+            },
+            MaterialDesc::Principled(desc) => Arc::new(self.principled(desc)?),
+            MaterialDesc::DiffuseLight { emit, strength, two_sided, alpha } => {
+                let mut light = DiffuseLight::new(self.texture_ref(emit)?).with_two_sided(*two_sided);
+                if let Some(strength) = strength {
+                    light = light.with_strength(self.param_ref(strength)?);
+                }
+                if let Some(alpha) = alpha {
+                    light = light.with_alpha(self.texture_ref(alpha)?);
+                }
+                Arc::new(light)
+            },
+            MaterialDesc::Isotropic { albedo } => Arc::new(Isotropic::new(self.texture_ref(albedo)?))
+        })
+    }
+
+    fn principled(&mut self, desc: &PrincipledDesc) -> Result<Principled, SceneError> {
+        let mut principled = Principled::default();
+        if let Some(base_color) = &desc.base_color {
+            principled.base_color = self.texture_ref(base_color)?;
+        }
+        if let Some(emission) = &desc.emission {
+            principled.emission = self.texture_ref(emission)?;
+        }
+        if let Some(alpha) = &desc.alpha {
+            principled.alpha = Some(self.texture_ref(alpha)?);
+        }
+        principled.two_sided = desc.two_sided;
+
+        let params = [
+            (&desc.metallic, &mut principled.metallic),
+            (&desc.roughness, &mut principled.roughness),
+            (&desc.anisotropy, &mut principled.anisotropy),
+            (&desc.specular, &mut principled.specular),
+            (&desc.specular_tint, &mut principled.specular_tint),
+            (&desc.sheen, &mut principled.sheen),
+            (&desc.sheen_tint, &mut principled.sheen_tint),
+            (&desc.clearcoat, &mut principled.clearcoat),
+            (&desc.clearcoat_roughness, &mut principled.clearcoat_roughness),
+            (&desc.transmission, &mut principled.transmission),
+            (&desc.emission_strength, &mut principled.emission_strength)
+        ];
+        for (value, param) in params {
+            if let Some(value) = value {
+                *param = self.param_ref(value)?;
+            }
+        }
+        if let Some(refraction_index) = desc.refraction_index {
+            principled.refraction_index = refraction_index;
+        }
+
+        Ok(principled)
+    }
+
+    fn object(&mut self, desc: &ObjectDesc) -> Result<Arc<dyn Hittable>, SceneError> {
+        Ok(match desc {
+            ObjectDesc::Sphere { center, radius, material } => Arc::new(Sphere::new_stationary_sphere(
+                vec3(*center),
+                *radius,
+                self.material_ref(material)?
+            )),
+            ObjectDesc::MovingSphere { origin, end, radius, material } => Arc::new(Sphere::new_moving_sphere(
+                vec3(*origin),
+                vec3(*end),
+                *radius,
+                self.material_ref(material)?
+            )),
+            ObjectDesc::Quad { q, u, v, material } => Arc::new(Quad::new(
+                vec3(*q),
+                vec3(*u),
+                vec3(*v),
+                self.material_ref(material)?
+            )),
+            ObjectDesc::Triangle { q, u, v, material } => Arc::new(Quad::triangle(
+                vec3(*q),
+                vec3(*u),
+                vec3(*v),
+                self.material_ref(material)?
+            )),
+            ObjectDesc::Ellipse { center, u, v, material } => Arc::new(Quad::ellipse(
+                vec3(*center),
+                vec3(*u),
+                vec3(*v),
+                self.material_ref(material)?
+            )),
+            ObjectDesc::Disk { center, radius, normal, material } => Arc::new(Quad::disk(
+                vec3(*center),
+                *radius,
+                vec3(*normal),
+                self.material_ref(material)?
+            )),
+            ObjectDesc::Box { a, b, material } => Arc::new(make_box(
+                vec3(*a),
+                vec3(*b),
+                self.material_ref(material)?
+            )),
+            ObjectDesc::Mesh { path, material } => {
+                let meshes = obj::load(self.base_dir.join(path), self.material_ref(material)?)
+                    .map_err(SceneError::Obj)?;
+                let mut list = HittableList::default();
+                meshes.into_iter().for_each(|mesh| list.add(Arc::new(mesh)));
+                Arc::new(list)
+            },
+            ObjectDesc::Transform { object, scale, rotate, translate } => {
+                // Scale first, then rotate, then translate.
+                let mut matrix = Mat4::identity();
+                if let Some(scale) = scale {
+                    matrix = Mat4::scale(vec3(*scale)) * matrix;
+                }
+                if let Some(RotateDesc { axis, angle }) = rotate {
+                    matrix = Mat4::rotation(vec3(*axis), degree_to_radian(*angle)) * matrix;
+                }
+                if let Some(translate) = translate {
+                    matrix = Mat4::translation(vec3(*translate)) * matrix;
+                }
+                if matrix.inverse().is_none() {
+                    return Err(SceneError::SingularTransform);
+                }
+
+                Arc::new(Transform::new(self.object(object)?, matrix))
+            },
+            ObjectDesc::ConstantMedium { boundary, density, material } => Arc::new(ConstantMedium::with_phase_function(
+                self.object(boundary)?,
+                *density,
+                self.material_ref(material)?
+            ))
+        })
+    }
+}
+
+fn vec3([x, y, z]: [f64; 3]) -> Vec3 {
+    Vec3::new(x, y, z)
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use utils::rng::Rng;
+use vector3::{Point3, Vec3};
+use crate::{
+    background::Background,
+    camera::Camera,
+    color::Color,
+    hittable_list::HittableList,
+    material::{DiffuseLight, Lambertian},
+    quad::Quad,
+    scenes::Scene,
+    sphere::Sphere,
+    texture::NoiseTexture
+};
+
+pub fn simple_light(rng: &mut Rng) -> Scene {
+    let mut world = HittableList::default();
+
+    let perlin_material = Arc::new(Lambertian::new(Arc::new(NoiseTexture::new(rng))));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_y(-1000.), 1000., perlin_material.clone())));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_y(2.), 2., perlin_material)));
+
+    let diffuse_light = Arc::new(DiffuseLight::from_color(Color::isotropic(4.)));
+    let mut lights = HittableList::default();
+    lights.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_y(7.), 2., diffuse_light.clone())));
+    lights.add(Arc::new(Quad::new(Point3::new(3., 1., -2.), Vec3::from_x(2.), Vec3::from_y(2.), diffuse_light)));
+    lights.objects.iter().for_each(|light| world.add(light.clone()));
+
+    let mut camera = Camera::new(
+        16./9.,
+        400,
+        100,
+        50,
+        20.,
+        Point3::new(26., 3., 6.),
+        Point3::from_y(2.),
+        Vec3::from_y(1.),
+        0.,
+        10.
+    );
+    camera.background = Background::Solid(Color::zero());
+
+    Scene { world, camera, lights, bvh: None }
+}
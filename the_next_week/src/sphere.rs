@@ -1,24 +1,25 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::{
     aabb::AABB,
-    hittable::{HitRecord, Hittable},
+    hittable::{object_key, HitRecord, Hittable},
     material::Material,
-    ray::Ray
+    ray::Ray,
+    sampler::Sampler
 };
-use utils::{interval::Interval, rtweekend::PI};
-use vector3::{Point3, Vec3};
+use utils::{interval::Interval, rng::Rng, rtweekend::PI};
+use vector3::{extension::sample_unit_vector, onb::ONB, Point3, Vec3};
 
 pub struct Sphere {
     center: Ray,
     radius: f64,
-    material: Rc<dyn Material>,
+    material: Arc<dyn Material>,
     bbox: AABB
 }
 
 impl Sphere {
     /// 创建静止球体
-    pub fn new_stationary_sphere(center: Point3, radius: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new_stationary_sphere(center: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
         let rvec = Vec3::isotropic(radius);
         Self {
             center: Ray::new(center, Vec3::zero(), 0.),
@@ -29,7 +30,7 @@ impl Sphere {
     }
 
     /// 创建运动球体
-    pub fn new_moving_sphere(origin: Point3, end: Point3, radius: f64, material: Rc<dyn Material>) -> Self {
+    pub fn new_moving_sphere(origin: Point3, end: Point3, radius: f64, material: Arc<dyn Material>) -> Self {
         let center = Ray::new(origin, end - origin, 0.);
         let rvec = Vec3::isotropic(radius);
         let box0 = AABB::from_points(center.at(0.) - rvec, center.at(0.) + rvec);
@@ -42,7 +43,7 @@ impl Sphere {
         }
     }
 
-    fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+    pub(crate) fn get_sphere_uv(p: &Point3) -> (f64, f64) {
         // p: a given point on the sphere of radius one, centered at the origin.
         // u: returned value [0,1] of angle around the Y axis from X=-1.
         // v: returned value [0,1] of angle from Y=-1 to Y=+1.
@@ -57,45 +58,95 @@ impl Sphere {
 
         (u, v)
     }
-}
 
-impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
-        let current_center = self.center.at(ray.time());
+    /// 朝半径为 `radius`、距离平方为 `distance_squared` 的球体所张圆锥内均匀分布的方向，圆锥轴为 +z
+    fn random_to_sphere(radius: f64, distance_squared: f64, (r1, r2): (f64, f64)) -> Vec3 {
+        let z = 1. + r2 * ((1. - radius * radius / distance_squared).sqrt() - 1.);
+
+        let phi = 2. * PI * r1;
+        let x = phi.cos() * (1. - z * z).sqrt();
+        let y = phi.sin() * (1. - z * z).sqrt();
+
+        Vec3::new(x, y, z)
+    }
+
+    /// 光线与球面在 `ray_t` 内的交点参数，由近到远，不考虑材质的镂空
+    fn roots(&self, ray: &Ray, ray_t: Interval, current_center: Point3) -> impl Iterator<Item = f64> {
         let oc = current_center - *ray.origin();
         let a = ray.direction().squared();
         let h = ray.direction().dot(&oc);
         let c = oc.squared() - self.radius*self.radius;
 
         let disc = h*h - a*c;
-        if disc < 0. {
-            return None;
-        }
+        let roots = (disc >= 0.).then(|| {
+            let sqrt_disc = disc.sqrt();
+            [(h - sqrt_disc) / a, (h + sqrt_disc) / a]
+        });
+        roots.into_iter().flatten().filter(move |&root| ray_t.surrounds(root))
+    }
 
-        let sqrt_disc = disc.sqrt();
-        // Find the nearest root that lies in the acceptable range.
-        let mut root = (h - sqrt_disc) / a;
-        if !ray_t.surrounds(root) {
-            root = (h + sqrt_disc) / a;
-            if !ray_t.surrounds(root) {
-                return None;
-            }
-        }
+    fn intersect(&self, ray: &Ray, ray_t: Interval) -> Option<HitRecord> {
+        let current_center = self.center.at(ray.time());
+        // Find the nearest root that lies in the acceptable range and is not cut out.
+        self.roots(ray, ray_t, current_center)
+            .map(|root| self.hit_record(ray, root, current_center))
+            .find(HitRecord::is_opaque)
+    }
 
+    /// 光线参数为 `root` 处的交点
+    fn hit_record(&self, ray: &Ray, root: f64, current_center: Point3) -> HitRecord {
         let mut hit_record = HitRecord {
             point: ray.at(root),
             material: self.material.clone(),
             t: root,
+            object: object_key(self),
             ..Default::default()
         };
         let outward_normal = (hit_record.point - current_center) / self.radius;
         hit_record.set_face_normal(ray, outward_normal);
         (hit_record.u, hit_record.v) = Self::get_sphere_uv(&outward_normal);
+        // U runs around the y axis, so dP/du is horizontal and vanishes at the poles.
+        hit_record.tangent = 2. * PI * self.radius * Vec3::new(outward_normal.z, 0., -outward_normal.x);
+
+        hit_record
+    }
+}
 
-        Some(hit_record)
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray, ray_t: Interval, _rng: &mut Rng) -> Option<HitRecord> {
+        self.intersect(ray, ray_t)
     }
 
     fn bounding_box(&self) -> &AABB {
         &self.bbox
     }
+
+    // This method only works for stationary spheres.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let distance_squared = (self.center.at(0.) - *origin).squared();
+        if distance_squared <= self.radius * self.radius {
+            // Seen from inside, the sphere covers every direction.
+            return 1. / (4. * PI);
+        }
+        let ray = Ray::new(*origin, *direction, 0.);
+        if self.roots(&ray, Interval::new(0.001, f64::INFINITY), self.center.at(0.)).next().is_none() {
+            return 0.;
+        }
+
+        let cos_theta_max = (1. - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2. * PI * (1. - cos_theta_max);
+
+        solid_angle.recip()
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let direction = self.center.at(0.) - *origin;
+        let distance_squared = direction.squared();
+        if distance_squared <= self.radius * self.radius {
+            return sample_unit_vector(sampler.get_2d());
+        }
+        let uvw = ONB::new(&direction);
+
+        uvw.transform(Self::random_to_sphere(self.radius, distance_squared, sampler.get_2d()))
+    }
 }
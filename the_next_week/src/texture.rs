@@ -1,11 +1,22 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::{color::Color, perlin::Perlin, rtw_image::RTWImage};
-use utils::interval::Interval;
+use crate::{color::{Color, ColorSpace}, perlin::Perlin, rtw_image::RTWImage};
+use utils::{rng::Rng, rtweekend::{degree_to_radian, Degree}};
 use vector3::Point3;
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+
+    /// 纹理坐标空间中以 (`u`, `v`) 为中心、宽度约为 `footprint` 的区域上的平均颜色，用于抗锯齿；
+    /// 默认忽略足迹
+    fn filtered_value(&self, u: f64, v: f64, p: &Point3, _footprint: f64) -> Color {
+        self.value(u, v, p)
+    }
+
+    /// 不透明度，0 为完全透明，默认完全不透明
+    fn alpha(&self, _u: f64, _v: f64, _p: &Point3) -> f64 {
+        1.
+    }
 }
 
 #[derive(Default)]
@@ -31,59 +42,251 @@ impl Texture for SolidColor {
 
 pub struct CheckerTexture {
     inv_scale: f64,
-    even: Rc<dyn Texture>,
-    odd: Rc<dyn Texture>
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>
 }
 
 impl CheckerTexture {
-    pub fn new(scale: f64, even: Rc<dyn Texture>, odd: Rc<dyn Texture>) -> Self {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
         Self { inv_scale: scale.recip(), even, odd }
     }
 
     pub fn from_color(scale: f64, c1: Color, c2: Color) -> Self {
-        Self::new(scale, Rc::new(SolidColor::new(c1)), Rc::new(SolidColor::new(c2)))
+        Self::new(scale, Arc::new(SolidColor::new(c1)), Arc::new(SolidColor::new(c2)))
     }
-}
 
-impl Texture for CheckerTexture {
-    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+    /// `p` 所在格子的纹理
+    fn select(&self, p: &Point3) -> &dyn Texture {
         let x_integer = (self.inv_scale * p.x).floor() as i32;
         let y_integer = (self.inv_scale * p.y).floor() as i32;
         let z_integer = (self.inv_scale * p.z).floor() as i32;
         let is_even = (x_integer + y_integer + z_integer) % 2 == 0;
 
-        if is_even { self.even.value(u, v, p) } else { self.odd.value(u, v, p) }
+        if is_even { self.even.as_ref() } else { self.odd.as_ref() }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.select(p).value(u, v, p)
+    }
+
+    fn filtered_value(&self, u: f64, v: f64, p: &Point3, footprint: f64) -> Color {
+        self.select(p).filtered_value(u, v, p, footprint)
+    }
+
+    fn alpha(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.select(p).alpha(u, v, p)
+    }
+}
+
+/// 图片纹理的像素插值方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// 最近的像素
+    Nearest,
+    /// 相邻 2x2 个像素的双线性插值
+    #[default]
+    Bilinear,
+    /// 相邻 4x4 个像素的 Catmull-Rom 双三次插值
+    Bicubic
+}
+
+/// 纹理坐标超出 [0, 1] 时的处理方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// 平铺
+    Repeat,
+    /// 镜像平铺
+    Mirror,
+    /// 取边缘的像素
+    #[default]
+    Clamp
+}
+
+impl WrapMode {
+    /// 把像素坐标 `x` 映射到 [0, `size`) 内
+    fn apply(self, x: i64, size: u32) -> u32 {
+        let size = size as i64;
+        let x = match self {
+            Self::Repeat => x.rem_euclid(size),
+            Self::Mirror => {
+                let x = x.rem_euclid(2 * size);
+                if x < size { x } else { 2 * size - 1 - x }
+            },
+            Self::Clamp => x.clamp(0, size - 1)
+        };
+        x as u32
+    }
+}
+
+/// 纹理坐标的变换：先缩放，再绕原点逆时针旋转 `rotation` 度，最后平移 `offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvTransform {
+    pub scale: (f64, f64),
+    pub offset: (f64, f64),
+    pub rotation: Degree
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        Self { scale: (1., 1.), offset: (0., 0.), rotation: 0. }
+    }
+}
+
+impl UvTransform {
+    fn apply(&self, u: f64, v: f64) -> (f64, f64) {
+        let (u, v) = (u * self.scale.0, v * self.scale.1);
+        let (sin, cos) = degree_to_radian(self.rotation).sin_cos();
+        (cos * u - sin * v + self.offset.0, sin * u + cos * v + self.offset.1)
     }
 }
 
+/// 图片纹理，支持多种插值和平铺方式，并按光线足迹在 mip 金字塔的相邻两层之间插值（三线性过滤）
 #[derive(Default)]
 pub struct ImageTexture {
-    image: RTWImage
+    image: RTWImage,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    transform: UvTransform,
+    lod_bias: f64
 }
 
 impl ImageTexture {
-    pub fn new(image_filename: &str) -> Self {
-        Self { image: RTWImage::new(image_filename) }
+    /// 加载图片，8 位和 16 位图片按 sRGB 解码，浮点图片视为线性
+    pub fn new(image_filename: &str) -> Result<Self, image::ImageError> {
+        Ok(Self { image: RTWImage::new(image_filename)?, ..Default::default() })
     }
+
+    /// 按 `color_space` 解码图片，如把数据贴图标记为线性
+    pub fn with_color_space(image_filename: &str, color_space: ColorSpace) -> Result<Self, image::ImageError> {
+        Ok(Self { image: RTWImage::with_color_space(image_filename, color_space)?, ..Default::default() })
+    }
+
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: UvTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// 在按足迹选出的 mip 层级上加上 `lod_bias`，正数更模糊，负数更清晰
+    pub fn with_lod_bias(mut self, lod_bias: f64) -> Self {
+        self.lod_bias = lod_bias;
+        self
+    }
+
+    /// 纹理坐标处的 RGBA 值，`footprint` 为纹理坐标空间中的足迹宽度
+    fn sample(&self, u: f64, v: f64, footprint: f64) -> [f64; 4] {
+        let (u, v) = self.transform.apply(u, v);
+        let (scale_u, scale_v) = self.transform.scale;
+        let footprint = footprint * scale_u.abs().max(scale_v.abs());
+
+        // A footprint of one texel selects level zero, each doubling moves one level up.
+        let size = self.image.width().max(self.image.height()) as f64;
+        let lod = if footprint > 0. { (footprint * size).log2() } else { 0. } + self.lod_bias;
+        let lod = lod.clamp(0., (self.image.mip_level_count() - 1) as f64);
+
+        let level = lod.floor() as usize;
+        let t = lod - level as f64;
+        let lower = self.sample_level(level, u, v);
+        if t == 0. {
+            return lower;
+        }
+        let upper = self.sample_level(level + 1, u, v);
+        std::array::from_fn(|channel| (1. - t) * lower[channel] + t * upper[channel])
+    }
+
+    fn sample_level(&self, level: usize, u: f64, v: f64) -> [f64; 4] {
+        let (width, height) = self.image.mip_level_size(level);
+        // Flip V to image coordinates, and place texel centers at half-integer positions.
+        let x = u * width as f64 - 0.5;
+        let y = (1. - v) * height as f64 - 0.5;
+        let texel = |x: i64, y: i64| {
+            self.image.texel(level, self.wrap.apply(x, width), self.wrap.apply(y, height))
+        };
+
+        match self.filter {
+            TextureFilter::Nearest => texel(x.round() as i64, y.round() as i64),
+            TextureFilter::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let weights_x = [1. - (x - x0), x - x0];
+                let weights_y = [1. - (y - y0), y - y0];
+                filter_texels(x0 as i64, y0 as i64, &weights_x, &weights_y, texel)
+            },
+            TextureFilter::Bicubic => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let result = filter_texels(
+                    x0 as i64 - 1,
+                    y0 as i64 - 1,
+                    &catmull_rom_weights(x - x0),
+                    &catmull_rom_weights(y - y0),
+                    texel
+                );
+                // The negative lobes may overshoot below zero.
+                result.map(|channel| channel.max(0.))
+            }
+        }
+    }
+}
+
+/// 以 (`x0`, `y0`) 为左上角、按可分离权重加权的像素之和
+fn filter_texels(
+        x0: i64,
+        y0: i64,
+        weights_x: &[f64],
+        weights_y: &[f64],
+        texel: impl Fn(i64, i64) -> [f64; 4]
+    ) -> [f64; 4] {
+    let mut result = [0.; 4];
+    for (dy, weight_y) in weights_y.iter().enumerate() {
+        for (dx, weight_x) in weights_x.iter().enumerate() {
+            let value = texel(x0 + dx as i64, y0 + dy as i64);
+            result.iter_mut().zip(value).for_each(|(result, channel)| *result += weight_x * weight_y * channel);
+        }
+    }
+    result
+}
+
+/// Catmull-Rom 样条在相邻四个像素上的权重，`t` 为到第二个像素的距离
+fn catmull_rom_weights(t: f64) -> [f64; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        0.5 * (-t3 + 2. * t2 - t),
+        0.5 * (3. * t3 - 5. * t2 + 2.),
+        0.5 * (-3. * t3 + 4. * t2 + t),
+        0.5 * (t3 - t2)
+    ]
 }
 
 impl Texture for ImageTexture {
-    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.filtered_value(u, v, p, 0.)
+    }
+
+    fn filtered_value(&self, u: f64, v: f64, _p: &Point3, footprint: f64) -> Color {
         // If we have no texture data, then return solid cyan as a debugging aid.
-        if self.image.height() == 0 {
+        if self.image.is_empty() {
             return Color::new(0., 1., 1.);
         }
-        // Clamp input texture coordinates to [0,1] x [1,0]
-        let u = Interval::new(0., 1.).clamp(u);
-        // Flip V to image coordinates
-        let v = 1. - Interval::new(0., 1.).clamp(v);
+        let [r, g, b, _] = self.sample(u, v, footprint);
 
-        let x = (u * self.image.width() as f64) as u32;
-        let y = (v * self.image.height() as f64) as u32;
-        let pixel = self.image.pixel_data(x, y);
-        let color_scale = (255.0_f64).recip();
+        Color::new(r, g, b)
+    }
 
-        Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64) * color_scale
+    fn alpha(&self, u: f64, v: f64, _p: &Point3) -> f64 {
+        if self.image.is_empty() {
+            return 1.;
+        }
+        self.sample(u, v, 0.)[3]
     }
 }
 
@@ -93,8 +296,8 @@ pub struct NoiseTexture {
 }
 
 impl NoiseTexture {
-    pub fn new() -> Self {
-        Self { noise: Perlin::new() }
+    pub fn new(rng: &mut Rng) -> Self {
+        Self { noise: Perlin::new(rng) }
     }
 }
 
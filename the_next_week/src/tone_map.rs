@@ -0,0 +1,219 @@
+//! 色调映射
+//!
+//! 渲染得到的是没有上限的线性辐亮度，而 8 位图片只能表示 [0, 1]。色调映射先按曝光（EV）缩放，再用一条
+//! 压缩高光的曲线把颜色映射到显示范围内，之后才进行 sRGB 编码和量化。直接截断会让光源和高光处生硬地溢出。
+
+use crate::color::{luminance, Color};
+use vector3::{matrix::Mat3, Vec3};
+
+/// 色调映射曲线
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ToneMapOperator {
+    /// 不压缩，超过 1 的部分在量化时被截断
+    #[default]
+    Clamp,
+    /// Reinhard：按亮度 L / (1 + L) 压缩，永远达不到白色
+    Reinhard,
+    /// 扩展的 Reinhard：亮度为 `white` 时映射为白色
+    ReinhardExtended { white: f64 },
+    /// ACES 电影曲线（Stephen Hill 的 RRT + ODT 拟合）
+    Aces,
+    /// 《神秘海域 2》中 John Hable 的电影曲线
+    Hable,
+    /// Troy Sobotka 的 AgX，高光向白色过渡而不偏色
+    AgX,
+    /// 伪彩色：按亮度相对中灰（0.18）的档位着色，用于检查曝光
+    FalseColor
+}
+
+/// 曝光与色调映射，把线性辐亮度映射为 [0, 1] 内的显示线性颜色
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ToneMapping {
+    /// 曝光补偿，单位为档（EV），每增加 1 亮度翻倍
+    pub exposure: f64,
+    pub operator: ToneMapOperator
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMapOperator) -> Self {
+        Self { exposure: 0., operator }
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// 映射一个线性颜色
+    pub fn apply(&self, color: Color) -> Color {
+        let color = 2f64.powf(self.exposure) * color;
+
+        match self.operator {
+            ToneMapOperator::Clamp => color,
+            ToneMapOperator::Reinhard => scale_luminance(color, |l| l / (1. + l)),
+            ToneMapOperator::ReinhardExtended { white } => {
+                scale_luminance(color, |l| (l * (1. + l / (white * white)) / (1. + l)).min(1.))
+            },
+            ToneMapOperator::Aces => aces(color),
+            ToneMapOperator::Hable => {
+                const WHITE: f64 = 11.2;
+                // The curve was tuned for an exposure bias of two.
+                map(color, |x| (hable(2. * x) / hable(WHITE)).min(1.))
+            },
+            ToneMapOperator::AgX => agx(color),
+            ToneMapOperator::FalseColor => false_color(luminance(color))
+        }
+    }
+}
+
+/// 对每个通道施加 `f`
+fn map(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    Color::new(f(color.x), f(color.y), f(color.z))
+}
+
+/// 把颜色的亮度 L 映射为 `f(L)`，保持色相
+fn scale_luminance(color: Color, f: impl Fn(f64) -> f64) -> Color {
+    let l = luminance(color);
+    if l <= 0. {
+        return Color::zero();
+    }
+
+    f(l) / l * color
+}
+
+fn aces(color: Color) -> Color {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    let input = Mat3::from_rows(
+        Vec3::new(0.59719, 0.35458, 0.04823),
+        Vec3::new(0.07600, 0.90834, 0.01566),
+        Vec3::new(0.02840, 0.13383, 0.83777)
+    );
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    let output = Mat3::from_rows(
+        Vec3::new(1.60475, -0.53108, -0.07367),
+        Vec3::new(-0.10208, 1.10813, -0.00605),
+        Vec3::new(-0.00327, -0.07276, 1.07602)
+    );
+
+    let color = map(input * color, |v| {
+        (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081)
+    });
+    map(output * color, |v| v.clamp(0., 1.))
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+fn agx(color: Color) -> Color {
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let inset = Mat3::from_rows(
+        Vec3::new(0.842479062253094, 0.0784335999999992, 0.0792237451477643),
+        Vec3::new(0.0423282422610123, 0.878468636469772, 0.0791661274605434),
+        Vec3::new(0.0423756549057051, 0.0784336, 0.879142973793104)
+    );
+    let outset = Mat3::from_rows(
+        Vec3::new(1.19687900512017, -0.0980208811401368, -0.0990297440797205),
+        Vec3::new(-0.0528968517574562, 1.15190312990417, -0.0989611768448433),
+        Vec3::new(-0.0529716355144438, -0.0980434501171241, 1.15107367264116)
+    );
+
+    // Encode the stops of the inset color into [0, 1] and apply the sigmoid, whose output is display-encoded.
+    let color = map(inset * color, |v| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    });
+
+    map(outset * color, |v| v.clamp(0., 1.).powf(2.2))
+}
+
+fn false_color(luminance: f64) -> Color {
+    // One color every two stops from -8 to +8 EV around middle gray, which is green.
+    let ramp = [
+        Color::zero(),
+        Color::new(0.25, 0., 0.5),
+        Color::new(0., 0., 1.),
+        Color::new(0., 0.75, 1.),
+        Color::new(0., 0.75, 0.),
+        Color::new(1., 1., 0.),
+        Color::new(1., 0.5, 0.),
+        Color::new(1., 0., 0.),
+        Color::one()
+    ];
+
+    let stops = (luminance.max(1e-10) / 0.18).log2();
+    let t = ((stops + 8.) / 2.).clamp(0., (ramp.len() - 1) as f64);
+    let i = (t as usize).min(ramp.len() - 2);
+    let f = t - i as f64;
+
+    (1. - f) * ramp[i] + f * ramp[i + 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMapOperator; 5] = [
+        ToneMapOperator::Reinhard,
+        ToneMapOperator::ReinhardExtended { white: 4. },
+        ToneMapOperator::Aces,
+        ToneMapOperator::Hable,
+        ToneMapOperator::AgX
+    ];
+
+    #[test]
+    fn test_curves() {
+        for operator in OPERATORS {
+            let tone_mapping = ToneMapping::new(operator);
+            let gray = |value| tone_mapping.apply(Color::isotropic(value)).x;
+
+            // Black stays nearly black, gray stays gray, and brighter inputs never map darker.
+            assert!(gray(0.) < 1e-3, "{:?}", operator);
+            let mut previous = 0.;
+            for value in [0.01, 0.05, 0.18, 0.5, 1., 2., 4., 16., 1000.] {
+                let color = tone_mapping.apply(Color::isotropic(value));
+                assert!((color.x - color.y).abs() < 1e-3 && (color.y - color.z).abs() < 1e-3, "{:?}", operator);
+                assert!(color.x >= previous && color.x <= 1., "{:?}: {} => {}", operator, value, color.x);
+                previous = color.x;
+            }
+            // Highlights are compressed rather than clipped.
+            assert!(gray(2.) < 1. && gray(1000.) > 0.9, "{:?}", operator);
+        }
+
+        let reinhard = ToneMapping::new(ToneMapOperator::Reinhard);
+        assert_eq!(reinhard.apply(Color::isotropic(1.)), Color::isotropic(0.5));
+        let extended = ToneMapping::new(ToneMapOperator::ReinhardExtended { white: 4. });
+        assert!((extended.apply(Color::isotropic(4.)).x - 1.).abs() < 1e-12);
+        // Reinhard scales the luminance and keeps the ratio between the channels.
+        let color = reinhard.apply(Color::new(2., 1., 0.));
+        assert!((color.x / color.y - 2.).abs() < 1e-12 && color.z == 0.);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let clamp = ToneMapping::default().with_exposure(2.);
+        assert_eq!(clamp.apply(Color::new(0.25, 0.5, 1.)), Color::new(1., 2., 4.));
+        let reinhard = ToneMapping::new(ToneMapOperator::Reinhard).with_exposure(-1.);
+        assert_eq!(reinhard.apply(Color::isotropic(2.)), Color::isotropic(0.5));
+    }
+
+    #[test]
+    fn test_false_color() {
+        let false_color = ToneMapping::new(ToneMapOperator::FalseColor);
+        assert_eq!(false_color.apply(Color::isotropic(0.18)), Color::new(0., 0.75, 0.));
+        assert_eq!(false_color.apply(Color::zero()), Color::zero());
+        assert_eq!(false_color.apply(Color::isotropic(0.18 * 2f64.powi(10))), Color::one());
+        // Exposure shifts the bands.
+        assert_eq!(false_color.with_exposure(4.).apply(Color::isotropic(0.18)), Color::new(1., 0.5, 0.));
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AABB,
+    hittable::{object_key, HitRecord, Hittable},
+    ray::Ray,
+    sampler::Sampler
+};
+use utils::{interval::Interval, rng::Rng, rtweekend::{degree_to_radian, Degree}};
+use vector3::{matrix::{Mat3, Mat4}, Point3, Vec3};
+
+/// 仿射变换实例：对内部物体做平移、旋转和（非均匀）缩放
+///
+/// 求交时把射线变换到物体空间，再把交点和法线变换回世界空间，因此任何物体都可以被变换。
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    object_to_world: Mat4,
+    world_to_object: Mat4,
+    /// 法线变换矩阵，即线性部分的逆转置
+    normal_matrix: Mat3,
+    bbox: AABB
+}
+
+impl Transform {
+    /// 用物体空间到世界空间的变换矩阵 `matrix` 包装物体，矩阵必须可逆
+    pub fn new(object: Arc<dyn Hittable>, matrix: Mat4) -> Self {
+        let world_to_object = matrix.inverse().expect("transform matrix is not invertible");
+        let normal_matrix = world_to_object.linear().transpose();
+
+        // The world space bounding box encloses all eight transformed corners of the object's box.
+        let bbox = object.bounding_box();
+        let mut min = Point3::isotropic(f64::INFINITY);
+        let mut max = Point3::isotropic(f64::NEG_INFINITY);
+        for corner in 0..8 {
+            let x = if corner & 1 == 0 { bbox.x.min } else { bbox.x.max };
+            let y = if corner & 2 == 0 { bbox.y.min } else { bbox.y.max };
+            let z = if corner & 4 == 0 { bbox.z.min } else { bbox.z.max };
+            let p = matrix.transform_point(Point3::new(x, y, z));
+
+            min = Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        let bbox = AABB::from_points(min, max);
+
+        Self { object, object_to_world: matrix, world_to_object, normal_matrix, bbox }
+    }
+
+    /// 平移物体
+    pub fn translate(object: Arc<dyn Hittable>, offset: Vec3) -> Self {
+        Self::new(object, Mat4::translation(offset))
+    }
+
+    /// 绕过原点的轴 `axis` 旋转物体 `angle` 度
+    pub fn rotate(object: Arc<dyn Hittable>, axis: Vec3, angle: Degree) -> Self {
+        Self::new(object, Mat4::rotation(axis, degree_to_radian(angle)))
+    }
+
+    /// 绕 y 轴旋转物体 `angle` 度
+    pub fn rotate_y(object: Arc<dyn Hittable>, angle: Degree) -> Self {
+        Self::rotate(object, Vec3::from_y(1.), angle)
+    }
+
+    /// 以原点为中心缩放物体，各轴的缩放系数可以不同
+    pub fn scale(object: Arc<dyn Hittable>, factors: Vec3) -> Self {
+        Self::new(object, Mat4::scale(factors))
+    }
+
+    /// 物体空间到世界空间的变换矩阵
+    pub fn matrix(&self) -> &Mat4 {
+        &self.object_to_world
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, ray: &Ray, ray_t: Interval, rng: &mut Rng) -> Option<HitRecord> {
+        // Transform the ray from world space to object space. The direction is not normalized, so
+        // the ray parameter t is the same in both spaces.
+        let object_ray = Ray::new(
+            self.world_to_object.transform_point(*ray.origin()),
+            self.world_to_object.transform_vector(*ray.direction()),
+            ray.time()
+        );
+
+        // Determine whether an intersection exists in object space (and if so, where).
+        let mut hit_record = self.object.hit(&object_ray, ray_t, rng)?;
+
+        // Transform the intersection from object space back to world space. Normals transform by
+        // the inverse transpose, which keeps them on the same side as the incoming ray.
+        hit_record.point = self.object_to_world.transform_point(hit_record.point);
+        hit_record.normal = (self.normal_matrix * hit_record.normal).normalize();
+        hit_record.tangent = self.object_to_world.transform_vector(hit_record.tangent);
+        // Instances of the same object are told apart.
+        hit_record.object = object_key(self);
+
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> &AABB {
+        &self.bbox
+    }
+
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        // Directions map to object space by the normalized inverse linear part A. That changes
+        // solid angles by the Jacobian |det A| / |A w|^3, which is 1 for rigid transforms.
+        let inverse = self.world_to_object.linear();
+        let object_direction = inverse * direction.normalize();
+        let length = object_direction.norm();
+        let object_origin = self.world_to_object.transform_point(*origin);
+
+        self.object.pdf_value(&object_origin, &(object_direction / length))
+            * inverse.determinant().abs() / length.powi(3)
+    }
+
+    fn random(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Vec3 {
+        let object_origin = self.world_to_object.transform_point(*origin);
+        self.object_to_world.transform_vector(self.object.random(&object_origin, sampler))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::assert_f64_eq;
+
+    use crate::{material::Lambertian, quad::make_box, sphere::Sphere};
+    use super::*;
+
+    fn unit_box() -> Arc<dyn Hittable> {
+        let material = Arc::new(Lambertian::from_color(Vec3::one()));
+        Arc::new(make_box(Point3::zero(), Point3::one(), material))
+    }
+
+    fn assert_near(a: f64, b: f64) {
+        // Loose enough to absorb the padding of the quads' flat bounding boxes.
+        assert!((a - b).abs() < 1e-3, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_translate() {
+        let object = Transform::translate(unit_box(), Vec3::new(10., 0., 0.));
+        let bbox = object.bounding_box();
+        assert_near(bbox.x.min, 10.);
+        assert_near(bbox.x.max, 11.);
+
+        let ray = Ray::new(Point3::new(10.5, 0.5, 5.), Vec3::from_z(-1.), 0.);
+        let record = object.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut Rng::new(0)).unwrap();
+        assert_f64_eq!(record.t, 4.);
+        assert_eq!(record.point, Point3::new(10.5, 0.5, 1.));
+        assert_eq!(record.normal, Vec3::from_z(1.));
+        assert!(record.front_face);
+
+        let ray = Ray::new(Point3::new(0.5, 0.5, 5.), Vec3::from_z(-1.), 0.);
+        assert!(object.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut Rng::new(0)).is_none());
+    }
+
+    #[test]
+    fn test_rotate_bounding_box() {
+        // A quarter turn about y maps the unit box onto x in [0, 1], z in [-1, 0].
+        let object = Transform::rotate_y(unit_box(), 90.);
+        let bbox = object.bounding_box();
+        assert_near(bbox.x.min, 0.);
+        assert_near(bbox.x.max, 1.);
+        assert_near(bbox.z.min, -1.);
+        assert_near(bbox.z.max, 0.);
+
+        // An eighth turn widens the box to the diagonal.
+        let object = Transform::rotate(unit_box(), Vec3::from_y(1.), 45.);
+        assert_near(object.bounding_box().x.size(), 2_f64.sqrt());
+    }
+
+    #[test]
+    fn test_scale_normal() {
+        // Squashing a sphere into an ellipsoid tilts the normals towards the squashed axis.
+        let material = Arc::new(Lambertian::from_color(Vec3::one()));
+        let sphere = Arc::new(Sphere::new_stationary_sphere(Point3::zero(), 1., material));
+        let object = Transform::scale(sphere, Vec3::new(2., 1., 1.));
+        assert_near(object.bounding_box().x.max, 2.);
+
+        let ray = Ray::new(Point3::new(5., 0., 0.), Vec3::from_x(-1.), 0.);
+        let record = object.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut Rng::new(0)).unwrap();
+        assert_f64_eq!(record.t, 3.);
+        assert_eq!(record.normal, Vec3::from_x(1.));
+
+        let ray = Ray::new(Point3::new(5., 0.5, 0.), Vec3::from_x(-1.), 0.);
+        let record = object.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut Rng::new(0)).unwrap();
+        assert_near(record.point.x, 3_f64.sqrt());
+        // The ellipsoid normal at (x, y, 0) is proportional to (x / 4, y, 0).
+        let expected = Vec3::new(3_f64.sqrt() / 4., 0.5, 0.).normalize();
+        assert_near(record.normal.x, expected.x);
+        assert_near(record.normal.y, expected.y);
+    }
+}
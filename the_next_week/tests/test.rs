@@ -1,5 +1,27 @@
-use the_next_week::aabb::AABB;
-use utils::interval::Interval;
+use std::sync::Arc;
+
+use the_next_week::{
+    aabb::AABB,
+    aov::{Aov, AovBuffers},
+    background::Background,
+    bvh::{BVHLayout, BVHOptions, LinearBVH, SplitMethod},
+    camera::Camera,
+    denoise::Denoiser,
+    color::{to_bytes, ColorSpace},
+    environment::EnvironmentMap,
+    frame_buffer::{self, ExrPrecision, FrameBuffer, OutputFormat},
+    hittable_list::HittableList,
+    material::{Conductor, ConductorPreset, Dielectric, DiffuseLight, Lambertian, Material, Principled, RoughDielectric},
+    quad::Quad,
+    sampler::{AdaptiveSampling, SamplerKind},
+    scenes::{self, scene_file::{self, SceneError}, Scene},
+    sphere::Sphere,
+    texture::{CheckerTexture, ImageTexture, SolidColor, Texture, TextureFilter, UvTransform, WrapMode},
+    tone_map::{ToneMapOperator, ToneMapping},
+    transform::Transform
+};
+use utils::{interval::Interval, rng::Rng};
+use vector3::{Point3, Vec3};
 
 #[test]
 fn test_aabb_longest_axis() {
@@ -19,3 +41,806 @@ fn test_aabb_longest_axis() {
 
     assert_eq!(aabb.longest_axis(), 1);
 }
+
+fn small_scene() -> (HittableList, Camera) {
+    let mut world = HittableList::default();
+    let checker = Arc::new(CheckerTexture::from_color(0.32, Vec3::new(0.2, 0.3, 0.1), Vec3::isotropic(0.9)));
+    world.add(Arc::new(Sphere::new_stationary_sphere(
+        Point3::from_y(-1000.),
+        1000.,
+        Arc::new(Lambertian::new(checker))
+    )));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_y(1.), 1., Arc::new(Dielectric::new(1.5)))));
+
+    let mut camera = Camera::new(
+        16./9.,
+        40,
+        4,
+        10,
+        20.,
+        Point3::new(13., 2., 3.),
+        Point3::zero(),
+        Vec3::from_y(1.),
+        0.6,
+        10.
+    );
+    camera.tile_size = 7;
+
+    (world, camera)
+}
+
+fn furnace(material: Arc<dyn Material>, background: Background, samples: i32) -> FrameBuffer {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::zero(), 1., material)));
+    let mut camera = Camera::new(1., 8, samples, 50, 30., Point3::from_z(4.), Point3::zero(), Vec3::from_y(1.), 0., 4.);
+    camera.background = background;
+    camera.render(&world, &HittableList::default())
+}
+
+fn center_mean(image: &FrameBuffer) -> f64 {
+    [(3, 3), (4, 4), (3, 4), (4, 3)].iter().map(|&(i, j)| image.pixel(i, j).y).sum::<f64>() / 4.
+}
+
+fn mse(image: &FrameBuffer, reference: &FrameBuffer) -> f64 {
+    image.pixels().iter().zip(reference.pixels()).map(|(a, b)| (*a - *b).squared()).sum::<f64>() / image.pixels().len() as f64
+}
+
+fn parse(text: &str) -> Result<Scene, SceneError> {
+    scene_file::parse(text, std::path::Path::new("."), &mut Rng::new(0))
+}
+
+#[test]
+fn test_render_independent_of_threads() {
+    let (world, mut camera) = small_scene();
+
+    camera.threads = 1;
+    let serial = camera.render(&world, &HittableList::default());
+    camera.threads = 4;
+    let parallel = camera.render(&world, &HittableList::default());
+
+    assert_eq!((serial.width(), serial.height()), (40, 22));
+    assert!(serial.pixels().iter().zip(parallel.pixels()).all(|(a, b)| a.x == b.x && a.y == b.y && a.z == b.z));
+}
+
+#[test]
+fn test_seeded_render() {
+    // Noise textures, volumes and motion blur all draw from the seeded streams.
+    let render = |seed: u64, threads: usize| {
+        let mut rng = Rng::new(seed);
+        let mut scene = scenes::by_name("simple_light", &mut rng).unwrap();
+        scene.camera.image_width = 24;
+        scene.camera.samples_per_pixel = 3;
+        scene.camera.seed = seed;
+        scene.camera.threads = threads;
+        scene.camera.render(&scene.world, &scene.lights)
+    };
+    let smoke = |seed: u64, threads: usize| {
+        let mut scene = scenes::by_name("cornell_smoke", &mut Rng::new(seed)).unwrap();
+        scene.camera.image_width = 16;
+        scene.camera.samples_per_pixel = 3;
+        scene.camera.seed = seed;
+        scene.camera.threads = threads;
+        scene.camera.render(&scene.world, &scene.lights)
+    };
+    let same = |a: &FrameBuffer, b: &FrameBuffer| a.pixels().iter().zip(b.pixels()).all(|(a, b)| a == b);
+
+    assert!(same(&render(7, 1), &render(7, 3)));
+    assert!(same(&smoke(7, 1), &smoke(7, 4)));
+    assert!(!same(&render(7, 2), &render(8, 2)));
+}
+
+#[test]
+fn test_samplers() {
+    // An emissive checkerboard, so that all the noise comes from the pixel footprint.
+    let mut world = HittableList::default();
+    let checker = Arc::new(CheckerTexture::from_color(0.13, Vec3::one(), Vec3::zero()));
+    world.add(Arc::new(Quad::new(
+        Point3::new(-2., -2., 0.),
+        Vec3::from_x(4.),
+        Vec3::from_y(4.),
+        Arc::new(DiffuseLight::new(checker))
+    )));
+    let render = |sampler: SamplerKind, samples: i32, threads: usize| {
+        let mut camera = Camera::new(1., 24, samples, 2, 90., Point3::from_z(1.), Point3::zero(), Vec3::from_y(1.), 0., 1.);
+        camera.sampler = sampler;
+        camera.threads = threads;
+        camera.render(&world, &HittableList::default())
+    };
+    let reference = render(SamplerKind::Sobol, 1024, 0);
+
+    let independent = mse(&render(SamplerKind::Independent, 16, 0), &reference);
+    for sampler in [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+        let serial = render(sampler, 16, 1);
+        assert!(serial.pixels().iter().zip(render(sampler, 16, 3).pixels()).all(|(a, b)| a == b));
+        if sampler != SamplerKind::Independent {
+            let error = mse(&serial, &reference);
+            assert!(error < 0.7 * independent, "{:?}: {} >= {}", sampler, error, independent);
+        }
+    }
+}
+
+#[test]
+fn test_sah_bvh() {
+    let build = |options: BVHOptions| {
+        let mut scene = scenes::by_name("bouncing_spheres", &mut Rng::new(1)).unwrap();
+        let count = scene.world.objects.len();
+        scene.bvh = Some(options);
+        let stats = scene.build_bvh().unwrap();
+
+        scene.camera.image_width = 48;
+        scene.camera.samples_per_pixel = 2;
+        (count, stats, scene.camera.render(&scene.world, &scene.lights))
+    };
+
+    let tree = BVHOptions { layout: BVHLayout::Tree, ..Default::default() };
+    let (count, median, median_image) = build(tree);
+    assert_eq!(median.leaf_count, count);
+    assert_eq!(median.node_count, 2 * count - 1);
+
+    let sah_options = BVHOptions { split: SplitMethod::Sah, max_leaf_size: 4, ..tree };
+    let (_, sah, sah_image) = build(sah_options);
+    assert!(sah.sah_cost < median.sah_cost, "{} >= {}", sah.sah_cost, median.sah_cost);
+    assert!(sah.leaf_count * 4 >= count);
+
+    // The tree layout must not change what the rays hit.
+    assert!(median_image.pixels().iter().zip(sah_image.pixels()).all(|(a, b)| a == b));
+}
+
+#[test]
+fn test_linear_bvh() {
+    let render = |options: BVHOptions| {
+        let mut scene = scenes::by_name("bouncing_spheres", &mut Rng::new(2)).unwrap();
+        scene.bvh = Some(options);
+        scene.build_bvh();
+
+        scene.camera.image_width = 48;
+        scene.camera.samples_per_pixel = 2;
+        scene.camera.render(&scene.world, &scene.lights)
+    };
+
+    // Flattening the tree gives bit-identical images for either split method.
+    for split in [SplitMethod::Median, SplitMethod::Sah] {
+        let options = BVHOptions { split, max_leaf_size: 2, ..Default::default() };
+        let tree = render(BVHOptions { layout: BVHLayout::Tree, ..options });
+        let linear = render(BVHOptions { layout: BVHLayout::Linear, ..options });
+        assert!(tree.pixels().iter().zip(linear.pixels()).all(|(a, b)| a.x == b.x && a.y == b.y && a.z == b.z));
+    }
+
+    let mut list = HittableList::default();
+    list.add(Arc::new(Sphere::new_stationary_sphere(Point3::zero(), 1., Arc::new(Dielectric::new(1.5)))));
+    list.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_x(3.), 1., Arc::new(Dielectric::new(1.5)))));
+    list.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_x(6.), 1., Arc::new(Dielectric::new(1.5)))));
+    let bvh = LinearBVH::from_hittable_list(&mut list, &BVHOptions::default());
+    assert_eq!(bvh.node_count(), 5);
+}
+
+#[test]
+fn test_emission_and_background() {
+    let mut world = HittableList::default();
+    world.add(Arc::new(Quad::new(
+        Point3::new(-1., -1., 0.),
+        Vec3::from_x(2.),
+        Vec3::from_y(2.),
+        Arc::new(DiffuseLight::from_color(Vec3::new(4., 2., 1.)))
+    )));
+
+    let mut camera = Camera::new(1., 9, 4, 10, 90., Point3::from_z(2.), Point3::zero(), Vec3::from_y(1.), 0., 2.);
+    camera.background = Background::Solid(Vec3::new(0., 0., 0.1));
+    let image = camera.render(&world, &HittableList::default());
+
+    assert_eq!(image.pixel(4, 4), Vec3::new(4., 2., 1.));
+    assert_eq!(image.pixel(0, 0), Vec3::new(0., 0., 0.1));
+}
+
+#[test]
+fn test_emission_controls_and_alpha() {
+    // An RGBA image whose left half is transparent.
+    let path = std::env::temp_dir().join("the_next_week_alpha.png");
+    image::RgbaImage::from_fn(2, 1, |x, _| image::Rgba([255, 128, 0, if x == 0 { 0 } else { 255 }])).save(&path).unwrap();
+    let texture = Arc::new(ImageTexture::new(&path.to_string_lossy()).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(texture.alpha(0.25, 0.5, &Point3::zero()), 0.);
+    assert_eq!(texture.alpha(0.75, 0.5, &Point3::zero()), 1.);
+    assert_eq!(texture.value(0.75, 0.5, &Point3::zero()), Vec3::new(1., ColorSpace::Srgb.decode(128. / 255.), 0.));
+
+    let render = |world: &HittableList| {
+        let mut camera = Camera::new(1., 8, 4, 10, 90., Point3::from_z(2.), Point3::zero(), Vec3::from_y(1.), 0., 2.);
+        camera.background = Background::Solid(Vec3::zero());
+        camera.render(world, &HittableList::default())
+    };
+
+    // A sphere whose near half is cut out shows the inside of its far half, which only glows when
+    // the light is two-sided. The strength scales the emitted color.
+    struct NearHalfTransparent;
+    impl Texture for NearHalfTransparent {
+        fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Vec3 {
+            Vec3::one()
+        }
+
+        fn alpha(&self, _u: f64, _v: f64, p: &Point3) -> f64 {
+            if p.z > 0. { 0. } else { 1. }
+        }
+    }
+    let light = |two_sided| DiffuseLight::from_color(Vec3::new(1., 0.5, 0.25))
+        .with_strength(Arc::new(SolidColor::new(Vec3::isotropic(2.))))
+        .with_two_sided(two_sided)
+        .with_alpha(Arc::new(NearHalfTransparent));
+    for two_sided in [false, true] {
+        let mut world = HittableList::default();
+        world.add(Arc::new(Sphere::new_stationary_sphere(Point3::zero(), 1., Arc::new(light(two_sided)))));
+        let color = render(&world).pixel(4, 4);
+        assert_eq!(color, if two_sided { Vec3::new(2., 1., 0.5) } else { Vec3::zero() });
+    }
+}
+
+#[test]
+fn test_image_texture_filtering() {
+    // A 4x2 linear grayscale ramp 0, 1/3, 2/3, 1 whose bottom row is black.
+    let path = std::env::temp_dir().join("the_next_week_filtering.png");
+    image::GrayImage::from_fn(4, 2, |x, y| image::Luma([if y == 0 { x as u8 * 85 } else { 0 }])).save(&path).unwrap();
+    let texture = |filter| ImageTexture::with_color_space(&path.to_string_lossy(), ColorSpace::Linear).unwrap().with_filter(filter);
+    let nearest = texture(TextureFilter::Nearest);
+    let bilinear = texture(TextureFilter::Bilinear);
+    let bicubic = texture(TextureFilter::Bicubic);
+    let repeat = texture(TextureFilter::Nearest).with_wrap(WrapMode::Repeat);
+    let mirror = texture(TextureFilter::Nearest).with_wrap(WrapMode::Mirror);
+    let transformed = texture(TextureFilter::Nearest)
+        .with_wrap(WrapMode::Repeat)
+        .with_transform(UvTransform { scale: (2., 1.), offset: (0.25, 0.), rotation: 0. });
+    let blurred = texture(TextureFilter::Bilinear).with_lod_bias(2.);
+    std::fs::remove_file(&path).unwrap();
+    let p = Point3::zero();
+    let gray = |color: Vec3| color.x;
+
+    // Texel centers are exact, and bilinear filtering interpolates between them.
+    assert_eq!(gray(nearest.value(0.4, 0.75, &p)), 85. / 255.);
+    assert_eq!(gray(bilinear.value(0.375, 0.75, &p)), 85. / 255.);
+    assert!((gray(bilinear.value(0.5, 0.75, &p)) - 127.5 / 255.).abs() < 1e-12);
+    assert!((gray(bicubic.value(0.5, 0.75, &p)) - 127.5 / 255.).abs() < 1e-12);
+    assert!((gray(bicubic.value(0.375, 0.75, &p)) - 85. / 255.).abs() < 1e-12);
+
+    // Outside [0, 1], clamping repeats the edge, repeating tiles and mirroring flips.
+    assert_eq!(gray(nearest.value(1.1, 0.75, &p)), 1.);
+    assert_eq!(gray(repeat.value(1.1, 0.75, &p)), 0.);
+    assert_eq!(gray(mirror.value(1.1, 0.75, &p)), 1.);
+    assert_eq!(gray(mirror.value(-0.1, 0.75, &p)), 0.);
+    assert_eq!(gray(transformed.value(0.2, 0.75, &p)), 170. / 255.);
+
+    // Wide footprints and positive biases read the coarser levels, whose single texel averages the image.
+    assert!((gray(bilinear.filtered_value(0.1, 0.75, &p, 100.)) - 0.25).abs() < 1e-12);
+    assert!((gray(blurred.value(0.1, 0.75, &p)) - 0.25).abs() < 1e-12);
+    let footprint = bilinear.filtered_value(0.1, 0.75, &p, 0.25 * 2f64.sqrt());
+    assert!(gray(footprint) > gray(bilinear.value(0.1, 0.75, &p)) && gray(footprint) < 0.25);
+
+    // The built-in earth scene finds its texture from the crate directory the tests run in, too.
+    assert!(scenes::by_name("earth", &mut Rng::new(0)).is_some());
+}
+
+#[test]
+fn test_light_sampling() {
+    // A small light above a diffuse floor, seen from above the light.
+    let mut world = HittableList::default();
+    world.add(Arc::new(Quad::new(
+        Point3::new(-5., 0., -5.),
+        Vec3::from_z(10.),
+        Vec3::from_x(10.),
+        Arc::new(Lambertian::from_color(Vec3::isotropic(0.5)))
+    )));
+    let light = Arc::new(Quad::new(
+        Point3::new(-0.25, 1., -0.25),
+        Vec3::from_x(0.5),
+        Vec3::from_z(0.5),
+        Arc::new(DiffuseLight::from_color(Vec3::isotropic(20.)))
+    ));
+    world.add(light.clone());
+    let lights = HittableList::from_hittable(light);
+
+    let render = |lights: &HittableList, samples: i32| {
+        let mut camera = Camera::new(1., 12, samples, 4, 60., Point3::new(0., 3., 2.), Point3::zero(), Vec3::from_y(1.), 0., 1.);
+        camera.background = Background::Solid(Vec3::zero());
+        camera.render(&world, lights)
+    };
+    let mean = |image: &FrameBuffer| image.pixels().iter().map(|p| p.x).sum::<f64>() / image.pixels().len() as f64;
+
+    let reference = render(&lights, 1024);
+    let unsampled = render(&HittableList::default(), 1024);
+    assert!((mean(&unsampled) / mean(&reference) - 1.).abs() < 0.05, "{} != {}", mean(&unsampled), mean(&reference));
+
+    let sampled_error = mse(&render(&lights, 16), &reference);
+    let unsampled_error = mse(&render(&HittableList::default(), 16), &reference);
+    assert!(sampled_error < 0.2 * unsampled_error, "{} >= {}", sampled_error, unsampled_error);
+}
+
+#[test]
+fn test_environment_furnace() {
+    // A diffuse sphere under a uniform white environment reflects exactly its albedo, whatever
+    // the environment map's own sampling density is.
+    let map = EnvironmentMap::new(8, 4, vec![Vec3::one(); 32]).with_rotation(20.).with_intensity(2.);
+    let image = furnace(
+        Arc::new(Lambertian::from_color(Vec3::isotropic(0.5))),
+        Background::Environment(Arc::new(map)),
+        1024
+    );
+
+    assert_eq!(image.pixel(0, 0), Vec3::isotropic(2.));
+    for (i, j) in [(3, 3), (4, 4), (3, 4), (4, 3)] {
+        let pixel = image.pixel(i, j);
+        assert!((pixel.x - 1.).abs() < 0.05 && pixel.x == pixel.y, "{:?}", pixel);
+    }
+}
+
+#[test]
+fn test_microfacet_furnace() {
+    // Under a uniform white background, glass and a conductor that reflects everything only lose
+    // the energy that single scattering between the microfacets misses.
+    let render = |material: Arc<dyn Material>| furnace(material, Background::Solid(Vec3::one()), 512);
+
+    for roughness in [0., 0.3] {
+        let glass = center_mean(&render(Arc::new(RoughDielectric::new(1.5, roughness, 0.5))));
+        assert!((glass - 1.).abs() < 0.03, "{}", glass);
+    }
+    let mirror = center_mean(&render(Arc::new(Conductor::new(Vec3::zero(), Vec3::isotropic(1e3), 0.5, 0.))));
+    assert!(mirror < 1. && mirror > 0.88, "{}", mirror);
+    let gold = render(Arc::new(Conductor::from_preset(ConductorPreset::Gold, 0.2, 0.)));
+    let gold = gold.pixel(4, 4);
+    assert!(gold.x > gold.y && gold.y > gold.z, "{:?}", gold);
+}
+
+#[test]
+fn test_principled_furnace() {
+    let render = |material: Principled| furnace(Arc::new(material), Background::Solid(Vec3::one()), 512);
+    let white = || Principled { base_color: Principled::constant(1.), ..Default::default() };
+
+    // White materials lose a little energy to the layering approximations but never gain any.
+    let plastic = Principled { clearcoat: Principled::constant(1.), sheen: Principled::constant(1.), ..white() };
+    let metal = Principled { metallic: Principled::constant(1.), roughness: Principled::constant(0.3), ..white() };
+    let glass = Principled { transmission: Principled::constant(1.), roughness: Principled::constant(0.2), ..white() };
+    for (name, material) in [("plastic", plastic), ("metal", metal), ("glass", glass)] {
+        let value = center_mean(&render(material));
+        assert!(value < 1.02 && value > 0.85, "{}: {}", name, value);
+    }
+
+    // Emission is added on top of the reflected light.
+    let glowing = Principled { base_color: Principled::constant(0.), emission: Principled::constant(2.), ..Default::default() };
+    let value = center_mean(&render(glowing));
+    assert!((value - 2.).abs() < 0.1, "{}", value);
+}
+
+#[test]
+fn test_frame_buffer_output() {
+    assert_eq!(OutputFormat::from_path("out/image.PNG"), Some(OutputFormat::Png));
+    assert_eq!(OutputFormat::from_path("image.ppm"), Some(OutputFormat::Ppm));
+    assert_eq!(OutputFormat::from_path("image.pnm"), Some(OutputFormat::Ppm));
+    assert_eq!(OutputFormat::from_path("image"), None);
+
+    let mut image = FrameBuffer::new(2, 1);
+    image.set_pixel(1, 0, Vec3::new(1., 0.25, 0.));
+
+    let mut ascii = Vec::new();
+    image.write_ppm_ascii(&mut ascii).unwrap();
+    assert_eq!(String::from_utf8(ascii).unwrap(), "P3\n2 1\n255\n0 0 0\n255 137 0\n");
+
+    let mut binary = Vec::new();
+    image.write_ppm(&mut binary).unwrap();
+    assert_eq!(binary, b"P6\n2 1\n255\n\x00\x00\x00\xff\x89\x00");
+
+    let path = std::env::temp_dir().join("the_next_week_frame_buffer.png");
+    image.save(&path).unwrap();
+    let png = image::open(&path).unwrap().to_rgb8();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(png.get_pixel(1, 0).0, [255, 137, 0]);
+}
+
+#[test]
+fn test_aovs() {
+    // Two spheres that share a material side by side, centered on pixels (14, 9) and (25, 9), and an
+    // instance of the left one behind them around pixel (19, 6).
+    let red = Arc::new(Lambertian::from_color(Vec3::new(0.8, 0.1, 0.1)));
+    let center = Point3::new(1.65, 0.15, 0.);
+    let sphere = Arc::new(Sphere::new_stationary_sphere(Point3::new(-1.65, 0.15, 0.), 1., red.clone()));
+    let mut world = HittableList::default();
+    world.add(sphere.clone());
+    world.add(Arc::new(Sphere::new_stationary_sphere(center, 1., red)));
+    world.add(Arc::new(Transform::translate(sphere, Vec3::new(1.65, 2.5, -5.))));
+    let mut camera = Camera::new(2., 40, 4, 10, 90., Point3::from_z(3.), Point3::zero(), Vec3::from_y(1.), 0., 3.);
+    camera.sampler = SamplerKind::Stratified;
+    camera.background = Background::Solid(Vec3::isotropic(0.5));
+
+    let (image, aovs) = camera.render_with_aovs(&world, &HittableList::default(), &Aov::ALL);
+    assert_eq!(image.pixels(), camera.render(&world, &HittableList::default()).pixels());
+    let aov = |aov, x, y| aovs.get(aov).unwrap().pixel(x, y);
+
+    // The pixel over the right sphere's center sees the point that faces the camera.
+    let (x, y) = (25, 9);
+    let to_camera = Point3::from_z(3.) - center;
+    let normal = aov(Aov::Normal, x, y);
+    assert!((normal - to_camera.normalize()).norm() < 0.05, "{}", normal);
+    assert!((aov(Aov::Depth, x, y).x - (to_camera.norm() - 1.)).abs() < 0.05);
+    assert!((aov(Aov::Position, x, y) - (center + to_camera.normalize())).norm() < 0.05);
+    assert_eq!(aov(Aov::Albedo, x, y), Vec3::new(0.8, 0.1, 0.1));
+    assert!(aov(Aov::Uv, x, y).z == 0. && aov(Aov::Uv, x, y).y > 0.4 && aov(Aov::Uv, x, y).y < 0.6);
+    assert!(aov(Aov::Time, x, y).x > 0. && aov(Aov::Time, x, y).x < 1.);
+
+    // Objects are numbered in the raster order of the pixels where they first appear: the instance
+    // above first, then the two spheres, whose top edges share a row. The background is zero everywhere.
+    let (left, right, instance, background) = ((14, 9), (25, 9), (19, 6), (0, 19));
+    let id = |kind, (x, y)| aov(kind, x, y).x;
+    let mut first_seen = Vec::new();
+    for pixel in aovs.get(Aov::ObjectId).unwrap().pixels() {
+        if pixel.x != 0. && !first_seen.contains(&pixel.x) {
+            first_seen.push(pixel.x);
+        }
+    }
+    assert_eq!(first_seen, [1., 2., 3.]);
+    assert_eq!([instance, background].map(|pixel| id(Aov::ObjectId, pixel)), [1., 0.]);
+    assert_eq!(id(Aov::ObjectId, left) + id(Aov::ObjectId, right), 5.);
+    assert_eq!([left, right, instance, background].map(|pixel| id(Aov::MaterialId, pixel)), [1., 1., 1., 0.]);
+    assert_eq!(aov(Aov::Normal, 0, 19), Vec3::zero());
+    assert_eq!(aov(Aov::Depth, 0, 19), Vec3::zero());
+
+    // The numbering doesn't depend on the threads.
+    camera.threads = 3;
+    let (_, threaded) = camera.render_with_aovs(&world, &HittableList::default(), &[Aov::ObjectId]);
+    assert_eq!(threaded.get(Aov::ObjectId).unwrap().pixels(), aovs.get(Aov::ObjectId).unwrap().pixels());
+    assert!(threaded.get(Aov::Normal).is_none());
+
+    // EXR files hold every pass as a layer, other formats get an image per pass.
+    let directory = std::env::temp_dir();
+    let path = directory.join("the_next_week_aovs.exr");
+    aovs.save(&image, &path, OutputFormat::Exr(ExrPrecision::Float)).unwrap();
+    let exr = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let names: Vec<String> = exr.layer_data[0].channel_data.list.iter().map(|channel| channel.name.to_string()).collect();
+    assert_eq!(names.len(), 33);
+    assert!(names.contains(&"R".to_string()) && names.contains(&"object_id.R".to_string()));
+
+    let path = directory.join("the_next_week_aovs.png");
+    assert_eq!(AovBuffers::aov_path(&path, Aov::Normal), directory.join("the_next_week_aovs.normal.png"));
+    aovs.save(&image, &path, OutputFormat::Png).unwrap();
+    for aov in Aov::ALL {
+        let aov_path = AovBuffers::aov_path(&path, aov);
+        let png = image::open(&aov_path).unwrap().to_rgb8();
+        std::fs::remove_file(&aov_path).unwrap();
+        assert_eq!(png.dimensions(), (40, 20));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_denoise() {
+    // A checkered floor lit by a small light, with a sphere casting a soft shadow on it.
+    let mut world = HittableList::default();
+    let checker = Arc::new(CheckerTexture::from_color(1., Vec3::new(0.8, 0.2, 0.1), Vec3::isotropic(0.9)));
+    world.add(Arc::new(Quad::new(
+        Point3::new(-10., 0., -10.),
+        Vec3::from_z(20.),
+        Vec3::from_x(20.),
+        Arc::new(Lambertian::new(checker))
+    )));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::new(0., 1., 0.), 1., Arc::new(Lambertian::from_color(Vec3::isotropic(0.5))))));
+    let light = Arc::new(Quad::new(
+        Point3::new(-1., 4., -1.),
+        Vec3::from_x(2.),
+        Vec3::from_z(2.),
+        Arc::new(DiffuseLight::from_color(Vec3::isotropic(4.)))
+    ));
+    world.add(light.clone());
+    let mut lights = HittableList::default();
+    lights.add(light);
+    let render = |samples: i32| {
+        let mut camera = Camera::new(2., 96, samples, 4, 60., Point3::new(0., 3., 5.), Point3::zero(), Vec3::from_y(1.), 0., 5.);
+        camera.background = Background::Solid(Vec3::zero());
+        camera.render_with_aovs(&world, &lights, &Denoiser::GUIDES)
+    };
+
+    let (reference, _) = render(256);
+    let (noisy, aovs) = render(16);
+    let error = |image: &FrameBuffer| mse(image, &reference);
+    let denoised = Denoiser::default().denoise(&noisy, &aovs);
+    assert!(denoised.pixels().iter().all(|color| color.x.is_finite() && color.y.is_finite() && color.z.is_finite()));
+    assert!(error(&denoised) < 0.5 * error(&noisy), "{} {}", error(&denoised), error(&noisy));
+
+    // Blurring the same image without the guides loses the checker edges.
+    let mut unguided = AovBuffers::default();
+    unguided.insert(Aov::Variance, aovs.get(Aov::Variance).unwrap().clone());
+    let unguided = error(&Denoiser::default().denoise(&noisy, &unguided));
+    assert!(unguided > 1.5 * error(&denoised), "{} {}", unguided, error(&denoised));
+}
+
+#[test]
+fn test_adaptive_sampling() {
+    // A diffuse sphere on the ground under a uniform sky: the sky converges at once, the shading
+    // near the contact is noisy.
+    let mut world = HittableList::default();
+    let gray = Arc::new(Lambertian::from_color(Vec3::isotropic(0.5)));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::zero(), 1., gray.clone())));
+    world.add(Arc::new(Sphere::new_stationary_sphere(Point3::from_y(-101.), 100., gray)));
+    let mut camera = Camera::new(2., 32, 64, 4, 90., Point3::from_z(2.), Point3::zero(), Vec3::from_y(1.), 0., 2.);
+    camera.background = Background::Solid(Vec3::one());
+
+    // Without adaptive sampling, every pixel takes the same number of samples.
+    let (_, aovs) = camera.render_with_aovs(&world, &HittableList::default(), &[Aov::SampleCount]);
+    assert!(aovs.get(Aov::SampleCount).unwrap().pixels().iter().all(|count| count.x == 64.));
+
+    camera.adaptive_sampling = Some(AdaptiveSampling::new(8, 256, 0.05));
+    let (image, aovs) = camera.render_with_aovs(&world, &HittableList::default(), &[Aov::SampleCount]);
+    let counts = aovs.get(Aov::SampleCount).unwrap();
+    let count = |x, y| counts.pixel(x, y).x;
+    assert_eq!(count(0, 0), 8.);
+    assert_eq!(image.pixel(0, 0), Vec3::one());
+    // The samples saved on the sky go to the shadow below the sphere, within the budget of 64 samples
+    // per pixel on average.
+    let budget = 64. * counts.pixels().len() as f64;
+    let total = |counts: &FrameBuffer| counts.pixels().iter().map(|count| count.x).sum::<f64>();
+    assert!(count(16, 14) > 64.);
+    assert!(total(counts) < budget);
+    assert!(counts.pixels().iter().all(|count| count.x >= 8. && count.x <= 256. && count.x % 8. == 0.));
+    assert!(counts.pixels().iter().any(|count| count.x == 256.));
+
+    // When few pixels converge, the samples stop at the budget instead of at the maximum.
+    camera.adaptive_sampling = Some(AdaptiveSampling::new(8, 256, 0.01));
+    let (_, strict) = camera.render_with_aovs(&world, &HittableList::default(), &[Aov::SampleCount]);
+    let strict = strict.get(Aov::SampleCount).unwrap();
+    assert_eq!(total(strict), budget);
+    assert!(strict.pixels().iter().any(|count| count.x > 64.));
+
+    // Fewer samples per pixel than the minimum still keep to the budget.
+    camera.samples_per_pixel = 4;
+    camera.adaptive_sampling = Some(AdaptiveSampling::new(16, 64, 0.05));
+    let (_, few) = camera.render_with_aovs(&world, &HittableList::default(), &[Aov::SampleCount]);
+    assert!(total(few.get(Aov::SampleCount).unwrap()) <= 4. * counts.pixels().len() as f64);
+    camera.samples_per_pixel = 64;
+    camera.adaptive_sampling = Some(AdaptiveSampling::new(8, 256, 0.05));
+
+    // The samples spent on each pixel don't depend on the threads.
+    camera.threads = 3;
+    let (threaded, threaded_aovs) = camera.render_with_aovs(&world, &HittableList::default(), &[Aov::SampleCount]);
+    assert_eq!(threaded.pixels(), image.pixels());
+    assert_eq!(threaded_aovs.get(Aov::SampleCount).unwrap().pixels(), counts.pixels());
+
+    // The heatmap goes from blue for the fewest samples to red for the most.
+    let heatmap = Aov::SampleCount.visualize(counts);
+    assert_eq!(heatmap.pixel(0, 0), Vec3::new(0., 0., 1.));
+    assert!(heatmap.pixels().contains(&Vec3::new(1., 0., 0.)));
+
+    // Scene files turn it on, the maximum defaults to four times the samples per pixel.
+    let camera = parse("[camera]\nsamples_per_pixel = 32\nadaptive = { threshold = 0.1 }").unwrap().camera;
+    assert_eq!(camera.adaptive_sampling, Some(AdaptiveSampling::new(16, 128, 0.1)));
+    let camera = parse("[camera]\nadaptive = { min_samples = 4, max_samples = 64 }").unwrap().camera;
+    assert_eq!(camera.adaptive_sampling, Some(AdaptiveSampling::new(4, 64, 0.05)));
+    let camera = parse("[camera]\nsamples_per_pixel = 4\nadaptive = {}").unwrap().camera;
+    assert_eq!(camera.adaptive_sampling, Some(AdaptiveSampling::new(4, 16, 0.05)));
+    assert_eq!(parse("[camera]").unwrap().camera.adaptive_sampling, None);
+}
+
+#[test]
+fn test_tone_mapping_output() {
+    // A light at four times white stays distinguishable from white only after tone mapping.
+    let mut image = FrameBuffer::new(2, 1);
+    image.set_pixel(0, 0, Vec3::isotropic(4.));
+    image.set_pixel(1, 0, Vec3::isotropic(1.));
+    assert_eq!(image.to_rgb8().as_raw(), &[255; 6]);
+
+    let image = image.with_tone_mapping(ToneMapping::new(ToneMapOperator::Reinhard));
+    assert_eq!(image.to_rgb8().get_pixel(1, 0).0, [to_bytes(Vec3::isotropic(0.5))[0]; 3]);
+    assert!(image.to_rgb8().get_pixel(0, 0).0[0] < 255);
+    let mut ascii = Vec::new();
+    image.write_ppm_ascii(&mut ascii).unwrap();
+    assert!(String::from_utf8(ascii).unwrap().ends_with("188 188 188\n"));
+
+    // The floating-point formats keep the linear values.
+    let mut pfm = Vec::new();
+    image.write_pfm(&mut pfm).unwrap();
+    assert_eq!(f32::from_le_bytes(pfm[pfm.len() - 24..pfm.len() - 20].try_into().unwrap()), 4.);
+
+    // The camera passes its setting on to the image, and scene files choose it.
+    let text = r#"
+        [camera]
+        image_width = 4
+        samples_per_pixel = 1
+        exposure = -1.5
+        tone_map = "reinhard_extended"
+        white_point = 8.0
+    "#;
+    let mut scene = parse(text).unwrap();
+    let expected = ToneMapping::new(ToneMapOperator::ReinhardExtended { white: 8. }).with_exposure(-1.5);
+    assert_eq!(scene.camera.tone_mapping, expected);
+    assert_eq!(scene.camera.render(&scene.world, &scene.lights).tone_mapping(), expected);
+    let agx = parse("[camera]\ntone_map = \"agx\"").unwrap();
+    assert_eq!(agx.camera.tone_mapping.operator, ToneMapOperator::AgX);
+}
+
+#[test]
+fn test_hdr_output() {
+    assert_eq!(OutputFormat::from_path("image.EXR"), Some(OutputFormat::Exr(ExrPrecision::Half)));
+    assert_eq!(OutputFormat::from_path("image.hdr"), Some(OutputFormat::Hdr));
+    assert_eq!(OutputFormat::from_path("image.pfm"), Some(OutputFormat::Pfm));
+
+    // Radiance above one survives, unlike in the 8-bit formats.
+    let mut image = FrameBuffer::new(2, 2);
+    image.set_pixel(0, 0, Vec3::new(16., 0.5, 0.25));
+    image.set_pixel(1, 1, Vec3::new(0., 1000., 0.125));
+    let mut albedo = FrameBuffer::new(2, 2);
+    albedo.set_pixel(1, 0, Vec3::new(0.2, 0.4, 0.6));
+
+    let directory = std::env::temp_dir();
+    for (precision, tolerance) in [(ExrPrecision::Half, 1e-3), (ExrPrecision::Float, 1e-7)] {
+        let path = directory.join("the_next_week_output.exr");
+        frame_buffer::write_exr(&path, &[("", &image), ("albedo", &albedo)], precision).unwrap();
+        let exr = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let channels = &exr.layer_data[0].channel_data.list;
+        let names: Vec<String> = channels.iter().map(|channel| channel.name.to_string()).collect();
+        assert_eq!(names, ["B", "G", "R", "albedo.B", "albedo.G", "albedo.R"]);
+        let sample = |channel: usize, index: usize| channels[channel].sample_data.value_by_flat_index(index).to_f32();
+        let close = |value: f32, expected: f32| (value / expected - 1.).abs() < tolerance;
+        assert!(close(sample(2, 0), 16.) && close(sample(1, 3), 1000.) && close(sample(0, 3), 0.125));
+        assert!(close(sample(4, 1), 0.4) && sample(4, 0) == 0.);
+    }
+
+    let mismatched = FrameBuffer::new(1, 1);
+    let path = directory.join("the_next_week_mismatched.exr");
+    assert!(frame_buffer::write_exr(&path, &[("", &image), ("albedo", &mismatched)], ExrPrecision::Half).is_err());
+
+    let path = directory.join("the_next_week_output.hdr");
+    image.save(&path).unwrap();
+    let hdr = image::open(&path).unwrap().to_rgb32f();
+    std::fs::remove_file(&path).unwrap();
+    // RGBE shares an exponent, so the small channels of a bright pixel lose precision.
+    assert_eq!(hdr.get_pixel(0, 0).0[0], 16.);
+    assert_eq!(hdr.get_pixel(1, 1).0[1], 1000.);
+
+    // PFM stores the rows from bottom to top.
+    let mut pfm = Vec::new();
+    image.write_pfm(&mut pfm).unwrap();
+    let header = b"PF\n2 2\n-1.0\n";
+    assert_eq!(&pfm[..header.len()], header);
+    let floats: Vec<f32> = pfm[header.len()..].chunks(4).map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())).collect();
+    assert_eq!(floats.len(), 12);
+    assert_eq!(floats[3..6], [0., 1000., 0.125]);
+    assert_eq!(floats[6..9], [16., 0.5, 0.25]);
+}
+
+#[test]
+fn test_color_space() {
+    // The sRGB transfer functions are inverses and match known values.
+    for value in [0., 0.001, 0.0031308, 0.04045, 0.2, 0.5, 1.] {
+        assert!((ColorSpace::Srgb.decode(ColorSpace::Srgb.encode(value)) - value).abs() < 1e-12, "{}", value);
+    }
+    assert!((ColorSpace::Srgb.decode(0.5) - 0.214041).abs() < 1e-6);
+    assert!((ColorSpace::Srgb.encode(0.18) - 0.461356).abs() < 1e-6);
+    assert!((ColorSpace::Srgb.encode(1.) - 1.).abs() < 1e-12);
+    assert_eq!(ColorSpace::Linear.decode(0.5), 0.5);
+    assert_eq!(ColorSpace::Srgb.decode_rgba([0.5, 0., 1., 0.5])[3], 0.5);
+
+    // 8-bit images are decoded from sRGB unless tagged linear, and floating-point images are linear.
+    let path = std::env::temp_dir().join("the_next_week_color_space.png");
+    image::GrayImage::from_pixel(1, 1, image::Luma([188])).save(&path).unwrap();
+    let srgb = ImageTexture::new(&path.to_string_lossy()).unwrap();
+    let linear = ImageTexture::with_color_space(&path.to_string_lossy(), ColorSpace::Linear).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let p = Point3::zero();
+    assert!((srgb.value(0.5, 0.5, &p).x - 0.502886).abs() < 1e-6);
+    assert_eq!(linear.value(0.5, 0.5, &p).x, 188. / 255.);
+
+    let path = std::env::temp_dir().join("the_next_week_color_space.exr");
+    image::Rgb32FImage::from_pixel(1, 1, image::Rgb([0.5, 2., 4.])).save(&path).unwrap();
+    let hdr = ImageTexture::new(&path.to_string_lossy()).unwrap().with_filter(TextureFilter::Bicubic);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(hdr.value(0.5, 0.5, &p), Vec3::new(0.5, 2., 4.));
+}
+
+#[test]
+fn test_scene_file() {
+    let text = r#"
+        [camera]
+        image_width = 32
+        look_from = [0.0, 0.0, 5.0]
+        look_at = [0.0, 0.0, 0.0]
+
+        [textures.red]
+        type = "solid"
+        color = [1.0, 0.0, 0.0]
+
+        [textures.checker]
+        type = "checker"
+        scale = 0.5
+        even = "red"
+        odd = [1.0, 1.0, 1.0]
+
+        [materials.ground]
+        type = "lambertian"
+        albedo = "checker"
+
+        [[objects]]
+        type = "sphere"
+        center = [0.0, 0.0, 0.0]
+        radius = 1.0
+        material = "ground"
+
+        [[objects]]
+        type = "moving_sphere"
+        origin = [2.0, 0.0, 0.0]
+        end = [2.0, 1.0, 0.0]
+        radius = 0.5
+        material = { type = "metal", albedo = [0.8, 0.8, 0.8], fuzz = 0.1 }
+    "#;
+    let scene = parse(text).unwrap();
+    assert_eq!(scene.world.objects.len(), 2);
+    assert_eq!(scene.camera.image_width, 32);
+
+    let unknown_material = "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\nmaterial = \"gold\"";
+    assert!(matches!(
+        parse(unknown_material),
+        Err(SceneError::UnknownMaterial(name)) if name == "gold"
+    ));
+
+    let cycle = r#"
+        [textures.a]
+        type = "checker"
+        scale = 1.0
+        even = "b"
+        odd = [0.0, 0.0, 0.0]
+
+        [textures.b]
+        type = "checker"
+        scale = 1.0
+        even = "a"
+        odd = [0.0, 0.0, 0.0]
+
+        [materials.m]
+        type = "lambertian"
+        albedo = "a"
+
+        [[objects]]
+        type = "sphere"
+        center = [0.0, 0.0, 0.0]
+        radius = 1.0
+        material = "m"
+    "#;
+    assert!(matches!(parse(cycle), Err(SceneError::TextureCycle(_))));
+    assert!(matches!(parse("[camera]\nfov = 1.0"), Err(SceneError::Parse(_))));
+    for camera in ["image_width = -5", "samples_per_pixel = 0", "max_depth = -1"] {
+        let text = format!("[camera]\n{}", camera);
+        assert!(matches!(parse(&text), Err(SceneError::Parse(_))), "{}", camera);
+    }
+
+    let flat = r#"
+        [[objects]]
+        type = "transform"
+        object = { type = "sphere", center = [0.0, 0.0, 0.0], radius = 1.0, material = { type = "dielectric", refraction_index = 1.5 } }
+        scale = [1.0, 0.0, 1.0]
+    "#;
+    assert!(matches!(parse(flat), Err(SceneError::SingularTransform)));
+    let zero_axis = flat.replace("scale = [1.0, 0.0, 1.0]", "rotate = { axis = [0.0, 0.0, 0.0], angle = 30.0 }");
+    assert!(matches!(parse(&zero_axis), Err(SceneError::SingularTransform)));
+
+    let principled = "[[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+        material = { type = \"principled\", metallic = 1, roughness = \"bumps\" }";
+    assert!(matches!(
+        parse(principled),
+        Err(SceneError::UnknownTexture(name)) if name == "bumps"
+    ));
+
+    let missing = "[camera]\nbackground = { path = \"missing.hdr\", rotation = 90.0 }";
+    assert!(matches!(parse(missing), Err(SceneError::Image(..))));
+
+    let missing = "[textures.earth]\ntype = \"image\"\npath = \"missing.jpg\"\n\
+        [[objects]]\ntype = \"sphere\"\ncenter = [0.0, 0.0, 0.0]\nradius = 1.0\n\
+        material = { type = \"lambertian\", albedo = \"earth\" }";
+    assert!(matches!(
+        scene_file::parse(missing, std::path::Path::new("scenes"), &mut Rng::new(0)),
+        Err(SceneError::Image(path, _)) if path == std::path::Path::new("scenes/missing.jpg")
+    ));
+}
+
+#[test]
+fn test_example_scene_files() {
+    for entry in std::fs::read_dir("asserts/scenes").unwrap() {
+        let path = entry.unwrap().path();
+        if let Err(err) = scene_file::load(&path, &mut Rng::new(0)) {
+            panic!("{}: {}", path.display(), err);
+        }
+    }
+}
@@ -1,5 +1,3 @@
-use num_complex;
-
 use utils::base::*;
 
 const PATH: &str = "utils/examples/asserts";
@@ -1,5 +1,6 @@
 pub mod macro_def;
 pub mod rtweekend;
+pub mod rng;
 pub mod interval;
 
 pub mod base;
@@ -9,11 +9,12 @@ macro_rules! check_f64_eq {
 /// 断言两个浮点数相等
 #[macro_export]
 macro_rules! assert_f64_eq {
-    ($left:expr, $right:expr $(,)?) => {
-        if !($crate::check_f64_eq!($left, $right)) {
+    ($left:expr, $right:expr $(,)?) => {{
+        let is_eq = $crate::check_f64_eq!($left, $right);
+        if !is_eq {
             panic!("Expected `left`: {:?}, get `right`: {:?}", $left, $right);
         }
-    };
+    }};
 }
 
 /// 断言两个浮点数不相等
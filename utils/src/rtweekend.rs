@@ -1,5 +1,3 @@
-use rand::{distributions::uniform::SampleUniform, prelude::*};
-
 pub const PI: f64 = std::f64::consts::PI;
 pub type Degree = f64;
 pub type Radian = f64;
@@ -9,18 +7,15 @@ pub fn degree_to_radian(degree: Degree) -> Radian {
     degree * PI / 180.
 }
 
-/// 随机数，范围[0, 1)
-pub fn random<>() -> f64 {
-    // random number in range [0, 1)
-    random_range(0., 1.)
-}
-
-/// 随机数，范围[min, max)
-pub fn random_range<T>(min: T, max: T) -> T
-    where T: SampleUniform + PartialOrd
-{
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
+/// 将多个整数混合为一个种子（SplitMix64）
+/// 用于为每个像素派生互不相关的随机数种子
+pub fn mix_seed(seed: u64, values: &[u64]) -> u64 {
+    values.iter().fold(seed, |state, &value| {
+        let mut z = (state ^ value).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    })
 }
 
 #[cfg(test)]
@@ -34,10 +29,7 @@ mod tests {
     }
 
     #[test]
-    fn test_random() {
-        assert!(random() >= 0.);
-        assert!(random() < 1.);
-        assert!(random_range(1., 2.) >= 1.);
-        assert!(random_range(1., 2.) < 2.);
+    fn test_mix_seed() {
+        assert_ne!(mix_seed(0, &[1, 2]), mix_seed(0, &[2, 1]));
     }
 }
@@ -1,13 +1,13 @@
-use utils::rtweekend::random_range;
+use utils::{rng::Rng, rtweekend::PI};
 use crate::Vec3;
 
 pub fn cos_theta(uv: &Vec3, n: &Vec3) -> f64 {
     (-uv.dot(n)).min(1.)
 }
 
-pub fn random_unit_vector() -> Vec3 {
+pub fn random_unit_vector(rng: &mut Rng) -> Vec3 {
     loop {
-        let p = Vec3::random_range(-1., 1.);
+        let p = Vec3::random_range(-1., 1., rng);
         let sq = p.squared();
         if 1e-160 < sq && sq < 1. {
             return p / sq.sqrt();
@@ -15,11 +15,11 @@ pub fn random_unit_vector() -> Vec3 {
     }
 }
 
-pub fn random_unit_disk() -> Vec3 {
+pub fn random_unit_disk(rng: &mut Rng) -> Vec3 {
     loop {
         let p = Vec3 {
-            x: random_range(-1., 1.),
-            y: random_range(-1., 1.),
+            x: rng.random_range(-1., 1.),
+            y: rng.random_range(-1., 1.),
             z: 0.
         };
         if p.squared() < 1. {
@@ -28,8 +28,8 @@ pub fn random_unit_disk() -> Vec3 {
     }
 }
 
-pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
-    let unit = random_unit_vector();
+pub fn random_on_hemisphere(normal: &Vec3, rng: &mut Rng) -> Vec3 {
+    let unit = random_unit_vector(rng);
 
     // In the same hemisphere as the normal
     if unit.dot(normal) > 0. {
@@ -39,6 +39,41 @@ pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
     }
 }
 
+/// 把 [0, 1)² 中的样本映射为单位球面上均匀分布的方向
+pub fn sample_unit_vector((u1, u2): (f64, f64)) -> Vec3 {
+    let z = 1. - 2. * u1;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * u2;
+
+    Vec3 { x: r * phi.cos(), y: r * phi.sin(), z }
+}
+
+/// 把 [0, 1)² 中的样本映射为 +z 半球上按余弦加权分布的单位向量
+pub fn sample_cosine_direction((u1, u2): (f64, f64)) -> Vec3 {
+    let phi = 2. * PI * u1;
+    let r = u2.sqrt();
+
+    Vec3 { x: phi.cos() * r, y: phi.sin() * r, z: (1. - u2).sqrt() }
+}
+
+/// 把 [0, 1)² 中的样本映射为单位圆盘上均匀分布的点（同心映射，保持样本的分层）
+pub fn sample_unit_disk((u1, u2): (f64, f64)) -> Vec3 {
+    let a = 2. * u1 - 1.;
+    let b = 2. * u2 - 1.;
+    if a == 0. && b == 0. {
+        return Vec3::zero();
+    }
+
+    // Map concentric squares to concentric circles.
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, PI / 4. * (b / a))
+    } else {
+        (b, PI / 2. - PI / 4. * (a / b))
+    };
+
+    Vec3 { x: r * theta.cos(), y: r * theta.sin(), z: 0. }
+}
+
 pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
     *v - 2. * v.dot(n) * *n
 }
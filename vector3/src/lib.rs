@@ -2,9 +2,11 @@ use std::{
     fmt::{Debug, Display},
     ops::{Add, AddAssign, Div, Index, Mul, Neg, Sub, SubAssign}
 };
-use utils::rtweekend;
+use utils::rng::Rng;
 
 pub mod extension;
+pub mod matrix;
+pub mod onb;
 
 #[derive(Default, Clone, Copy)]
 pub struct Vec3 {
@@ -108,20 +110,16 @@ impl Vec3 {
     }
 
     /// 随机向量，范围[0, 1)
-    pub fn random() -> Self {
-        Self::new(
-            rtweekend::random(),
-            rtweekend::random(),
-            rtweekend::random()
-        )
+    pub fn random(rng: &mut Rng) -> Self {
+        Self::new(rng.random(), rng.random(), rng.random())
     }
 
-    /// 随机向量，范围[min, max]
-    pub fn random_range(min: f64, max: f64) -> Self {
+    /// 随机向量，范围[min, max)
+    pub fn random_range(min: f64, max: f64, rng: &mut Rng) -> Self {
         Self::new(
-            rtweekend::random_range(min, max),
-            rtweekend::random_range(min, max),
-            rtweekend::random_range(min, max)
+            rng.random_range(min, max),
+            rng.random_range(min, max),
+            rng.random_range(min, max)
         )
     }
 
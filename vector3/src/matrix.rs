@@ -0,0 +1,344 @@
+use std::{
+    fmt::Debug,
+    ops::{Index, Mul}
+};
+
+use crate::Vec3;
+
+/// 3x3 矩阵，按行存储
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub m: [[f64; 3]; 3]
+}
+
+impl Mat3 {
+    /// 单位矩阵
+    /// ```
+    /// use vector3::{matrix::Mat3, Vec3};
+    /// let v = Vec3::new(1., 2., 3.);
+    /// assert_eq!(Mat3::identity() * v, v);
+    /// ```
+    pub fn identity() -> Self {
+        Self::from_diagonal(Vec3::one())
+    }
+
+    /// 按行构造矩阵
+    pub fn from_rows(r0: Vec3, r1: Vec3, r2: Vec3) -> Self {
+        Self { m: [[r0.x, r0.y, r0.z], [r1.x, r1.y, r1.z], [r2.x, r2.y, r2.z]] }
+    }
+
+    /// 按列构造矩阵
+    /// ```
+    /// use vector3::{matrix::Mat3, Vec3};
+    /// let m = Mat3::from_cols(Vec3::new(1., 2., 3.), Vec3::new(4., 5., 6.), Vec3::new(7., 8., 9.));
+    /// assert_eq!(m.col(1), Vec3::new(4., 5., 6.));
+    /// assert_eq!(m.row(1), Vec3::new(2., 5., 8.));
+    /// ```
+    pub fn from_cols(c0: Vec3, c1: Vec3, c2: Vec3) -> Self {
+        Self::from_rows(c0, c1, c2).transpose()
+    }
+
+    /// 对角矩阵
+    pub fn from_diagonal(d: Vec3) -> Self {
+        Self { m: [[d.x, 0., 0.], [0., d.y, 0.], [0., 0., d.z]] }
+    }
+
+    /// 缩放矩阵，各轴缩放系数由 `factors` 给出
+    pub fn scale(factors: Vec3) -> Self {
+        Self::from_diagonal(factors)
+    }
+
+    /// 绕任意轴 `axis` 旋转 `radians` 弧度的矩阵（右手定则）
+    /// ```
+    /// use vector3::{matrix::Mat3, Vec3};
+    /// let m = Mat3::rotation(Vec3::from_z(1.), std::f64::consts::FRAC_PI_2);
+    /// assert_eq!(m * Vec3::from_x(1.), Vec3::from_y(1.));
+    /// ```
+    pub fn rotation(axis: Vec3, radians: f64) -> Self {
+        // Rodrigues' rotation formula.
+        let Vec3 { x, y, z } = axis.normalize();
+        let (sin, cos) = radians.sin_cos();
+        let k = 1. - cos;
+
+        Self { m: [
+            [cos + x*x*k, x*y*k - z*sin, x*z*k + y*sin],
+            [y*x*k + z*sin, cos + y*y*k, y*z*k - x*sin],
+            [z*x*k - y*sin, z*y*k + x*sin, cos + z*z*k]
+        ] }
+    }
+
+    pub fn row(&self, i: usize) -> Vec3 {
+        Vec3::new(self.m[i][0], self.m[i][1], self.m[i][2])
+    }
+
+    pub fn col(&self, j: usize) -> Vec3 {
+        Vec3::new(self.m[0][j], self.m[1][j], self.m[2][j])
+    }
+
+    /// 转置矩阵
+    pub fn transpose(&self) -> Self {
+        Self::from_rows(self.col(0), self.col(1), self.col(2))
+    }
+
+    /// 行列式
+    /// ```
+    /// use vector3::{matrix::Mat3, Vec3};
+    /// assert_eq!(Mat3::scale(Vec3::new(2., 3., 4.)).determinant(), 24.);
+    /// ```
+    pub fn determinant(&self) -> f64 {
+        self.row(0).dot(&self.row(1).cross(&self.row(2)))
+    }
+
+    /// 逆矩阵，矩阵奇异或含有 NaN、无穷大时返回 `None`
+    pub fn inverse(&self) -> Option<Self> {
+        let (r0, r1, r2) = (self.row(0), self.row(1), self.row(2));
+        let det = r0.dot(&r1.cross(&r2));
+        // A NaN determinant, e.g. from a rotation about the zero vector, fails every comparison.
+        if !det.is_finite() || det.abs() < 1e-12 {
+            return None;
+        }
+
+        // The columns of the inverse are the cross products of the rows, over the determinant.
+        Some(Self::from_cols(r1.cross(&r2), r2.cross(&r0), r0.cross(&r1)) * det.recip())
+    }
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Debug for Mat3 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mat3({:?})", self.m)
+    }
+}
+
+impl Index<(usize, usize)> for Mat3 {
+    type Output = f64;
+
+    fn index(&self, (i, j): (usize, usize)) -> &Self::Output {
+        &self.m[i][j]
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut m = [[0.; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.row(i).dot(&rhs.col(j));
+            }
+        }
+
+        Self { m }
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        Vec3::new(self.row(0).dot(&rhs), self.row(1).dot(&rhs), self.row(2).dot(&rhs))
+    }
+}
+
+impl Mul<f64> for Mat3 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self { m: self.m.map(|row| row.map(|value| value * rhs)) }
+    }
+}
+
+/// 4x4 齐次变换矩阵，按行存储
+#[derive(Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f64; 4]; 4]
+}
+
+impl Mat4 {
+    /// 单位矩阵
+    pub fn identity() -> Self {
+        Self::from_mat3(Mat3::identity())
+    }
+
+    /// 由线性部分构造，平移为零
+    pub fn from_mat3(linear: Mat3) -> Self {
+        Self::from_affine(linear, Vec3::zero())
+    }
+
+    /// 由线性部分和平移构造仿射变换
+    pub fn from_affine(linear: Mat3, translation: Vec3) -> Self {
+        let [r0, r1, r2] = linear.m;
+        Self { m: [
+            [r0[0], r0[1], r0[2], translation.x],
+            [r1[0], r1[1], r1[2], translation.y],
+            [r2[0], r2[1], r2[2], translation.z],
+            [0., 0., 0., 1.]
+        ] }
+    }
+
+    /// 平移矩阵
+    /// ```
+    /// use vector3::{matrix::Mat4, Vec3};
+    /// let m = Mat4::translation(Vec3::new(1., 2., 3.));
+    /// assert_eq!(m.transform_point(Vec3::zero()), Vec3::new(1., 2., 3.));
+    /// assert_eq!(m.transform_vector(Vec3::from_x(1.)), Vec3::from_x(1.));
+    /// ```
+    pub fn translation(offset: Vec3) -> Self {
+        Self::from_affine(Mat3::identity(), offset)
+    }
+
+    /// 缩放矩阵
+    pub fn scale(factors: Vec3) -> Self {
+        Self::from_mat3(Mat3::scale(factors))
+    }
+
+    /// 绕任意轴旋转 `radians` 弧度的矩阵
+    pub fn rotation(axis: Vec3, radians: f64) -> Self {
+        Self::from_mat3(Mat3::rotation(axis, radians))
+    }
+
+    /// 左上角 3x3 的线性部分
+    pub fn linear(&self) -> Mat3 {
+        Mat3 { m: [0, 1, 2].map(|i| [self.m[i][0], self.m[i][1], self.m[i][2]]) }
+    }
+
+    /// 平移部分
+    pub fn translation_part(&self) -> Vec3 {
+        Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    /// 转置矩阵
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+
+        Self { m }
+    }
+
+    /// 仿射变换的逆矩阵，线性部分奇异时返回 `None`
+    /// ```
+    /// use vector3::{matrix::Mat4, Vec3};
+    /// let m = Mat4::translation(Vec3::new(1., 2., 3.)) * Mat4::scale(Vec3::new(2., 4., 8.));
+    /// let p = Vec3::new(5., 6., 7.);
+    /// assert_eq!(m.inverse().unwrap().transform_point(m.transform_point(p)), p);
+    /// ```
+    pub fn inverse(&self) -> Option<Self> {
+        // The inverse of [L | t] is [L^-1 | -L^-1 t].
+        let linear_inverse = self.linear().inverse()?;
+        Some(Self::from_affine(linear_inverse, -(linear_inverse * self.translation_part())))
+    }
+
+    /// 变换点（齐次坐标 w = 1）
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.linear() * p + self.translation_part()
+    }
+
+    /// 变换方向向量（齐次坐标 w = 0），不受平移影响
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.linear() * v
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Debug for Mat4 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mat4({:?})", self.m)
+    }
+}
+
+impl Index<(usize, usize)> for Mat4 {
+    type Output = f64;
+
+    fn index(&self, (i, j): (usize, usize)) -> &Self::Output {
+        &self.m[i][j]
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+        }
+
+        Self { m }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::assert_f64_eq;
+
+    use super::*;
+
+    fn assert_mat3_eq(a: Mat3, b: Mat3) {
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((a[(i, j)] - b[(i, j)]).abs() < 1e-12, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    fn assert_vec3_near(a: Vec3, b: Vec3) {
+        assert!((a - b).norm() < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_mat3_mul() {
+        let a = Mat3::from_rows(Vec3::new(1., 2., 3.), Vec3::new(4., 5., 6.), Vec3::new(7., 8., 10.));
+        assert_mat3_eq(a * Mat3::identity(), a);
+        assert_eq!(a * Vec3::new(1., 0., -1.), Vec3::new(-2., -2., -3.));
+        assert_f64_eq!(a.determinant(), -3.);
+    }
+
+    #[test]
+    fn test_mat3_inverse() {
+        let a = Mat3::from_rows(Vec3::new(1., 2., 3.), Vec3::new(4., 5., 6.), Vec3::new(7., 8., 10.));
+        assert_mat3_eq(a * a.inverse().unwrap(), Mat3::identity());
+        assert!(Mat3::scale(Vec3::new(1., 0., 1.)).inverse().is_none());
+        assert!(Mat3::rotation(Vec3::zero(), 0.5).inverse().is_none());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let axis = Vec3::new(1., 1., 1.);
+        let r = Mat3::rotation(axis, 2. * std::f64::consts::FRAC_PI_3);
+        // A third of a turn about (1, 1, 1) cycles the coordinate axes.
+        assert_vec3_near(r * Vec3::from_x(1.), Vec3::from_y(1.));
+        assert_vec3_near(r * axis, axis);
+        assert_mat3_eq(r.transpose(), r.inverse().unwrap());
+        assert!((r.determinant() - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mat4_compose() {
+        let t = Mat4::translation(Vec3::new(1., 0., 0.));
+        let r = Mat4::rotation(Vec3::from_y(1.), std::f64::consts::FRAC_PI_2);
+        let s = Mat4::scale(Vec3::isotropic(2.));
+        // Scale first, then rotate, then translate.
+        let m = t * r * s;
+        let p = m.transform_point(Vec3::from_x(1.));
+        assert_vec3_near(p, Vec3::new(1., 0., -2.));
+        assert_vec3_near(m.inverse().unwrap().transform_point(p), Vec3::from_x(1.));
+        assert_eq!(m.transpose().transpose(), m);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::Vec3;
+
+/// 右手正交归一基（orthonormal basis），`w` 轴沿给定方向
+#[derive(Debug, Clone, Copy)]
+pub struct ONB {
+    axis: [Vec3; 3]
+}
+
+impl ONB {
+    /// 以 `n` 为 `w` 轴构造正交归一基
+    /// ```
+    /// use vector3::{onb::ONB, Vec3};
+    /// let uvw = ONB::new(&Vec3::new(0., 2., 0.));
+    /// assert_eq!(uvw.w(), Vec3::from_y(1.));
+    /// assert_eq!(uvw.u().dot(&uvw.w()), 0.);
+    /// assert_eq!(uvw.u().cross(&uvw.v()), uvw.w());
+    /// assert_eq!(uvw.transform(Vec3::from_z(3.)), Vec3::from_y(3.));
+    /// ```
+    pub fn new(n: &Vec3) -> Self {
+        let w = n.normalize();
+        let a = if w.x.abs() > 0.9 { Vec3::from_y(1.) } else { Vec3::from_x(1.) };
+        let v = w.cross(&a).normalize();
+        let u = v.cross(&w);
+
+        Self { axis: [u, v, w] }
+    }
+
+    /// 以 `n` 为 `w` 轴、`tangent` 在 `w` 的垂直平面上的投影为 `u` 轴构造正交归一基，
+    /// 投影退化时与 [`ONB::new`] 相同
+    /// ```
+    /// use vector3::{onb::ONB, Vec3};
+    /// let uvw = ONB::from_tangent(&Vec3::from_z(1.), &Vec3::new(2., 0., 1.));
+    /// assert_eq!(uvw.u(), Vec3::from_x(1.));
+    /// assert_eq!(uvw.v(), Vec3::from_y(1.));
+    /// assert_eq!(uvw.local(Vec3::new(1., 2., 3.)), Vec3::new(1., 2., 3.));
+    /// ```
+    pub fn from_tangent(n: &Vec3, tangent: &Vec3) -> Self {
+        let w = n.normalize();
+        let u = *tangent - tangent.dot(&w) * w;
+        if u.squared() < 1e-16 {
+            return Self::new(n);
+        }
+        let u = u.normalize();
+        let v = w.cross(&u);
+
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.axis[0]
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.axis[1]
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.axis[2]
+    }
+
+    /// 把基坐标下的向量转换为世界坐标
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        v.x * self.axis[0] + v.y * self.axis[1] + v.z * self.axis[2]
+    }
+
+    /// 把世界坐标下的向量转换为基坐标，是 [`ONB::transform`] 的逆变换
+    pub fn local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(&self.axis[0]), v.dot(&self.axis[1]), v.dot(&self.axis[2]))
+    }
+}